@@ -0,0 +1,185 @@
+//! Derive macro for the `MmapRecord` trait of `mmap_buffer`, which is
+//! re-exported there along with the trait, see its documentation.
+//!
+//! The struct definition is parsed by hand to keep the macro free of
+//! dependencies, so it only accepts what records need: non-generic
+//! `#[repr(C)]` structs with named fields.
+
+use std::iter::Peekable;
+
+use proc_macro::{token_stream, Delimiter, TokenStream, TokenTree};
+
+type Tokens = Peekable<token_stream::IntoIter>;
+
+/// Implement `MmapRecord` for a `#[repr(C)]` struct with named fields,
+/// fingerprinting the size and alignment of the struct, and the name,
+/// type, offset and size of each field
+#[proc_macro_derive(MmapRecord)]
+pub fn derive_mmap_record(input: TokenStream) -> TokenStream {
+    match parse(input) {
+        Ok(record) => expand(&record),
+        Err(message) => format!("::core::compile_error!({message:?});")
+            .parse()
+            .unwrap(),
+    }
+}
+
+/// The parts of a struct definition which make up its layout
+struct Record {
+    name: String,
+    fields: Vec<Field>,
+}
+
+struct Field {
+    /// As written, which may be a raw identifier
+    name: String,
+    /// As written, for use in the generated code
+    ty: String,
+}
+
+fn parse(input: TokenStream) -> Result<Record, String> {
+    let mut tokens = input.into_iter().peekable();
+
+    let mut repr_c = false;
+    skip_attributes(&mut tokens, |attribute| repr_c |= is_repr_c(attribute));
+
+    match tokens.next() {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "struct" => {}
+        _ => return Err("`MmapRecord` can only be derived for structs".into()),
+    }
+    let name = match tokens.next() {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err("expected the name of the struct".into()),
+    };
+
+    let body = match tokens.next() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group.stream(),
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            return Err("`MmapRecord` can't be derived for generic structs".into())
+        }
+        _ => return Err("`MmapRecord` can only be derived for structs with named fields".into()),
+    };
+
+    if !repr_c {
+        return Err(
+            "`MmapRecord` requires `#[repr(C)]`, so that the layout only depends on the fields"
+                .into(),
+        );
+    }
+
+    Ok(Record {
+        name,
+        fields: parse_fields(body)?,
+    })
+}
+
+fn parse_fields(body: TokenStream) -> Result<Vec<Field>, String> {
+    let mut tokens = body.into_iter().peekable();
+    let mut fields = Vec::new();
+
+    while tokens.peek().is_some() {
+        skip_attributes(&mut tokens, |_| {});
+
+        let name = match tokens.next() {
+            Some(TokenTree::Ident(ident)) => ident.to_string(),
+            _ => return Err("expected the name of a field".into()),
+        };
+        match tokens.next() {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == ':' => {}
+            _ => return Err(format!("expected `:` after field `{name}`")),
+        }
+
+        // The type runs up to the next comma outside of angle brackets
+        let mut ty = Vec::new();
+        let mut depth = 0usize;
+        for token in tokens.by_ref() {
+            if let TokenTree::Punct(punct) = &token {
+                match punct.as_char() {
+                    ',' if depth == 0 => break,
+                    '<' => depth += 1,
+                    '>' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            ty.push(token);
+        }
+
+        fields.push(Field {
+            name,
+            ty: TokenStream::from_iter(ty).to_string(),
+        });
+    }
+
+    Ok(fields)
+}
+
+/// Skip any attributes and visibility, passing the contents of each
+/// attribute to `attribute`
+fn skip_attributes(tokens: &mut Tokens, mut attribute: impl FnMut(TokenStream)) {
+    loop {
+        match tokens.peek() {
+            Some(TokenTree::Punct(punct)) if punct.as_char() == '#' => {
+                tokens.next();
+                if let Some(TokenTree::Group(group)) = tokens.next() {
+                    attribute(group.stream());
+                }
+            }
+            Some(TokenTree::Ident(ident)) if ident.to_string() == "pub" => {
+                tokens.next();
+
+                // As in `pub(crate)`
+                if matches!(tokens.peek(), Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis)
+                {
+                    tokens.next();
+                }
+            }
+            _ => break,
+        }
+    }
+}
+
+/// Whether an attribute is `repr(C)`, possibly along with other
+/// representation hints like `align(8)`
+fn is_repr_c(attribute: TokenStream) -> bool {
+    let mut tokens = attribute.into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(ident)), Some(TokenTree::Group(hints)))
+            if ident.to_string() == "repr" =>
+        {
+            hints
+                .stream()
+                .into_iter()
+                .any(|hint| matches!(hint, TokenTree::Ident(ident) if ident.to_string() == "C"))
+        }
+        _ => false,
+    }
+}
+
+fn expand(record: &Record) -> TokenStream {
+    let mut layout = String::from(
+        "let hash = ::mmap_buffer::__private::SEED;
+        let hash = ::mmap_buffer::__private::hash_usize(hash, ::core::mem::size_of::<Self>());
+        let hash = ::mmap_buffer::__private::hash_usize(hash, ::core::mem::align_of::<Self>());",
+    );
+
+    for Field { name, ty } in &record.fields {
+        // Spacing within the type is up to the compiler, so leave it out
+        let ty_name: String = ty.chars().filter(|c| !c.is_whitespace()).collect();
+        layout += &format!(
+            "let hash = ::mmap_buffer::__private::hash_str(hash, {:?});
+            let hash = ::mmap_buffer::__private::hash_str(hash, {ty_name:?});
+            let hash = ::mmap_buffer::__private::hash_usize(hash, ::core::mem::offset_of!(Self, {name}));
+            let hash = ::mmap_buffer::__private::hash_usize(hash, ::core::mem::size_of::<{ty}>());",
+            name.trim_start_matches("r#"),
+        );
+    }
+
+    format!(
+        "impl ::mmap_buffer::MmapRecord for {} {{
+            const LAYOUT: u64 = {{ {layout} hash }};
+        }}",
+        record.name
+    )
+    .parse()
+    .unwrap()
+}
@@ -0,0 +1,117 @@
+use std::{ops::Deref, path::Path, sync::Arc};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError, ReadOnlyBuffer};
+
+/// A read-only buffer which is cheap to clone and can be shared across
+/// threads, like an `Arc<[T]>` whose elements live in a file. The mapping,
+/// and the lock on its file, are released once the last clone is dropped.
+///
+/// Made by loading a file read-only with [`ArcBuffer::load`], or from an
+/// existing [`BackedBuffer`] or [`ReadOnlyBuffer`] with `From`. A
+/// [`BackedBuffer`] which is shared this way can no longer be written to,
+/// and runs its drop options, such as flushing, along with the last clone.
+pub struct ArcBuffer<T: Pod> {
+    inner: Arc<Inner<T>>,
+}
+
+/// Whichever buffer owns the mapping
+enum Inner<T: Pod> {
+    ReadOnly(ReadOnlyBuffer<T>),
+    Backed(BackedBuffer<T>),
+}
+
+impl<T: Pod> ArcBuffer<T> {
+    /// Load an existing file read-only, see [`ReadOnlyBuffer::load`]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Ok(ReadOnlyBuffer::load(path)?.into())
+    }
+
+    /// Number of clones sharing the mapping, including this one
+    pub fn strong_count(this: &Self) -> usize {
+        Arc::strong_count(&this.inner)
+    }
+
+    /// Whether both are clones sharing the same mapping
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(&this.inner, &other.inner)
+    }
+}
+
+impl<T: Pod> Clone for ArcBuffer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: Pod> From<ReadOnlyBuffer<T>> for ArcBuffer<T> {
+    fn from(buffer: ReadOnlyBuffer<T>) -> Self {
+        Self {
+            inner: Arc::new(Inner::ReadOnly(buffer)),
+        }
+    }
+}
+
+impl<T: Pod> From<BackedBuffer<T>> for ArcBuffer<T> {
+    fn from(buffer: BackedBuffer<T>) -> Self {
+        Self {
+            inner: Arc::new(Inner::Backed(buffer)),
+        }
+    }
+}
+
+impl<T: Pod> Deref for ArcBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        match &*self.inner {
+            Inner::ReadOnly(buffer) => buffer,
+            Inner::Backed(buffer) => buffer,
+        }
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for ArcBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ArcBuffer, BackedBuffer};
+    use std::{error::Error, path::Path, thread};
+
+    #[test]
+    fn share_across_threads() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u64>::new(1000, file_path.clone())?;
+        buf.iter_mut().enumerate().for_each(|(i, x)| *x = i as u64);
+        let shared = ArcBuffer::from(buf);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || shared.iter().sum::<u64>())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 999 * 1000 / 2);
+        }
+        assert_eq!(ArcBuffer::strong_count(&shared), 1);
+        drop(shared);
+
+        let shared = ArcBuffer::<u64>::load(&file_path)?;
+        let other = shared.clone();
+        assert!(ArcBuffer::ptr_eq(&shared, &other));
+        assert_eq!(other[999], 999);
+
+        Ok(())
+    }
+}
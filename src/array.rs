@@ -0,0 +1,134 @@
+use std::{
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+/// A [`BackedBuffer`] of exactly `N` elements, which derefs to `[T; N]`.
+/// The length is checked once when the buffer is loaded, so indexing with
+/// constants and loops up to `N` need no bounds checks.
+///
+/// Options such as headers or checksums are set by building the buffer
+/// with [`BackedBufferBuilder`](crate::BackedBufferBuilder) and converting
+/// it with [`from_buffer`](Self::from_buffer).
+#[derive(Debug)]
+pub struct BackedArray<T: Pod, const N: usize> {
+    buffer: BackedBuffer<T>,
+}
+
+impl<T: Pod, const N: usize> BackedArray<T, N> {
+    /// Create a new array of zeroes at the given path. Any existing file is
+    /// truncated.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Ok(Self {
+            buffer: BackedBuffer::new(N, path)?,
+        })
+    }
+
+    /// Load an array from an existing path, failing with
+    /// [`MmapBufferError::LengthMismatch`] unless the file holds exactly
+    /// `N` elements.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Self::from_buffer(BackedBuffer::load(path)?)
+    }
+
+    /// Turn a buffer holding exactly `N` elements into an array, failing
+    /// with [`MmapBufferError::LengthMismatch`] otherwise
+    pub fn from_buffer(buffer: BackedBuffer<T>) -> Result<Self, MmapBufferError> {
+        if buffer.len() != N {
+            return Err(MmapBufferError::LengthMismatch {
+                expected: N,
+                actual: buffer.len(),
+            });
+        }
+
+        Ok(Self { buffer })
+    }
+
+    /// The underlying buffer
+    pub fn into_buffer(self) -> BackedBuffer<T> {
+        self.buffer
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> Option<&Path> {
+        self.buffer.path()
+    }
+
+    /// Synchronously flush the array to the file, see
+    /// [`BackedBuffer::flush`]
+    pub fn flush(&self) -> Result<(), MmapBufferError> {
+        self.buffer.flush()
+    }
+
+    /// Start flushing the array without waiting, see
+    /// [`BackedBuffer::flush_async`]
+    pub fn flush_async(&self) -> Result<(), MmapBufferError> {
+        self.buffer.flush_async()
+    }
+}
+
+impl<T: Pod, const N: usize> Deref for BackedArray<T, N> {
+    type Target = [T; N];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // Can't fail, the length was checked when the array was made and
+        // only the buffer could change it
+        self.buffer[..].try_into().unwrap()
+    }
+}
+
+impl<T: Pod, const N: usize> DerefMut for BackedArray<T, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        (&mut self.buffer[..]).try_into().unwrap()
+    }
+}
+
+impl<T: Pod, const N: usize> AsRef<[T; N]> for BackedArray<T, N> {
+    fn as_ref(&self) -> &[T; N] {
+        self
+    }
+}
+
+impl<T: Pod, const N: usize> AsMut<[T; N]> for BackedArray<T, N> {
+    fn as_mut(&mut self) -> &mut [T; N] {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedArray, MmapBufferError};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn fixed_length() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut array = BackedArray::<u32, 16>::new(file_path.clone())?;
+        for (i, x) in array.iter_mut().enumerate() {
+            *x = i as u32;
+        }
+        let [first, .., last] = *array;
+        assert_eq!((first, last), (0, 15));
+        drop(array);
+
+        assert_eq!(BackedArray::<u32, 16>::load(&file_path)?[15], 15);
+        let err = BackedArray::<u32, 8>::load(&file_path).unwrap_err();
+        assert!(matches!(
+            err,
+            MmapBufferError::LengthMismatch {
+                expected: 8,
+                actual: 16
+            }
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::atomic::{
+    AtomicI16, AtomicI32, AtomicI8, AtomicIsize, AtomicU16, AtomicU32, AtomicU8, AtomicUsize,
+};
+#[cfg(target_has_atomic = "64")]
+use std::sync::atomic::{AtomicI64, AtomicU64};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Atomic integer types which have the same in-memory representation as
+/// their plain integer counterparts, and so can be viewed directly in a
+/// mapping. This trait is sealed.
+pub trait AtomicElement: private::Sealed + Sync {}
+
+macro_rules! atomic_element {
+    ($($atomic:ty),*) => {
+        $(
+            impl private::Sealed for $atomic {}
+            impl AtomicElement for $atomic {}
+        )*
+    };
+}
+
+atomic_element!(
+    AtomicU8,
+    AtomicU16,
+    AtomicU32,
+    AtomicUsize,
+    AtomicI8,
+    AtomicI16,
+    AtomicI32,
+    AtomicIsize
+);
+
+#[cfg(target_has_atomic = "64")]
+atomic_element!(AtomicU64, AtomicI64);
+
+impl<T: Pod> BackedBuffer<T> {
+    /// View the contents of the buffer as a slice of atomics, for example
+    /// `&[AtomicU64]`, so that several processes mapping the same file can
+    /// share lock-free counters and flags. Fails if the buffer isn't
+    /// suitably sized and aligned for `A`.
+    ///
+    /// This borrows the buffer mutably, so the plain `&[T]` view can't be
+    /// used to observe concurrent atomic writes.
+    pub fn as_atomic_slice<A: AtomicElement>(&mut self) -> Result<&[A], MmapBufferError> {
+        self.mark_dirty();
+        let len_bytes = self.len * std::mem::size_of::<T>();
+
+        // The pointer comes from a mutable borrow of the mapping, since the
+        // atomics are written through even though they are shared
+        // SAFETY: the elements lie within the mapping
+        let ptr = unsafe { self.mmap.as_mut_ptr().add(self.offset) };
+
+        if !(ptr as usize).is_multiple_of(std::mem::align_of::<A>()) {
+            return Err(MmapBufferError::Alignment);
+        }
+
+        if !len_bytes.is_multiple_of(std::mem::size_of::<A>()) {
+            return Err(MmapBufferError::SizeMismatch {
+                file_size: len_bytes,
+                element_size: std::mem::size_of::<A>(),
+            });
+        }
+
+        // SAFETY: atomic integers have the same size and bit validity as the
+        // corresponding integers, we checked size and alignment above, and
+        // the mutable borrow excludes any non-atomic view within this process
+        Ok(unsafe {
+            std::slice::from_raw_parts(ptr as *const A, len_bytes / std::mem::size_of::<A>())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BackedBuffer;
+    use std::{
+        error::Error,
+        path::Path,
+        sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    };
+
+    #[test]
+    fn atomic_views() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u64>::new(4, file_path.clone())?;
+        let counters = buf.as_atomic_slice::<AtomicU64>()?;
+        counters[1].fetch_add(5, Ordering::SeqCst);
+        assert_eq!(buf[1], 5);
+        drop(buf);
+
+        let mut buf = BackedBuffer::<u8>::load(file_path)?;
+        buf.shrink(30);
+        assert!(buf.as_atomic_slice::<AtomicU32>().is_err());
+
+        Ok(())
+    }
+}
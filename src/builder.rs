@@ -0,0 +1,1422 @@
+use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+#[cfg(target_os = "linux")]
+use std::os::fd::FromRawFd;
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+
+use bytemuck::{try_cast_slice, Pod};
+use fs2::FileExt;
+use memmap2::{MmapMut, MmapOptions};
+
+#[cfg(unix)]
+use crate::guard::{self, GuardPages};
+use crate::{
+    byte_size, checksum,
+    dirty::{page_size, DirtyPages},
+    header::{self, Header},
+    pid_file::PidFile,
+    BackedBuffer, MmapBufferError, MmapRecord, ReadOnlyBuffer,
+};
+#[cfg(target_os = "linux")]
+use crate::{numa, NumaPolicy};
+
+/// Which advisory lock to take on the backing file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LockMode {
+    /// No other buffer may lock the file while this one is alive
+    #[default]
+    Exclusive,
+    /// Other shared locks may coexist, but exclusive locks are kept out
+    Shared,
+    /// Don't take any advisory lock. Keeping other buffers and programs
+    /// from modifying the file concurrently is the caller's responsibility.
+    Unlocked,
+}
+
+/// What to do when loading a file whose size isn't a whole number of
+/// elements
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SizePolicy {
+    /// Fail with [`MmapBufferError::SizeMismatch`]
+    #[default]
+    Exact,
+    /// Cut the file down to the largest whole number of elements. Files
+    /// which can't be written, e.g. when loading copy-on-write, are left
+    /// alone and treated like [`Prefix`](Self::Prefix).
+    Truncate,
+    /// Leave the file alone and only expose the whole elements at its start
+    Prefix,
+}
+
+/// Whether to flush the mapping when a buffer is dropped
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlushOnDrop {
+    /// Leave writing back changes to the kernel, whenever it gets to it
+    #[default]
+    None,
+    /// Start writing back changes, without waiting for them to complete,
+    /// see [`BackedBuffer::flush_async`]
+    FlushAsync,
+    /// Write back changes and wait for them to complete, see
+    /// [`BackedBuffer::flush`]
+    FlushSync,
+}
+
+/// Whether to wipe the buffer when it is dropped, see
+/// [`BackedBufferBuilder::zeroize_on_drop`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ZeroizeOnDrop {
+    /// Leave the contents alone
+    #[default]
+    None,
+    /// Overwrite the elements with zeroes, see [`BackedBuffer::zeroize`]
+    Wipe,
+    /// Overwrite the elements with zeroes, then punch a hole over them in
+    /// the file on Linux, so the filesystem releases the blocks which held
+    /// them
+    WipeAndPunchHoles,
+}
+
+/// Whether to keep a dirty flag in the header, and what to do when loading
+/// a file whose flag is still set, see [`BackedBufferBuilder::dirty_flag`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DirtyFlag {
+    /// Leave the flag alone
+    #[default]
+    Off,
+    /// Keep the flag, and load files left dirty anyway, which
+    /// [`BackedBuffer::unclean_shutdown`] then reports
+    Report,
+    /// Keep the flag, and fail with [`MmapBufferError::UncleanShutdown`]
+    /// when loading a file left dirty
+    Refuse,
+}
+
+/// What to do when a loaded file turns out to be damaged, i.e. its size
+/// isn't a whole number of elements or it doesn't match its checksum, see
+/// [`BackedBufferBuilder::on_corruption`]. Repairs are written back to the
+/// file, except when loading copy-on-write.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnCorruption {
+    /// Panic with the error
+    Panic,
+    /// Fail with the error, like [`SizePolicy`] decides for the size
+    #[default]
+    Error,
+    /// Keep only the elements before the first damaged one. Trailing bytes
+    /// are cut off like with [`SizePolicy::Truncate`], and a checksum
+    /// mismatch without page checksums leaves the buffer empty, since no
+    /// element is known to be intact.
+    TruncateToValidPrefix,
+    /// Overwrite the damaged elements with zeroes. Trailing bytes are
+    /// padded out to a whole element, and a checksum mismatch without page
+    /// checksums zeroes every element.
+    ZeroFill,
+}
+
+/// How long to wait for a contended advisory lock
+#[derive(Clone, Copy, Debug)]
+enum LockWait {
+    Fail,
+    Forever,
+    Until(Duration),
+}
+
+/// Configures how a [`BackedBuffer`] is created or loaded. The
+/// constructors on [`BackedBuffer`] use the default options.
+///
+/// ```
+/// use mmap_buffer::BackedBufferBuilder;
+///
+/// let buf = BackedBufferBuilder::new()
+///     .huge_pages(true)
+///     .anonymous::<f64>(1 << 20)
+///     .unwrap();
+/// assert_eq!(buf.len(), 1 << 20);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BackedBufferBuilder {
+    populate: bool,
+    lock: LockMode,
+    lock_wait: LockWait,
+    lock_file: bool,
+    pid_file: bool,
+    copy_on_write: bool,
+    huge_pages: bool,
+    lock_in_memory: bool,
+    guard_pages: bool,
+    #[cfg(target_os = "linux")]
+    numa_policy: Option<NumaPolicy>,
+    truncate: bool,
+    create_new: bool,
+    preallocate: bool,
+    delete_on_drop: bool,
+    flush_on_drop: FlushOnDrop,
+    fsync_on_drop: bool,
+    zeroize_on_drop: ZeroizeOnDrop,
+    dirty_flag: DirtyFlag,
+    size_policy: SizePolicy,
+    on_corruption: OnCorruption,
+    header: bool,
+    /// Layout fingerprint recorded in the header instead of the hash of
+    /// the type name, see [`record`](Self::record)
+    layout: Option<u64>,
+    align: usize,
+    checksum: bool,
+    page_checksums: bool,
+}
+
+impl Default for BackedBufferBuilder {
+    fn default() -> Self {
+        Self {
+            populate: false,
+            lock: LockMode::Exclusive,
+            lock_wait: LockWait::Fail,
+            lock_file: false,
+            pid_file: false,
+            copy_on_write: false,
+            huge_pages: false,
+            lock_in_memory: false,
+            guard_pages: false,
+            #[cfg(target_os = "linux")]
+            numa_policy: None,
+            truncate: true,
+            create_new: false,
+            preallocate: false,
+            delete_on_drop: false,
+            flush_on_drop: FlushOnDrop::None,
+            fsync_on_drop: false,
+            zeroize_on_drop: ZeroizeOnDrop::None,
+            dirty_flag: DirtyFlag::Off,
+            size_policy: SizePolicy::Exact,
+            on_corruption: OnCorruption::Error,
+            header: false,
+            layout: None,
+            align: 1,
+            checksum: false,
+            page_checksums: false,
+        }
+    }
+}
+
+impl BackedBufferBuilder {
+    /// Create a builder with the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to fault the whole mapping into memory up front, rather than
+    /// lazily as pages are touched (the default). Populating makes opening
+    /// a large file slow, but avoids page faults later on.
+    pub fn populate(&mut self, enable: bool) -> &mut Self {
+        self.populate = enable;
+        self
+    }
+
+    /// Which advisory lock to take on the file, defaults to
+    /// [`LockMode::Exclusive`].
+    pub fn lock(&mut self, mode: LockMode) -> &mut Self {
+        self.lock = mode;
+        self
+    }
+
+    /// Wait for a contended lock instead of failing immediately with
+    /// [`MmapBufferError::Locked`]. With a timeout, retries with backoff
+    /// until the deadline passes, otherwise blocks indefinitely.
+    pub fn wait_for_lock(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.lock_wait = match timeout {
+            Some(timeout) => LockWait::Until(timeout),
+            None => LockWait::Forever,
+        };
+        self
+    }
+
+    /// Take the advisory lock on a separate `<path>.lock` file, created if
+    /// needed, instead of the file itself. Tools which know nothing about
+    /// the lock, like backup or checksumming tools, can then open the file
+    /// without being blocked by it, or blocking it. The lock file is left in
+    /// place when the buffer is dropped, unless the buffer is deleted.
+    pub fn lock_file(&mut self, enable: bool) -> &mut Self {
+        self.lock_file = enable;
+        self
+    }
+
+    /// Record the process holding the buffer in a `<path>.pid` file while it
+    /// is loaded, failing with [`MmapBufferError::Locked`] if it exists. A
+    /// file left behind by a process on this host which no longer exists is
+    /// replaced automatically, others can be removed with
+    /// [`BackedBuffer::force_unlock`].
+    ///
+    /// Unlike advisory locks, pid files work on network filesystems, where
+    /// a crashed client can leave its lock held on the server. Combine with
+    /// [`LockMode::Unlocked`] there. Pid files are always exclusive, and
+    /// don't apply to read-only buffers.
+    pub fn pid_file(&mut self, enable: bool) -> &mut Self {
+        self.pid_file = enable;
+        self
+    }
+
+    /// Map loaded files privately, so writes are never carried through to
+    /// the file, see [`BackedBuffer::load_cow`]. The file is opened without
+    /// write permissions.
+    pub fn copy_on_write(&mut self, enable: bool) -> &mut Self {
+        self.copy_on_write = enable;
+        self
+    }
+
+    /// Request that the mapping be backed by huge pages, which cut down on
+    /// TLB misses over large buffers.
+    ///
+    /// On Linux, anonymous buffers take explicit huge pages from the
+    /// kernel's hugetlb pool (see `/proc/sys/vm/nr_hugepages`), as with
+    /// `MAP_HUGETLB`, and their capacity is rounded up to a whole number of
+    /// huge pages. When the pool is empty, and for file-backed mappings,
+    /// which can't use the pool, the mapping is only marked as eligible for
+    /// transparent huge pages with `madvise`, a hint which the kernel mostly
+    /// ignores for files. [`BackedBuffer::uses_huge_pages`] tells which one
+    /// happened. Ignored on other platforms.
+    pub fn huge_pages(&mut self, enable: bool) -> &mut Self {
+        self.huge_pages = enable;
+        self
+    }
+
+    /// Pin the pages of the mapping in RAM as soon as it is created, see
+    /// [`BackedBuffer::lock_in_memory`]
+    #[cfg(unix)]
+    pub fn lock_in_memory(&mut self, enable: bool) -> &mut Self {
+        self.lock_in_memory = enable;
+        self
+    }
+
+    /// Surround the mapping with inaccessible guard pages, so that reading
+    /// or writing just past either end of it, e.g. through an off-by-one
+    /// in unsafe code, faults instead of touching neighbouring memory.
+    /// Fails with [`io::ErrorKind::AddrInUse`] if the address space next to
+    /// the mapping is taken. Guards are put up again around the new mapping
+    /// by [`resize`](BackedBuffer::resize) and
+    /// [`remap`](BackedBuffer::remap).
+    #[cfg(unix)]
+    pub fn guard_pages(&mut self, enable: bool) -> &mut Self {
+        self.guard_pages = enable;
+        self
+    }
+
+    /// Place the pages of the mapping on NUMA nodes according to the given
+    /// policy, rather than on whichever node first touches them. See also
+    /// [`BackedBuffer::move_to_node`].
+    #[cfg(target_os = "linux")]
+    pub fn numa_policy(&mut self, policy: NumaPolicy) -> &mut Self {
+        self.numa_policy = Some(policy);
+        self
+    }
+
+    /// Whether [`create`](Self::create) should discard the contents of an
+    /// existing file (the default). When disabled, existing contents are
+    /// kept and the file is zero-extended or cut to the new capacity.
+    pub fn truncate(&mut self, enable: bool) -> &mut Self {
+        self.truncate = enable;
+        self
+    }
+
+    /// Make [`create`](Self::create) fail if a file already exists at the
+    /// path, rather than reusing it.
+    pub fn create_new(&mut self, enable: bool) -> &mut Self {
+        self.create_new = enable;
+        self
+    }
+
+    /// Reserve disk space for the whole file when creating it, so running
+    /// out of space fails up front instead of with a `SIGBUS` on a later
+    /// write. By default the file is extended sparsely, which is nearly
+    /// instant even for very large buffers.
+    pub fn preallocate(&mut self, enable: bool) -> &mut Self {
+        self.preallocate = enable;
+        self
+    }
+
+    /// Delete the file, along with any checksum sidecar, when the buffer is
+    /// dropped, for scratch buffers which shouldn't outlive the program.
+    /// Use [`BackedBuffer::persist`] to keep the file after all.
+    pub fn delete_on_drop(&mut self, enable: bool) -> &mut Self {
+        self.delete_on_drop = enable;
+        self
+    }
+
+    /// Whether to wipe the elements when the buffer is dropped, for key
+    /// material and personal data, defaults to [`ZeroizeOnDrop::None`].
+    /// In a file-backed buffer the zeroes are written through to the file,
+    /// a copy-on-write buffer only wipes its private copy. Errors while
+    /// wiping on drop are ignored.
+    pub fn zeroize_on_drop(&mut self, policy: ZeroizeOnDrop) -> &mut Self {
+        self.zeroize_on_drop = policy;
+        self
+    }
+
+    /// Keep a flag in the header which is set before the buffer is first
+    /// written to, and cleared once every write has reached the file, by
+    /// [`BackedBuffer::flush_clean`] or on drop. A file whose flag is still
+    /// set when it is loaded wasn't shut down cleanly, e.g. because the
+    /// program crashed, and may hold a mix of old and new contents. Defaults
+    /// to [`DirtyFlag::Off`], and only applies to buffers with a header.
+    ///
+    /// Dropping a dirty buffer flushes it synchronously regardless of
+    /// [`flush_on_drop`](Self::flush_on_drop), to be able to clear the flag.
+    pub fn dirty_flag(&mut self, policy: DirtyFlag) -> &mut Self {
+        self.dirty_flag = policy;
+        self
+    }
+
+    /// Whether to flush the mapping when the buffer is dropped, defaults to
+    /// [`FlushOnDrop::None`]. Errors while flushing on drop are ignored.
+    pub fn flush_on_drop(&mut self, policy: FlushOnDrop) -> &mut Self {
+        self.flush_on_drop = policy;
+        self
+    }
+
+    /// Whether to `fsync` the file when the buffer is dropped, after any
+    /// flush, so that the contents survive a power loss. See
+    /// [`BackedBuffer::sync_all`].
+    pub fn fsync_on_drop(&mut self, enable: bool) -> &mut Self {
+        self.fsync_on_drop = enable;
+        self
+    }
+
+    /// What to do when a loaded file has trailing bytes which don't make up
+    /// a whole element, defaults to [`SizePolicy::Exact`].
+    pub fn size_policy(&mut self, policy: SizePolicy) -> &mut Self {
+        self.size_policy = policy;
+        self
+    }
+
+    /// What to do when loading a file with trailing bytes, or which fails
+    /// its checksum, defaults to [`OnCorruption::Error`]. Takes precedence
+    /// over the size policy unless set to fail or panic. Buffers also
+    /// consult it in their fallible accessors, such as
+    /// [`BackedBuffer::try_as_slice`], when the file was truncated.
+    pub fn on_corruption(&mut self, policy: OnCorruption) -> &mut Self {
+        self.on_corruption = policy;
+        self
+    }
+
+    /// Store a small self-describing header at the start of the file, with
+    /// the size, alignment and type name of the elements, so that loading
+    /// it as the wrong type fails with [`MmapBufferError::TypeMismatch`].
+    /// The header also records the logical length of the buffer, so that
+    /// [`BackedBuffer::shrink`] and [`BackedBuffer::set_len`] persist across
+    /// reloads. Files created with a header must also be loaded with one.
+    pub fn header(&mut self, enable: bool) -> &mut Self {
+        self.header = enable;
+        self
+    }
+
+    /// Store a [header](Self::header) describing the elements by the layout
+    /// fingerprint of `T` rather than by its type name, so that files load
+    /// as any type with the same layout, wherever it is defined, and fail
+    /// with [`MmapBufferError::TypeMismatch`] once the layout changes. The
+    /// buffer must then be created or loaded as `T`.
+    pub fn record<T: MmapRecord>(&mut self) -> &mut Self {
+        self.header = true;
+        self.layout = Some(T::LAYOUT);
+        self
+    }
+
+    /// Guarantee that the first element of the buffer lies on a multiple of
+    /// `bytes` in memory, e.g. 64 for SIMD loads or 4096 for `O_DIRECT`. A
+    /// [header](Self::header) is padded to keep the elements aligned and
+    /// records the alignment, so files created with this option must be
+    /// loaded with it too, or fail with [`MmapBufferError::Alignment`]. Loading
+    /// a [range](Self::load_range) fails with [`MmapBufferError::Alignment`]
+    /// if it doesn't start suitably aligned. Defaults to 1, as mappings are
+    /// page aligned to begin with.
+    ///
+    /// Panics unless `bytes` is a power of two no larger than the page size.
+    pub fn align(&mut self, bytes: usize) -> &mut Self {
+        assert!(
+            bytes.is_power_of_two() && bytes <= page_size(),
+            "alignment must be a power of two no larger than the page size!"
+        );
+        self.align = bytes;
+        self
+    }
+
+    /// Keep a CRC-32 checksum of the file in a `<path>.crc32` sidecar file.
+    /// It is verified when loading, failing with
+    /// [`MmapBufferError::ChecksumMismatch`], and updated whenever the buffer
+    /// is flushed or dropped. See [`BackedBuffer::verify`].
+    pub fn checksum(&mut self, enable: bool) -> &mut Self {
+        self.checksum = enable;
+        self
+    }
+
+    /// Along with [`checksum`](Self::checksum), also keep a checksum of
+    /// every page in the sidecar file. Loading a file whose contents don't
+    /// match then fails with [`MmapBufferError::PageChecksumMismatch`],
+    /// listing which elements lie on damaged pages, e.g. ones torn by a
+    /// power failure in the middle of writing them back. Once a file has
+    /// per-page checksums, they are kept up to date whenever its checksum
+    /// is, with or without this option.
+    pub fn page_checksums(&mut self, enable: bool) -> &mut Self {
+        self.page_checksums = enable;
+        self
+    }
+
+    /// Create a new buffer at the given path with a fixed capacity.
+    /// This capacity is in units of `T`, not in bytes
+    pub fn create<T: Pod>(
+        &self,
+        capacity: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        self.create_with(capacity, path.as_ref(), &[])
+    }
+
+    /// Create a new buffer at the given path holding a copy of `slice`,
+    /// see [`BackedBuffer::copy_from_slice`]. The contents are written
+    /// straight to the file, so every byte is only written once.
+    pub fn create_from_slice<T: Pod>(
+        &self,
+        slice: &[T],
+        path: impl AsRef<Path>,
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        self.create_with(slice.len(), path.as_ref(), bytemuck::cast_slice(slice))
+    }
+
+    /// Create a buffer of `capacity` elements, with `contents` written at
+    /// the start of the data
+    fn create_with<T: Pod>(
+        &self,
+        capacity: usize,
+        path: &Path,
+        contents: &[u8],
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        let pid_file = self.acquire_pid_file(path)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .create(!self.create_new)
+            .create_new(self.create_new)
+            .open(path)?;
+
+        // Lock before touching the contents of an existing file, which may
+        // still be in use by another buffer
+        let lock_file = self.lock_path(&file, path)?;
+        if self.truncate && !self.create_new {
+            file.set_len(0)?;
+        }
+        let mut buffer = self.init_file(file, capacity, Some(path), contents)?;
+        buffer.lock_file = lock_file;
+        buffer.pid_file = pid_file;
+        Ok(buffer)
+    }
+
+    /// Size and fill an open, locked file, then map it. Files without a path
+    /// get no checksum sidecar.
+    fn init_file<T: Pod>(
+        &self,
+        mut file: File,
+        capacity: usize,
+        path: Option<&Path>,
+        contents: &[u8],
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        let capacity_bytes = byte_size::<T>(self.header_size::<T>(), capacity)?;
+        let existing_bytes = file.metadata()?.len() as usize;
+
+        if existing_bytes > capacity_bytes {
+            file.set_len(capacity_bytes as u64)?;
+        } else if existing_bytes < capacity_bytes {
+            if self.preallocate {
+                file.allocate(capacity_bytes as u64)?;
+            } else {
+                // Leaves a hole which reads as zeroes, without writing them
+                file.set_len(capacity_bytes as u64)?;
+            }
+        }
+
+        if self.header {
+            file.seek(SeekFrom::Start(0))?;
+            let header =
+                Header::new::<T>(capacity, self.align).with_type_hash(self.type_hash::<T>());
+            file.write_all(bytemuck::bytes_of(&header))?;
+        }
+
+        if !contents.is_empty() {
+            file.seek(SeekFrom::Start(self.header_size::<T>() as u64))?;
+            file.write_all(contents)?;
+        }
+
+        let mut buffer: BackedBuffer<T> = unsafe { self.map_file(file)? };
+        let Some(path) = path else {
+            return Ok(buffer);
+        };
+
+        if self.checksum {
+            let sidecar = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(checksum::sidecar_path(path))?;
+            if self.page_checksums {
+                // An empty table is enough to have the real one written
+                checksum::write_page_checksums(&sidecar, &[])?;
+            }
+            buffer.checksum = Some(sidecar);
+            buffer.update_checksum()?;
+        }
+
+        buffer.path = Some(path.to_owned());
+        buffer.delete_on_drop = self.delete_on_drop;
+        Ok(buffer)
+    }
+
+    /// Create a new buffer with a fixed capacity backed by an unnamed
+    /// temporary file in `dir`, see [`BackedBuffer::temp_in`]. Checksums
+    /// don't apply to temporary buffers.
+    pub fn create_temp<T: Pod>(
+        &self,
+        capacity: usize,
+        dir: impl AsRef<Path>,
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        let file = tempfile::tempfile_in(dir)?;
+        self.init_file(file, capacity, None, &[])
+    }
+
+    /// Load a buffer from an open file descriptor, e.g. one received from
+    /// another process, see [`BackedBuffer::from_fd`]. Checksums don't
+    /// apply, since there is no path to find the sidecar file at.
+    #[cfg(unix)]
+    pub fn load_fd<T: Pod>(&self, fd: OwnedFd) -> Result<BackedBuffer<T>, MmapBufferError> {
+        let file = File::from(fd);
+        self.acquire_lock(&file)?;
+
+        // SAFETY: exclusive locks work internally when files read from path
+        unsafe { self.map_file(file) }
+    }
+
+    /// Load a buffer from an existing path.
+    pub fn load<T: Pod>(&self, path: impl AsRef<Path>) -> Result<BackedBuffer<T>, MmapBufferError> {
+        let path = path.as_ref();
+        let pid_file = self.acquire_pid_file(path)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(!self.copy_on_write)
+            .open(path)?;
+        let lock_file = self.lock_path(&file, path)?;
+
+        let mut buffer = self.load_file(file, path)?;
+        buffer.lock_file = lock_file;
+        buffer.pid_file = pid_file;
+        Ok(buffer)
+    }
+
+    /// Load the buffer at the given path if a file exists there, and create
+    /// it with a fixed capacity otherwise, see [`BackedBuffer::open_or_create`].
+    /// An existing file must hold exactly `capacity` elements.
+    pub fn open_or_create<T: Pod>(
+        &self,
+        capacity: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        let path = path.as_ref();
+        let pid_file = self.acquire_pid_file(path)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        // Whoever gets the lock first on an empty file initializes it, and
+        // anyone after that sees a non-empty file and loads it
+        let lock_file = self.lock_path(&file, path)?;
+
+        let capacity_bytes = byte_size::<T>(self.header_size::<T>(), capacity)?;
+        let file_size = file.metadata()?.len() as usize;
+        let mut buffer = if file_size == 0 {
+            self.init_file(file, capacity, Some(path), &[])?
+        } else if file_size != capacity_bytes {
+            // A file which holds whole elements just holds the wrong number
+            let element_size = std::mem::size_of::<T>();
+            return Err(match file_size.checked_sub(self.header_size::<T>()) {
+                Some(bytes) if bytes.is_multiple_of(element_size) => {
+                    MmapBufferError::LengthMismatch {
+                        expected: capacity,
+                        actual: bytes / element_size,
+                    }
+                }
+                _ => MmapBufferError::SizeMismatch {
+                    file_size,
+                    element_size,
+                },
+            });
+        } else {
+            self.load_file(file, path)?
+        };
+
+        buffer.lock_file = lock_file;
+        buffer.pid_file = pid_file;
+        Ok(buffer)
+    }
+
+    /// Map an open, locked file and check it against its sidecar checksum
+    fn load_file<T: Pod>(
+        &self,
+        file: File,
+        path: &Path,
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        // SAFETY: exclusive locks work internally when files read from path
+        let mut buffer: BackedBuffer<T> = unsafe { self.map_file(file)? };
+        if self.checksum {
+            let sidecar = OpenOptions::new()
+                .read(true)
+                .write(!self.copy_on_write)
+                .open(checksum::sidecar_path(path))?;
+
+            let recovered = match checksum::check(
+                &sidecar,
+                &buffer.mmap,
+                buffer.offset,
+                std::mem::size_of::<T>(),
+            ) {
+                Ok(()) => false,
+                Err(err) => {
+                    self.recover(&mut buffer, err)?;
+                    true
+                }
+            };
+
+            // Copy-on-write changes never reach the file, so neither should
+            // their checksum
+            if !self.copy_on_write {
+                if self.page_checksums && !checksum::has_page_checksums(&sidecar)? {
+                    checksum::write_page_checksums(&sidecar, &buffer.mmap)?;
+                }
+                buffer.checksum = Some(sidecar);
+                if recovered {
+                    buffer.flush()?;
+                }
+            }
+        }
+
+        buffer.path = Some(path.to_owned());
+        buffer.delete_on_drop = self.delete_on_drop;
+        Ok(buffer)
+    }
+
+    /// Load `len` elements starting at element `offset` of an existing file,
+    /// see [`BackedBuffer::load_range`]. Offsets count from the very start
+    /// of the file, and the header and checksum options don't apply.
+    pub fn load_range<T: Pod>(
+        &self,
+        path: impl AsRef<Path>,
+        offset: usize,
+        len: usize,
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        let path = path.as_ref();
+        let pid_file = self.acquire_pid_file(path)?;
+        let (file, lock_file) = self.open_locked(path)?;
+        let start = byte_size::<T>(0, offset)?;
+
+        let mut buffer = self.map_range(file, path, start, len)?;
+        buffer.lock_file = lock_file;
+        buffer.pid_file = pid_file;
+        Ok(buffer)
+    }
+
+    /// Open an existing file for mapping and take the configured lock on it,
+    /// returning the lock file too if enabled
+    pub(crate) fn open_locked(&self, path: &Path) -> Result<(File, Option<File>), MmapBufferError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(!self.copy_on_write)
+            .open(path)?;
+        let lock_file = self.lock_path(&file, path)?;
+        Ok((file, lock_file))
+    }
+
+    /// Map `len` elements starting `start` bytes into an open, locked file
+    pub(crate) fn map_range<T: Pod>(
+        &self,
+        file: File,
+        path: &Path,
+        start: usize,
+        len: usize,
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        let end = byte_size::<T>(start, len)?;
+        if !start.is_multiple_of(self.align.max(std::mem::align_of::<T>())) {
+            return Err(MmapBufferError::Alignment);
+        }
+        if end as u64 > file.metadata()?.len() {
+            return Err(MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "range extends past the end of the file",
+            )));
+        }
+
+        // Mappings must start on a page boundary
+        let file_offset = start - start % page_size();
+        let mut options = self.mmap_options();
+        options.offset(file_offset as u64).len(end - file_offset);
+
+        // SAFETY: exclusive locks work internally when files read from path
+        let (mut mmap, guards) = self.map_guarded(end - file_offset, || {
+            if self.copy_on_write {
+                unsafe { options.map_copy(&file) }
+            } else {
+                unsafe { options.map_mut(&file) }
+            }
+        })?;
+        self.apply(&mut mmap)?;
+
+        Ok(BackedBuffer {
+            mmap,
+            #[cfg(unix)]
+            guards,
+            offset: start - file_offset,
+            file_offset: file_offset as u64,
+            window: true,
+            header: false,
+            copy_on_write: self.copy_on_write,
+            huge_pages: false,
+            file: Some(file),
+            path: Some(path.to_owned()),
+            delete_on_drop: self.delete_on_drop,
+            flush_on_drop: self.flush_on_drop,
+            fsync_on_drop: self.fsync_on_drop,
+            zeroize_on_drop: self.zeroize_on_drop,
+            dirty_flag: false,
+            unclean_shutdown: false,
+            lock: self.lock,
+            on_corruption: self.on_corruption,
+            checksum: None,
+            lock_file: None,
+            pid_file: None,
+            dirty: DirtyPages::default(),
+            len,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Load a read-only buffer from an existing path, see
+    /// [`ReadOnlyBuffer::load`]. The file is opened without write
+    /// permissions.
+    pub fn load_read_only<T: Pod>(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<ReadOnlyBuffer<T>, MmapBufferError> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().read(true).open(path)?;
+        let lock_file = self.lock_path(&file, path)?;
+
+        // SAFETY: writers through this crate are excluded by the lock
+        let mmap = unsafe { self.mmap_options().map(&file)? };
+
+        let offset = self.header_size::<T>();
+        if self.checksum {
+            let sidecar = File::open(checksum::sidecar_path(path))?;
+            checksum::check(&sidecar, &mmap, offset, std::mem::size_of::<T>())?;
+        }
+
+        // Catch alignment issues ahead of time
+        let capacity = self.element_count::<T>(&mmap, offset)?;
+        let len = self.validate_header::<T>(&mmap, capacity)?;
+
+        #[cfg(target_os = "linux")]
+        if self.huge_pages {
+            // Ignore the error, regular pages are a fine fallback
+            mmap.advise(memmap2::Advice::HugePage).unwrap_or(());
+        }
+
+        Ok(ReadOnlyBuffer {
+            mmap,
+            offset,
+            len,
+            file: Some(file),
+            lock_file,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Create a new buffer with a fixed capacity which isn't backed by any
+    /// file, see [`BackedBuffer::anonymous`]
+    pub fn anonymous<T: Pod>(&self, capacity: usize) -> Result<BackedBuffer<T>, MmapBufferError> {
+        let capacity_bytes = byte_size::<T>(0, capacity)?;
+        let huge_pages = std::cell::Cell::new(false);
+        let (mut mmap, guards) = self.map_guarded(capacity_bytes, || {
+            #[cfg(target_os = "linux")]
+            if self.huge_pages {
+                if let Some(mmap) = self.map_hugetlb(capacity_bytes) {
+                    huge_pages.set(true);
+                    return Ok(mmap);
+                }
+            }
+
+            huge_pages.set(false);
+            self.mmap_options().len(capacity_bytes).map_anon()
+        })?;
+        self.apply(&mut mmap)?;
+
+        Ok(BackedBuffer {
+            mmap,
+            #[cfg(unix)]
+            guards,
+            offset: 0,
+            file_offset: 0,
+            window: false,
+            header: false,
+            copy_on_write: false,
+            huge_pages: huge_pages.get(),
+            file: None,
+            path: None,
+            delete_on_drop: false,
+            flush_on_drop: FlushOnDrop::None,
+            fsync_on_drop: false,
+            zeroize_on_drop: self.zeroize_on_drop,
+            dirty_flag: false,
+            unclean_shutdown: false,
+            lock: LockMode::Unlocked,
+            on_corruption: self.on_corruption,
+            checksum: None,
+            lock_file: None,
+            pid_file: None,
+            dirty: DirtyPages::default(),
+            len: capacity,
+            _ph: PhantomData,
+        })
+    }
+
+    /// SAFETY: cannot `guarantee` advisory locks will work in this case, even
+    /// within the same program (File clone does weird stuff)
+    unsafe fn map_file<T: Pod>(&self, file: File) -> Result<BackedBuffer<T>, MmapBufferError> {
+        if !self.copy_on_write {
+            let file_size = file.metadata()?.len() as usize;
+            if let Some(data_bytes) = file_size.checked_sub(self.header_size::<T>()) {
+                let size = std::mem::size_of::<T>();
+                let slop = data_bytes % size;
+                if slop != 0 && self.on_corruption == OnCorruption::ZeroFill {
+                    file.set_len((file_size - slop + size) as u64)?;
+                } else if slop != 0 && self.effective_size_policy() == SizePolicy::Truncate {
+                    file.set_len((file_size - slop) as u64)?;
+                }
+            }
+        }
+
+        let (mut mmap, guards) = self.map_guarded(file.metadata()?.len() as usize, || {
+            if self.copy_on_write {
+                unsafe { self.mmap_options().map_copy(&file) }
+            } else {
+                unsafe { self.mmap_options().map_mut(&file) }
+            }
+        })?;
+
+        let offset = self.header_size::<T>();
+
+        // Catch alignment issues ahead of time
+        let capacity = self.element_count::<T>(&mmap, offset)?;
+        let len = self.validate_header::<T>(&mmap, capacity)?;
+
+        let dirty_flag = self.header && self.dirty_flag != DirtyFlag::Off;
+        let unclean_shutdown = dirty_flag && Header::load_dirty(&mmap);
+        if unclean_shutdown && self.dirty_flag == DirtyFlag::Refuse {
+            return Err(MmapBufferError::UncleanShutdown);
+        }
+        self.apply(&mut mmap)?;
+
+        Ok(BackedBuffer {
+            mmap,
+            #[cfg(unix)]
+            guards,
+            offset,
+            file_offset: 0,
+            window: false,
+            header: self.header,
+            copy_on_write: self.copy_on_write,
+            huge_pages: false,
+            file: Some(file),
+            path: None,
+            delete_on_drop: false,
+            flush_on_drop: self.flush_on_drop,
+            fsync_on_drop: self.fsync_on_drop,
+            zeroize_on_drop: self.zeroize_on_drop,
+            // Copy-on-write changes never reach the file to be covered
+            dirty_flag: dirty_flag && !self.copy_on_write,
+            unclean_shutdown,
+            lock: self.lock,
+            on_corruption: self.on_corruption,
+            checksum: None,
+            lock_file: None,
+            pid_file: None,
+            dirty: DirtyPages::default(),
+            len,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Number of bytes before the first element in the file
+    fn header_size<T: Pod>(&self) -> usize {
+        if self.header {
+            Header::size::<T>(self.align)
+        } else {
+            0
+        }
+    }
+
+    /// Check the header if enabled, returning the logical length of a
+    /// mapping which fits `capacity` elements
+    fn validate_header<T: Pod>(
+        &self,
+        bytes: &[u8],
+        capacity: usize,
+    ) -> Result<usize, MmapBufferError> {
+        if !self.header {
+            return Ok(capacity);
+        }
+
+        let len = Header::validate_as::<T>(bytes, self.align, self.type_hash::<T>())?;
+        if len > capacity {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        Ok(len)
+    }
+
+    /// What the header records about the type of the elements
+    fn type_hash<T: Pod>(&self) -> u64 {
+        self.layout.unwrap_or_else(header::type_hash::<T>)
+    }
+
+    /// Number of `T` which fit in the mapping after the first `offset`
+    /// bytes, according to the size policy, or an error if it can't be
+    /// viewed as a slice of `T`
+    fn element_count<T: Pod>(&self, bytes: &[u8], offset: usize) -> Result<usize, MmapBufferError> {
+        let bytes = bytes.get(offset..).ok_or(MmapBufferError::InvalidHeader)?;
+        let whole = bytes.len() - bytes.len() % std::mem::size_of::<T>();
+        let checked = match self.effective_size_policy() {
+            SizePolicy::Exact => bytes,
+            SizePolicy::Truncate | SizePolicy::Prefix => &bytes[..whole],
+        };
+
+        try_cast_slice::<u8, T>(checked)
+            .map(<[T]>::len)
+            .map_err(|err| {
+                let err =
+                    MmapBufferError::from_cast_error(err, bytes.len(), std::mem::size_of::<T>());
+                if self.on_corruption == OnCorruption::Panic {
+                    panic!("corrupted buffer file: {err}");
+                }
+                err
+            })
+    }
+
+    /// The size policy, unless the corruption policy repairs trailing
+    /// bytes itself
+    fn effective_size_policy(&self) -> SizePolicy {
+        match self.on_corruption {
+            OnCorruption::Panic | OnCorruption::Error => self.size_policy,
+            // Zero filling pads the file instead, unless it can't be written
+            OnCorruption::TruncateToValidPrefix | OnCorruption::ZeroFill => SizePolicy::Truncate,
+        }
+    }
+
+    /// Apply the corruption policy to a buffer which failed its checksum
+    /// with `err`, in memory only, leaving flushing the repair to the caller
+    fn recover<T: Pod>(
+        &self,
+        buffer: &mut BackedBuffer<T>,
+        err: MmapBufferError,
+    ) -> Result<(), MmapBufferError> {
+        let capacity = buffer.capacity();
+        let damaged = match &err {
+            MmapBufferError::PageChecksumMismatch { ranges } => ranges.clone(),
+            MmapBufferError::ChecksumMismatch => std::iter::once(0..capacity).collect(),
+            _ => return Err(err),
+        };
+
+        match self.on_corruption {
+            OnCorruption::Panic => panic!("corrupted buffer file: {err}"),
+            OnCorruption::Error => return Err(err),
+            OnCorruption::TruncateToValidPrefix => {
+                let prefix = damaged.first().map_or(capacity, |range| range.start);
+                let prefix = usize::min(prefix, buffer.len);
+                if self.copy_on_write {
+                    buffer.set_len(prefix);
+                } else {
+                    buffer.resize(prefix)?;
+                }
+            }
+            OnCorruption::ZeroFill => {
+                let size = std::mem::size_of::<T>();
+                for range in damaged {
+                    let end = usize::min(range.end, capacity);
+                    if range.start < end {
+                        let bytes = buffer.offset + range.start * size..buffer.offset + end * size;
+                        buffer.mmap[bytes].fill(0);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create the pid file for the buffer at `path`, if enabled
+    fn acquire_pid_file(&self, path: &Path) -> Result<Option<PidFile>, MmapBufferError> {
+        self.pid_file.then(|| PidFile::acquire(path)).transpose()
+    }
+
+    /// Lock the file at `path`, or its lock file if enabled, which is
+    /// returned so it can be kept open for as long as the lock is needed
+    fn lock_path(&self, file: &File, path: &Path) -> Result<Option<File>, MmapBufferError> {
+        if !self.lock_file {
+            self.acquire_lock(file)?;
+            return Ok(None);
+        }
+
+        let lock_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_file_path(path))?;
+        self.acquire_lock(&lock_file)?;
+        Ok(Some(lock_file))
+    }
+
+    /// Establish advisory lock
+    fn acquire_lock(&self, file: &File) -> Result<(), MmapBufferError> {
+        let try_lock = || {
+            match self.lock {
+                LockMode::Exclusive => file.try_lock_exclusive(),
+                LockMode::Shared => FileExt::try_lock_shared(file),
+                LockMode::Unlocked => Ok(()),
+            }
+            .map_err(MmapBufferError::from_lock_error)
+        };
+
+        match self.lock_wait {
+            LockWait::Fail => try_lock(),
+            LockWait::Forever => match self.lock {
+                LockMode::Exclusive => Ok(file.lock_exclusive()?),
+                LockMode::Shared => Ok(FileExt::lock_shared(file)?),
+                LockMode::Unlocked => Ok(()),
+            },
+            LockWait::Until(timeout) => {
+                let deadline = Instant::now() + timeout;
+                let mut backoff = Duration::from_millis(1);
+
+                loop {
+                    match try_lock() {
+                        Err(MmapBufferError::Locked) if Instant::now() < deadline => {
+                            let remaining = deadline.saturating_duration_since(Instant::now());
+                            std::thread::sleep(backoff.min(remaining));
+                            backoff = (backoff * 2).min(Duration::from_millis(100));
+                        }
+                        result => return result,
+                    }
+                }
+            }
+        }
+    }
+
+    fn mmap_options(&self) -> MmapOptions {
+        let mut options = MmapOptions::new();
+        if self.populate {
+            options.populate();
+        }
+
+        options
+    }
+
+    /// Map `len` bytes of explicit huge pages from the hugetlb pool, rounded
+    /// up to a whole number of them, or `None` if the pool can't supply
+    /// them. The pages come from a memory file, which memmap2 can map in
+    /// place of `MAP_HUGETLB`, and which is closed again right away.
+    #[cfg(target_os = "linux")]
+    fn map_hugetlb(&self, len: usize) -> Option<MmapMut> {
+        let len = len.max(1).checked_next_multiple_of(huge_page_size()?)?;
+
+        // SAFETY: the name is a valid null-terminated string
+        let fd = unsafe {
+            libc::memfd_create(
+                c"mmap_buffer".as_ptr(),
+                libc::MFD_CLOEXEC | libc::MFD_HUGETLB,
+            )
+        };
+        if fd < 0 {
+            return None;
+        }
+
+        // SAFETY: `fd` is a freshly opened descriptor which nothing else owns
+        let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        file.set_len(len as u64).ok()?;
+
+        // Huge pages are reserved when mapping, which fails if the pool
+        // doesn't have enough left, rather than faulting later on
+        // SAFETY: nobody else has access to the memory file
+        unsafe { self.mmap_options().len(len).map_mut(&file).ok() }
+    }
+
+    /// Create a mapping of `len` bytes, surrounded by guard pages if enabled
+    #[cfg(unix)]
+    fn map_guarded(
+        &self,
+        len: usize,
+        map: impl Fn() -> io::Result<MmapMut>,
+    ) -> Result<(MmapMut, Option<GuardPages>), MmapBufferError> {
+        if !self.guard_pages {
+            return Ok((map()?, None));
+        }
+
+        let (mmap, guards) = guard::map_guarded(len, map)?;
+        Ok((mmap, Some(guards)))
+    }
+
+    #[cfg(not(unix))]
+    fn map_guarded(
+        &self,
+        _len: usize,
+        map: impl Fn() -> io::Result<MmapMut>,
+    ) -> Result<(MmapMut, ()), MmapBufferError> {
+        Ok((map()?, ()))
+    }
+
+    /// Apply options which only take effect once the memory is mapped
+    fn apply(&self, mmap: &mut MmapMut) -> Result<(), MmapBufferError> {
+        #[cfg(target_os = "linux")]
+        if self.huge_pages {
+            // Ignore the error, regular pages are a fine fallback
+            mmap.advise(memmap2::Advice::HugePage).unwrap_or(());
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(policy) = self.numa_policy {
+            numa::mbind(mmap.as_ptr(), mmap.len(), policy)?;
+        }
+
+        #[cfg(unix)]
+        if self.lock_in_memory {
+            mmap.lock()?;
+        }
+
+        #[cfg(not(unix))]
+        let _ = mmap;
+
+        Ok(())
+    }
+}
+
+/// Size of the default huge pages in the hugetlb pool, if it has one
+#[cfg(target_os = "linux")]
+fn huge_page_size() -> Option<usize> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("Hugepagesize:"))?;
+    let kib: usize = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    kib.checked_mul(1024)
+}
+
+/// Path of the lock file for the buffer at `path`
+pub(crate) fn lock_file_path(path: &Path) -> PathBuf {
+    let mut lock_path = OsString::from(path.as_os_str());
+    lock_path.push(".lock");
+    lock_path.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BackedBufferBuilder, LockMode};
+    use crate::MmapBufferError;
+    use std::{error::Error, path::Path, time::Duration};
+
+    #[test]
+    fn create_semantics() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        BackedBufferBuilder::new()
+            .create_new(true)
+            .create::<u8>(4, file_path.clone())?
+            .copy_from_slice(&[1, 2, 3, 4]);
+
+        BackedBufferBuilder::new()
+            .create_new(true)
+            .create::<u8>(4, file_path.clone())
+            .expect_err("");
+
+        let buf = BackedBufferBuilder::new()
+            .truncate(false)
+            .populate(false)
+            .create::<u8>(6, file_path.clone())?;
+        assert_eq!(&buf[..], &[1, 2, 3, 4, 0, 0]);
+
+        // Creating over a file in use fails without wiping it
+        let err = BackedBufferBuilder::new()
+            .create::<u8>(2, file_path.clone())
+            .unwrap_err();
+        assert!(matches!(err, MmapBufferError::Locked));
+        assert_eq!(&buf[..], &[1, 2, 3, 4, 0, 0]);
+        drop(buf);
+
+        let mut options = BackedBufferBuilder::new();
+        options.header(true);
+        options.create_from_slice::<u16>(&[5, 6, 7], file_path.clone())?;
+        assert_eq!(&options.load::<u16>(file_path)?[..], &[5, 6, 7]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lock_file() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.lock_file(true);
+        let buf = options.create::<u8>(16, file_path.clone())?;
+        assert!(super::lock_file_path(&file_path).exists());
+
+        // The data file itself is left unlocked, but the lock file isn't
+        drop(BackedBufferBuilder::new().load::<u8>(&file_path)?);
+        assert!(matches!(
+            options.load::<u8>(&file_path),
+            Err(MmapBufferError::Locked)
+        ));
+        assert!(options.load_read_only::<u8>(&file_path).is_err());
+
+        drop(buf);
+        options.load::<u8>(&file_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_or_create() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBufferBuilder::new().open_or_create::<u32>(4, file_path.clone())?;
+        buf[3] = 3;
+        drop(buf);
+
+        let buf = BackedBufferBuilder::new().open_or_create::<u32>(4, file_path.clone())?;
+        assert_eq!(&buf[..], &[0, 0, 0, 3]);
+        drop(buf);
+
+        let err = BackedBufferBuilder::new()
+            .open_or_create::<u32>(5, file_path.clone())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            MmapBufferError::LengthMismatch {
+                expected: 5,
+                actual: 4
+            }
+        ));
+
+        let err = BackedBufferBuilder::new()
+            .open_or_create::<[u8; 3]>(5, file_path)
+            .unwrap_err();
+        assert!(matches!(err, MmapBufferError::SizeMismatch { .. }));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sparse_allocation() -> Result<(), Box<dyn Error>> {
+        use std::os::unix::fs::MetadataExt;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        let capacity = 1 << 24;
+
+        let buf = BackedBufferBuilder::new()
+            .populate(false)
+            .create::<u8>(capacity, file_path.clone())?;
+        assert_eq!(buf[capacity - 1], 0);
+        drop(buf);
+
+        let metadata = std::fs::metadata(&file_path)?;
+        assert_eq!(metadata.len(), capacity as u64);
+        assert!(metadata.blocks() * 512 < capacity as u64);
+
+        BackedBufferBuilder::new()
+            .preallocate(true)
+            .create::<u8>(capacity, file_path.clone())?;
+        assert!(std::fs::metadata(&file_path)?.blocks() * 512 >= capacity as u64);
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_locks() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        drop(BackedBufferBuilder::new().create::<u8>(4, file_path.clone())?);
+
+        let mut options = BackedBufferBuilder::new();
+        options.lock(LockMode::Shared);
+
+        let _buf_1 = options.load::<u8>(file_path.clone())?;
+        let _buf_2 = options.load_read_only::<u8>(file_path.clone())?;
+        BackedBufferBuilder::new()
+            .load::<u8>(file_path.clone())
+            .expect_err("");
+
+        BackedBufferBuilder::new()
+            .wait_for_lock(Some(Duration::from_millis(10)))
+            .load::<u8>(file_path.clone())
+            .expect_err("");
+
+        let _buf_3 = BackedBufferBuilder::new()
+            .lock(LockMode::Unlocked)
+            .load::<u8>(file_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn alignment() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.header(true).align(256);
+
+        let mut buf = options.create::<f32>(1000, file_path.clone())?;
+        assert_eq!(buf.as_ptr() as usize % 256, 0);
+        buf[0] = 1.0;
+
+        let (chunks, rest) = buf.as_aligned_chunks::<64>();
+        assert_eq!((chunks.len(), rest.len()), (15, 40));
+        assert_eq!(chunks[0][0], 1.0);
+        drop(buf);
+
+        assert_eq!(options.load::<f32>(file_path.clone())?[0], 1.0);
+        let err = BackedBufferBuilder::new()
+            .header(true)
+            .load::<f32>(file_path.clone())
+            .err()
+            .unwrap();
+        assert!(matches!(err, MmapBufferError::Alignment));
+
+        let err = BackedBufferBuilder::new()
+            .align(64)
+            .load_range::<f32>(file_path, 8, 16)
+            .err()
+            .unwrap();
+        assert!(matches!(err, MmapBufferError::Alignment));
+
+        Ok(())
+    }
+}
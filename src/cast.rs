@@ -0,0 +1,106 @@
+use bytemuck::Pod;
+
+use crate::{header::Header, BackedBuffer, MmapBufferError};
+
+impl<T: Pod> BackedBuffer<T> {
+    /// View the contents as a slice of another element type `U`, failing if
+    /// the buffer isn't suitably sized and aligned for `U`.
+    pub fn as_slice_of<U: Pod>(&self) -> Result<&[U], MmapBufferError> {
+        bytemuck::try_cast_slice(self).map_err(|err| cast_error::<T, U>(err, self.len()))
+    }
+
+    /// Mutably view the contents as a slice of another element type `U`,
+    /// failing if the buffer isn't suitably sized and aligned for `U`.
+    pub fn as_mut_slice_of<U: Pod>(&mut self) -> Result<&mut [U], MmapBufferError> {
+        let len = self.len();
+        bytemuck::try_cast_slice_mut(self).map_err(|err| cast_error::<T, U>(err, len))
+    }
+
+    /// Reinterpret the buffer as holding elements of type `U`, without
+    /// remapping the file. Fails, closing the buffer, if it isn't suitably
+    /// sized and aligned for `U`; [`as_slice_of`](Self::as_slice_of) can be
+    /// used to check beforehand.
+    ///
+    /// A header is rewritten to describe `U`, keeping its alignment and
+    /// flags, which requires the header to take up as many bytes for `U`
+    /// as for `T`. Headers recording the layout of an
+    /// [`MmapRecord`](crate::MmapRecord) can't be rewritten, since nothing
+    /// is known about the layout of `U`, and fail with
+    /// [`MmapBufferError::TypeMismatch`].
+    pub fn cast<U: Pod>(mut self) -> Result<BackedBuffer<U>, MmapBufferError> {
+        let len = self.as_slice_of::<U>()?.len();
+
+        if self.header {
+            Header::recast::<T, U>(&mut self.mmap, len)?;
+        }
+
+        Ok(self.with_element_type(len))
+    }
+}
+
+/// Describe a failed cast of `len` elements of `T` to `U`
+fn cast_error<T, U>(err: bytemuck::PodCastError, len: usize) -> MmapBufferError {
+    MmapBufferError::from_cast_error(
+        err,
+        len * std::mem::size_of::<T>(),
+        std::mem::size_of::<U>(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, BackedBufferBuilder, MmapBufferError, MmapRecord};
+    use bytemuck::{Pod, Zeroable};
+    use std::{error::Error, path::Path};
+
+    #[derive(Clone, Copy, MmapRecord)]
+    #[repr(C)]
+    struct Pair {
+        a: u32,
+        b: u32,
+    }
+
+    // SAFETY: `repr(C)` with no padding, and all fields are `Pod`
+    unsafe impl Zeroable for Pair {}
+    unsafe impl Pod for Pair {}
+
+    #[test]
+    fn reinterpret() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u8>::new(8, file_path.clone())?;
+        buf.as_mut_slice_of::<f32>()?[1] = 1.5;
+        assert_eq!(buf.as_slice_of::<u32>()?.len(), 2);
+
+        let err = buf.as_slice_of::<[u8; 3]>().unwrap_err();
+        assert!(matches!(err, MmapBufferError::SizeMismatch { .. }));
+
+        let buf = buf.cast::<f32>()?;
+        assert_eq!(&buf[..], &[0.0, 1.5]);
+        assert!(BackedBuffer::<f32>::load(file_path.clone()).is_err());
+        drop(buf);
+
+        // The lock was carried over and released on drop
+        assert_eq!(BackedBuffer::<f32>::load(&file_path)?[1], 1.5);
+
+        // Headers keep their alignment
+        let mut options = BackedBufferBuilder::new();
+        options.header(true).align(64);
+        options.create_from_slice::<u32>(&[1, 2], &file_path)?;
+        let buf = options.load::<u32>(&file_path)?.cast::<[u16; 2]>()?;
+        assert_eq!(buf.len(), 2);
+        drop(buf);
+        assert_eq!(options.load::<[u16; 2]>(&file_path)?.len(), 2);
+
+        // But layout fingerprints can't be carried over to another type
+        BackedBuffer::<Pair>::new_record(2, &file_path)?;
+        let buf = BackedBuffer::<Pair>::load_record(&file_path)?;
+        assert!(matches!(
+            buf.cast::<u64>().unwrap_err(),
+            MmapBufferError::TypeMismatch
+        ));
+
+        Ok(())
+    }
+}
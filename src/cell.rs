@@ -0,0 +1,154 @@
+use std::{io, path::Path};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+/// A single `T` backed by a file, e.g. a state record or a counter which
+/// has to survive restarts. Like a [`BackedBuffer`] holding exactly one
+/// element, but without the indexing.
+///
+/// Options such as checksums or flushing on drop are set by building the
+/// buffer with [`BackedBufferBuilder`](crate::BackedBufferBuilder) and
+/// converting it with [`from_buffer`](Self::from_buffer).
+#[derive(Debug)]
+pub struct BackedCell<T: Pod> {
+    buffer: BackedBuffer<T>,
+}
+
+impl<T: Pod> BackedCell<T> {
+    /// Create a new cell at the given path holding `value`. Any existing
+    /// file is truncated.
+    pub fn new(value: T, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let mut buffer = BackedBuffer::new(1, path)?;
+        buffer[0] = value;
+        Ok(Self { buffer })
+    }
+
+    /// Load a cell from an existing path, failing with
+    /// [`MmapBufferError::LengthMismatch`] unless the file holds exactly
+    /// one `T`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Self::from_buffer(BackedBuffer::load(path)?)
+    }
+
+    /// Load the cell at the given path if a file exists there, or create
+    /// it holding `value` otherwise. Only one of several racing processes
+    /// gets to create the file.
+    pub fn open_or_create(value: T, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let path = path.as_ref();
+        match BackedBuffer::create_new(1, path) {
+            Ok(mut buffer) => {
+                buffer[0] = value;
+                Ok(Self { buffer })
+            }
+            Err(MmapBufferError::Io(err)) if err.kind() == io::ErrorKind::AlreadyExists => {
+                Self::load(path)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Turn a buffer holding exactly one element into a cell, failing with
+    /// [`MmapBufferError::LengthMismatch`] otherwise
+    pub fn from_buffer(buffer: BackedBuffer<T>) -> Result<Self, MmapBufferError> {
+        if buffer.len() != 1 {
+            return Err(MmapBufferError::LengthMismatch {
+                expected: 1,
+                actual: buffer.len(),
+            });
+        }
+
+        Ok(Self { buffer })
+    }
+
+    /// The underlying buffer, holding the one element
+    pub fn into_buffer(self) -> BackedBuffer<T> {
+        self.buffer
+    }
+
+    /// Copy the value out
+    pub fn get(&self) -> T {
+        self.buffer[0]
+    }
+
+    /// Replace the value, returning the old one
+    pub fn set(&mut self, value: T) -> T {
+        std::mem::replace(&mut self.buffer[0], value)
+    }
+
+    /// Replace the value with `f` applied to it, returning the new value
+    pub fn update(&mut self, f: impl FnOnce(T) -> T) -> T {
+        let value = f(self.get());
+        self.buffer[0] = value;
+        value
+    }
+
+    /// Mutable access to the value in place
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.buffer[0]
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> Option<&Path> {
+        self.buffer.path()
+    }
+
+    /// Synchronously flush the value to the file, see
+    /// [`BackedBuffer::flush`]
+    pub fn flush(&self) -> Result<(), MmapBufferError> {
+        self.buffer.flush()
+    }
+
+    /// Start flushing the value without waiting, see
+    /// [`BackedBuffer::flush_async`]
+    pub fn flush_async(&self) -> Result<(), MmapBufferError> {
+        self.buffer.flush_async()
+    }
+
+    /// Flush the value and `fsync` the file, see [`BackedBuffer::sync_all`]
+    pub fn sync_all(&self) -> Result<(), MmapBufferError> {
+        self.buffer.sync_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, BackedCell, MmapBufferError};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn cell() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut cell = BackedCell::new([1u64, 0], file_path.clone())?;
+        assert_eq!(cell.set([1, 10]), [1, 0]);
+        assert_eq!(
+            cell.update(|[generation, offset]| [generation + 1, offset]),
+            [2, 10]
+        );
+        cell.get_mut()[1] += 1;
+        cell.flush()?;
+        drop(cell);
+
+        let cell = BackedCell::open_or_create([0u64, 0], &file_path)?;
+        assert_eq!(cell.get(), [2, 11]);
+        drop(cell);
+
+        let other_path = Path::join(tempdir.path(), "other");
+        assert_eq!(BackedCell::open_or_create(7u32, &other_path)?.get(), 7);
+
+        BackedBuffer::<u64>::new(2, file_path.clone())?;
+        let err = BackedCell::<u64>::load(&file_path).unwrap_err();
+        assert!(matches!(
+            err,
+            MmapBufferError::LengthMismatch {
+                expected: 1,
+                actual: 2
+            }
+        ));
+
+        Ok(())
+    }
+}
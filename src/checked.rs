@@ -0,0 +1,198 @@
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+use bytemuck::{
+    checked::{self, CheckedBitPattern, CheckedCastError},
+    NoUninit,
+};
+
+use crate::{BackedBuffer, MmapBufferError};
+
+/// A fixed size buffer of `T` backed by a file, for element types which
+/// aren't [`Pod`](bytemuck::Pod) because not every bit pattern is valid
+/// for them, such as `bool`, `NonZeroU32`, fieldless enums, and records
+/// containing them.
+///
+/// Every element is validated when the buffer is loaded, failing with
+/// [`MmapBufferError::InvalidBitPattern`] at the first invalid one, after
+/// which the elements are accessed directly like with a [`BackedBuffer`].
+/// Writes can only store valid values, so the contents stay valid as long
+/// as no other program modifies the file while it is mapped, which is
+/// already required of any buffer.
+#[derive(Debug)]
+pub struct CheckedBackedBuffer<T: CheckedBitPattern + NoUninit> {
+    bytes: BackedBuffer<u8>,
+    len: usize,
+    _ph: PhantomData<T>,
+}
+
+impl<T: CheckedBitPattern + NoUninit> CheckedBackedBuffer<T> {
+    /// Create a new buffer at the given path holding `capacity` copies of
+    /// `value`. Any existing file is truncated.
+    ///
+    /// # Panics
+    ///
+    /// If `T` is zero sized.
+    pub fn new(capacity: usize, value: T, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let size = element_size::<T>();
+        let capacity_bytes = capacity
+            .checked_mul(size)
+            .ok_or(MmapBufferError::CapacityOverflow)?;
+
+        let mut bytes = BackedBuffer::<u8>::new(capacity_bytes, path)?;
+        for chunk in bytes.chunks_exact_mut(size) {
+            chunk.copy_from_slice(bytemuck::bytes_of(&value));
+        }
+
+        Self::from_bytes(bytes)
+    }
+
+    /// Load a buffer from an existing path, validating every element.
+    ///
+    /// # Panics
+    ///
+    /// If `T` is zero sized.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Self::from_bytes(BackedBuffer::load(path)?)
+    }
+
+    /// Validate the contents of a buffer of bytes as elements of type `T`
+    /// and wrap it, so it can be built with any options of
+    /// [`BackedBufferBuilder`](crate::BackedBufferBuilder).
+    ///
+    /// # Panics
+    ///
+    /// If `T` is zero sized.
+    pub fn from_bytes(bytes: BackedBuffer<u8>) -> Result<Self, MmapBufferError> {
+        let size = element_size::<T>();
+        if !bytes.len().is_multiple_of(size) {
+            return Err(MmapBufferError::SizeMismatch {
+                file_size: bytes.len(),
+                element_size: size,
+            });
+        }
+
+        for (index, chunk) in bytes.chunks_exact(size).enumerate() {
+            match checked::try_from_bytes::<T>(chunk) {
+                Ok(_) => {}
+                Err(CheckedCastError::InvalidBitPattern) => {
+                    return Err(MmapBufferError::InvalidBitPattern { index });
+                }
+                Err(CheckedCastError::PodCastError(err)) => {
+                    return Err(MmapBufferError::from_cast_error(err, bytes.len(), size));
+                }
+            }
+        }
+
+        Ok(Self {
+            len: bytes.len() / size,
+            bytes,
+            _ph: PhantomData,
+        })
+    }
+
+    /// The underlying buffer of bytes
+    pub fn into_bytes(self) -> BackedBuffer<u8> {
+        self.bytes
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> Option<&Path> {
+        self.bytes.path()
+    }
+
+    /// Synchronously flush outstanding changes to the file, see
+    /// [`BackedBuffer::flush`]
+    pub fn flush(&self) -> Result<(), MmapBufferError> {
+        self.bytes.flush()
+    }
+
+    /// Start flushing outstanding changes without waiting, see
+    /// [`BackedBuffer::flush_async`]
+    pub fn flush_async(&self) -> Result<(), MmapBufferError> {
+        self.bytes.flush_async()
+    }
+}
+
+/// Size of `T`, which has to take up space to be stored
+fn element_size<T>() -> usize {
+    let size = std::mem::size_of::<T>();
+    assert!(size != 0, "zero sized types can't be stored in a file");
+    size
+}
+
+impl<T: CheckedBitPattern + NoUninit> Deref for CheckedBackedBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: every element was validated and is aligned, and can only
+        // be overwritten with valid values through `deref_mut`
+        unsafe { std::slice::from_raw_parts(self.bytes.as_ptr().cast(), self.len) }
+    }
+}
+
+impl<T: CheckedBitPattern + NoUninit> DerefMut for CheckedBackedBuffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: as above, and `T: NoUninit` so storing one leaves no
+        // uninitialized bytes behind in the mapping
+        unsafe { std::slice::from_raw_parts_mut(self.bytes.as_mut_ptr().cast(), self.len) }
+    }
+}
+
+impl<T: CheckedBitPattern + NoUninit> AsRef<[T]> for CheckedBackedBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T: CheckedBitPattern + NoUninit> AsMut<[T]> for CheckedBackedBuffer<T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, CheckedBackedBuffer, MmapBufferError};
+    use std::{error::Error, num::NonZeroU32, path::Path};
+
+    #[test]
+    fn checked_elements() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut flags = CheckedBackedBuffer::new(16, false, file_path.clone())?;
+        flags[3] = true;
+        drop(flags);
+
+        let flags = CheckedBackedBuffer::<bool>::load(&file_path)?;
+        assert_eq!(flags.iter().filter(|&&flag| flag).count(), 1);
+        drop(flags);
+
+        // Only the first four bytes include a one, and zero isn't a valid
+        // `NonZeroU32`
+        let err = CheckedBackedBuffer::<NonZeroU32>::load(&file_path).unwrap_err();
+        assert!(matches!(
+            err,
+            MmapBufferError::InvalidBitPattern { index: 1 }
+        ));
+
+        BackedBuffer::<u8>::load(&file_path)?[5] = 2;
+        let err = CheckedBackedBuffer::<bool>::load(&file_path).unwrap_err();
+        assert!(matches!(
+            err,
+            MmapBufferError::InvalidBitPattern { index: 5 }
+        ));
+
+        let one = NonZeroU32::new(1).unwrap();
+        let ids = CheckedBackedBuffer::new(4, one, file_path.clone())?;
+        assert_eq!(&ids[..], &[one; 4]);
+
+        Ok(())
+    }
+}
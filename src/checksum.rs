@@ -0,0 +1,354 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use bytemuck::Pod;
+
+use crate::{dirty::page_size, BackedBuffer, MmapBufferError};
+
+/// Lookup table for the reflected CRC-32 (IEEE) polynomial
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE) checksum of `bytes`
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// 32-bit xxHash of `bytes`, as used by the LZ4 frame format
+pub(crate) fn xxh32(bytes: &[u8], seed: u32) -> u32 {
+    const PRIME1: u32 = 2654435761;
+    const PRIME2: u32 = 2246822519;
+    const PRIME3: u32 = 3266489917;
+    const PRIME4: u32 = 668265263;
+    const PRIME5: u32 = 374761393;
+
+    let word = |chunk: &[u8]| u32::from_le_bytes(chunk.try_into().unwrap());
+    let round = |acc: u32, lane: u32| {
+        acc.wrapping_add(lane.wrapping_mul(PRIME2))
+            .rotate_left(13)
+            .wrapping_mul(PRIME1)
+    };
+
+    let stripes = bytes.chunks_exact(16);
+    let tail = stripes.remainder();
+    let mut hash = if bytes.len() >= 16 {
+        let mut lanes = [
+            seed.wrapping_add(PRIME1).wrapping_add(PRIME2),
+            seed.wrapping_add(PRIME2),
+            seed,
+            seed.wrapping_sub(PRIME1),
+        ];
+        for stripe in stripes {
+            for (lane, chunk) in lanes.iter_mut().zip(stripe.chunks_exact(4)) {
+                *lane = round(*lane, word(chunk));
+            }
+        }
+
+        lanes[0]
+            .rotate_left(1)
+            .wrapping_add(lanes[1].rotate_left(7))
+            .wrapping_add(lanes[2].rotate_left(12))
+            .wrapping_add(lanes[3].rotate_left(18))
+    } else {
+        seed.wrapping_add(PRIME5)
+    };
+    hash = hash.wrapping_add(bytes.len() as u32);
+
+    let words = tail.chunks_exact(4);
+    let rest = words.remainder();
+    for chunk in words {
+        hash = hash
+            .wrapping_add(word(chunk).wrapping_mul(PRIME3))
+            .rotate_left(17)
+            .wrapping_mul(PRIME4);
+    }
+    for byte in rest {
+        hash = hash
+            .wrapping_add((*byte as u32).wrapping_mul(PRIME5))
+            .rotate_left(11)
+            .wrapping_mul(PRIME1);
+    }
+
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(PRIME2);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(PRIME3);
+    hash ^ (hash >> 16)
+}
+
+/// Path of the sidecar file holding the checksum of the file at `path`
+pub(crate) fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = OsString::from(path.as_os_str());
+    sidecar.push(".crc32");
+    sidecar.into()
+}
+
+/// Read the checksum stored in a sidecar file
+pub(crate) fn read_checksum(mut sidecar: &File) -> Result<u32, MmapBufferError> {
+    let mut bytes = [0; 4];
+    sidecar.seek(SeekFrom::Start(0))?;
+    sidecar.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Overwrite the checksum stored in a sidecar file
+pub(crate) fn write_checksum(mut sidecar: &File, checksum: u32) -> Result<(), MmapBufferError> {
+    sidecar.seek(SeekFrom::Start(0))?;
+    sidecar.write_all(&checksum.to_le_bytes())?;
+
+    Ok(())
+}
+
+/// Whether a sidecar file holds per-page checksums after the checksum of
+/// the whole file
+pub(crate) fn has_page_checksums(sidecar: &File) -> Result<bool, MmapBufferError> {
+    Ok(sidecar.metadata()?.len() > 4)
+}
+
+/// Store the checksum of every page of `bytes` after the checksum of the
+/// whole file, along with the page size they were computed for, so other
+/// machines can still check them
+pub(crate) fn write_page_checksums(
+    mut sidecar: &File,
+    bytes: &[u8],
+) -> Result<(), MmapBufferError> {
+    let page_size = page_size();
+    let mut table = (page_size as u32).to_le_bytes().to_vec();
+    for page in bytes.chunks(page_size) {
+        table.extend(crc32(page).to_le_bytes());
+    }
+
+    sidecar.seek(SeekFrom::Start(4))?;
+    sidecar.write_all(&table)?;
+    sidecar.set_len(4 + table.len() as u64)?;
+
+    Ok(())
+}
+
+/// Check the contents of a mapping against the checksums in a sidecar file.
+/// If the file has per-page checksums, the error lists the ranges of
+/// elements of size `element_size`, starting `offset` bytes in, which lie
+/// on pages that don't match.
+pub(crate) fn check(
+    mut sidecar: &File,
+    bytes: &[u8],
+    offset: usize,
+    element_size: usize,
+) -> Result<(), MmapBufferError> {
+    if read_checksum(sidecar)? == crc32(bytes) {
+        return Ok(());
+    }
+
+    let mut table = Vec::new();
+    sidecar.read_to_end(&mut table)?;
+    let Some((page_size, checksums)) = table.split_first_chunk::<4>() else {
+        return Err(MmapBufferError::ChecksumMismatch);
+    };
+
+    // Pages of a different size, or a different number of them, say nothing
+    // about which elements are affected
+    let page_size = u32::from_le_bytes(*page_size) as usize;
+    if page_size == 0 || checksums.len() / 4 != bytes.len().div_ceil(page_size) {
+        return Err(MmapBufferError::ChecksumMismatch);
+    }
+
+    let element_size = element_size.max(1);
+    let capacity = bytes.len().saturating_sub(offset) / element_size;
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (index, (page, checksum)) in bytes
+        .chunks(page_size)
+        .zip(checksums.chunks_exact(4))
+        .enumerate()
+    {
+        if crc32(page).to_le_bytes() == checksum {
+            continue;
+        }
+
+        let start_byte = (index * page_size).saturating_sub(offset);
+        let end_byte = (index * page_size + page.len()).saturating_sub(offset);
+        let start = start_byte / element_size;
+        let end = end_byte.div_ceil(element_size).min(capacity);
+
+        match ranges.last_mut() {
+            Some(last) if last.end >= start => last.end = last.end.max(end),
+            _ => ranges.push(start..end),
+        }
+    }
+
+    // A damaged header holds no elements, but is still damage
+    if ranges.is_empty() {
+        Err(MmapBufferError::ChecksumMismatch)
+    } else {
+        Err(MmapBufferError::PageChecksumMismatch { ranges })
+    }
+}
+
+impl<T: Pod> BackedBuffer<T> {
+    /// CRC-32 checksum of the whole mapping, including any header and any
+    /// elements hidden by [`shrink`](Self::shrink).
+    pub fn checksum(&self) -> u32 {
+        crc32(&self.mmap[..])
+    }
+
+    /// Check the contents against the checksum stored in the sidecar file,
+    /// failing with [`MmapBufferError::ChecksumMismatch`] if they differ, or
+    /// with [`MmapBufferError::PageChecksumMismatch`] if the file has
+    /// per-page checksums. Always succeeds for buffers opened without
+    /// checksums.
+    pub fn verify(&self) -> Result<(), MmapBufferError> {
+        match &self.checksum {
+            Some(sidecar) => check(sidecar, &self.mmap, self.offset, std::mem::size_of::<T>()),
+            None => Ok(()),
+        }
+    }
+
+    /// Store the current checksum in the sidecar file, if there is one
+    pub(crate) fn update_checksum(&self) -> Result<(), MmapBufferError> {
+        if let Some(sidecar) = &self.checksum {
+            write_checksum(sidecar, self.checksum())?;
+            if has_page_checksums(sidecar)? {
+                write_page_checksums(sidecar, &self.mmap)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, sidecar_path, xxh32};
+    use crate::{dirty::page_size, BackedBufferBuilder, LockMode, MmapBufferError};
+    use std::{
+        error::Error,
+        fs::OpenOptions,
+        io::{Seek, SeekFrom, Write},
+        path::Path,
+    };
+
+    #[test]
+    fn known_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn known_xxh32() {
+        assert_eq!(xxh32(b"", 0), 0x02cc5d05);
+        assert_eq!(xxh32(b"abc", 0), 0x32d153ff);
+        assert_eq!(
+            xxh32(b"Nobody inspects the spammish repetition", 0),
+            0xe2293b2f
+        );
+    }
+
+    #[test]
+    fn detect_corruption() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.checksum(true);
+
+        {
+            let mut buf = options.create::<u32>(1024, file_path.clone())?;
+            buf[10] = 10;
+            buf.flush()?;
+            buf[20] = 20;
+        }
+
+        let buf = options.load::<u32>(file_path.clone())?;
+        assert_eq!(buf[20], 20);
+        buf.verify()?;
+        drop(buf);
+
+        // Simulate bit rot behind the buffer's back
+        OpenOptions::new()
+            .write(true)
+            .open(file_path.clone())?
+            .write_all(&[0xff])?;
+
+        let err = options.load::<u32>(file_path.clone()).unwrap_err();
+        assert!(matches!(err, MmapBufferError::ChecksumMismatch));
+        assert!(sidecar_path(&file_path).exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn flush_range() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.checksum(true).lock(LockMode::Unlocked);
+        let mut buf = options.create::<u32>(1024, file_path.clone())?;
+        buf[10] = 10;
+        buf.flush_range(10, 1)?;
+
+        // Crash right after, without the flush on drop
+        std::mem::forget(buf);
+        assert_eq!(options.load::<u32>(file_path)?[10], 10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn torn_pages() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        let page = page_size() / 4;
+
+        let mut options = BackedBufferBuilder::new();
+        options.checksum(true).page_checksums(true);
+        let mut buf = options.create::<u32>(4 * page, file_path.clone())?;
+        buf[page] = 1;
+        drop(buf);
+
+        // Damage the third page, as a torn write would
+        let mut file = OpenOptions::new().write(true).open(file_path.clone())?;
+        file.seek(SeekFrom::Start(4 * 2 * page as u64 + 10))?;
+        file.write_all(&[0xff])?;
+
+        let err = options.load::<u32>(file_path.clone()).unwrap_err();
+        match err {
+            MmapBufferError::PageChecksumMismatch { ranges } => {
+                assert_eq!(ranges, vec![(2 * page)..(3 * page)]);
+            }
+            err => panic!("unexpected error {err}"),
+        }
+
+        // Files without per-page checksums can only tell something is wrong
+        options.page_checksums(false);
+        std::fs::write(sidecar_path(&file_path), [0; 4])?;
+        let err = options.load::<u32>(file_path).unwrap_err();
+        assert!(matches!(err, MmapBufferError::ChecksumMismatch));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, Buffer};
+
+/// Compare buffers by their elements, like slices. Slice comparisons
+/// already compile down to `memcmp` for integers and other types whose
+/// equality is bitwise, so these do too.
+macro_rules! impl_comparisons {
+    ($($buffer:ident),*) => {
+        $(
+            impl<T: Pod + PartialEq> PartialEq for $buffer<T> {
+                fn eq(&self, other: &Self) -> bool {
+                    self[..] == other[..]
+                }
+            }
+
+            impl<T: Pod + Eq> Eq for $buffer<T> {}
+
+            impl<T: Pod + PartialEq> PartialEq<[T]> for $buffer<T> {
+                fn eq(&self, other: &[T]) -> bool {
+                    self[..] == *other
+                }
+            }
+
+            impl<T: Pod + PartialEq, const N: usize> PartialEq<[T; N]> for $buffer<T> {
+                fn eq(&self, other: &[T; N]) -> bool {
+                    self[..] == other[..]
+                }
+            }
+
+            impl<T: Pod + PartialEq> PartialEq<Vec<T>> for $buffer<T> {
+                fn eq(&self, other: &Vec<T>) -> bool {
+                    self[..] == other[..]
+                }
+            }
+
+            impl<T: Pod + PartialOrd> PartialOrd for $buffer<T> {
+                fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                    self[..].partial_cmp(&other[..])
+                }
+            }
+
+            impl<T: Pod + Ord> Ord for $buffer<T> {
+                fn cmp(&self, other: &Self) -> Ordering {
+                    self[..].cmp(&other[..])
+                }
+            }
+
+            /// Hashes the elements, consistently with equality
+            impl<T: Pod + Hash> Hash for $buffer<T> {
+                fn hash<H: Hasher>(&self, state: &mut H) {
+                    self[..].hash(state)
+                }
+            }
+        )*
+    };
+}
+
+impl_comparisons!(BackedBuffer, Buffer);
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, Buffer};
+    use std::{collections::HashSet, error::Error, path::Path};
+
+    #[test]
+    fn compare_contents() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut disk = BackedBuffer::<u32>::new(3, file_path)?;
+        disk.copy_from_slice(&[1, 2, 3]);
+        let mut anon = BackedBuffer::<u32>::anonymous(3)?;
+        assert!(anon < disk);
+
+        anon.copy_from_slice(&[1, 2, 3]);
+        assert_eq!(disk, anon);
+        assert_eq!(disk, [1, 2, 3]);
+        assert!(Buffer::Memory(vec![1, 2, 3]) == vec![1, 2, 3]);
+
+        let mut set = HashSet::new();
+        set.insert(disk);
+        assert!(set.contains(&anon));
+
+        // Not bitwise, NaN still differs from itself
+        let mut nan = BackedBuffer::<f32>::anonymous(1)?;
+        nan[0] = f32::NAN;
+        assert_ne!(nan, nan);
+
+        Ok(())
+    }
+}
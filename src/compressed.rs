@@ -0,0 +1,586 @@
+use std::{
+    fs::File,
+    io,
+    marker::PhantomData,
+    ops::{Deref, Range},
+    path::Path,
+    sync::OnceLock,
+};
+
+use bytemuck::{try_cast_slice, Pod};
+use fs2::FileExt;
+use memmap2::{Mmap, MmapOptions, MmapRaw};
+
+use crate::{checksum::xxh32, MmapBufferError};
+
+/// Identifies an LZ4 frame
+const LZ4_MAGIC: u32 = 0x184D2204;
+
+/// Identifies a zstd frame, which isn't supported yet
+const ZSTD_MAGIC: u32 = 0xFD2FB528;
+
+/// Skippable frames carry user data, with the low four bits free
+const SKIPPABLE_MAGIC: u32 = 0x184D2A50;
+
+/// Bits of the frame descriptor's `FLG` byte
+const BLOCK_INDEPENDENT: u8 = 1 << 5;
+const BLOCK_CHECKSUM: u8 = 1 << 4;
+const CONTENT_SIZE: u8 = 1 << 3;
+const CONTENT_CHECKSUM: u8 = 1 << 2;
+const DICT_ID: u8 = 1;
+
+/// Set in a block's size when it is stored uncompressed
+const UNCOMPRESSED: u32 = 1 << 31;
+
+/// An immutable buffer of `T` loaded from a file of
+/// [LZ4 frames](https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md),
+/// such as the output of the `lz4` command line tool. Only LZ4 is
+/// supported, zstd frames are rejected.
+///
+/// Each frame is decompressed into anonymous memory the first time part of
+/// it is accessed, and stays decompressed from then on, so only the parts
+/// of a large archive which are actually used take up memory. This needs
+/// frames to record their uncompressed size, as written by
+/// `lz4 --content-size`, to know where each one ends up. Frames which don't
+/// are decompressed right away when loading.
+///
+/// Several concatenated frames are decompressed back to back, and
+/// skippable frames are ignored. Block and content checksums are checked
+/// where present, as each frame is decompressed.
+///
+/// ```no_run
+/// use mmap_buffer::CompressedBuffer;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let archive = CompressedBuffer::<f32>::load("samples.f32.lz4")?;
+/// let recent = archive.get(archive.len() - 1000..archive.len())?;
+/// let mean = recent.iter().sum::<f32>() / recent.len() as f32;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CompressedBuffer<T: Pod> {
+    input: Mmap,
+    /// Anonymous memory each frame is decompressed into
+    output: MmapRaw,
+    frames: Vec<Frame>,
+    len: usize,
+    /// Holds a shared lock while the file is read from
+    file: File,
+    _ph: PhantomData<T>,
+}
+
+/// Where a frame's blocks are, and how they should be decoded
+struct Frame {
+    flags: u8,
+    max_block_size: usize,
+    content_size: Option<usize>,
+    /// Byte offset of the first block in the file
+    blocks_start: usize,
+    /// Number of blocks, not counting the end mark
+    blocks: usize,
+    /// Byte offset of the decompressed frame in the output
+    start: usize,
+    /// Decompressed size in bytes
+    size: usize,
+    /// Set once the frame has been decompressed, or failed to
+    decoded: OnceLock<Result<(), Damage>>,
+}
+
+/// Why a frame failed to decompress, kept to report it again on later
+/// accesses
+#[derive(Clone, Copy, Debug)]
+enum Damage {
+    Invalid,
+    Checksum,
+}
+
+impl From<MmapBufferError> for Damage {
+    fn from(err: MmapBufferError) -> Self {
+        match err {
+            MmapBufferError::ChecksumMismatch => Damage::Checksum,
+            _ => Damage::Invalid,
+        }
+    }
+}
+
+impl From<Damage> for MmapBufferError {
+    fn from(damage: Damage) -> Self {
+        match damage {
+            Damage::Invalid => MmapBufferError::InvalidHeader,
+            Damage::Checksum => MmapBufferError::ChecksumMismatch,
+        }
+    }
+}
+
+impl<T: Pod> CompressedBuffer<T> {
+    /// Load the LZ4 frames in the file at the given path. Fails with
+    /// [`MmapBufferError::InvalidHeader`] if the file isn't a valid stream
+    /// of LZ4 frames, and with [`MmapBufferError::SizeMismatch`] if it
+    /// doesn't decompress to a whole number of `T`. Frames compressed
+    /// against a dictionary, and zstd frames, fail with an error of kind
+    /// [`Unsupported`](io::ErrorKind::Unsupported).
+    ///
+    /// Damage inside a frame may only be found once it is decompressed,
+    /// see [`get`](Self::get).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = File::open(path)?;
+        FileExt::try_lock_shared(&file).map_err(MmapBufferError::from_lock_error)?;
+
+        // SAFETY: writers through this crate are excluded by the lock
+        let input = unsafe { MmapOptions::new().map(&file)? };
+
+        // Find every frame first, so the decompressed size is bounded before
+        // anything is decompressed
+        let mut frames = Vec::new();
+        let mut pos = 0;
+        while let Some(frame) = read_frame(&input, &mut pos)? {
+            frames.push(frame);
+        }
+
+        let capacity = frames.iter().try_fold(0usize, |capacity, frame| {
+            capacity
+                .checked_add(frame_bound(frame)?)
+                .ok_or(MmapBufferError::CapacityOverflow)
+        })?;
+
+        // Pages past the end of the decompressed data are never touched, so
+        // overestimating the size costs nothing
+        let output = MmapOptions::new().len(capacity.max(1)).map_anon()?;
+        let output = MmapRaw::from(output);
+
+        let mut len = 0;
+        for frame in &mut frames {
+            frame.start = len;
+            frame.size = match frame.content_size {
+                Some(size) => size,
+                None => {
+                    // SAFETY: nothing else has access to the output yet, and
+                    // the frame's bound lies within it
+                    let region = unsafe {
+                        std::slice::from_raw_parts_mut(
+                            output.as_mut_ptr().add(len),
+                            frame_bound(frame)?,
+                        )
+                    };
+                    let size = decode_frame(&input, frame, region)?;
+                    frame.decoded = OnceLock::from(Ok(()));
+                    size
+                }
+            };
+            len += frame.size;
+        }
+
+        let element_size = std::mem::size_of::<T>();
+        if !len.is_multiple_of(element_size) {
+            return Err(MmapBufferError::SizeMismatch {
+                file_size: len,
+                element_size,
+            });
+        }
+
+        Ok(Self {
+            input,
+            output,
+            frames,
+            len: len / element_size,
+            file,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Number of elements in the buffer, without decompressing anything
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the buffer holds no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// A range of the buffer, decompressing the frames it overlaps if they
+    /// weren't already. The range is in units of `T`, not in bytes. Fails
+    /// with [`MmapBufferError::ChecksumMismatch`] or
+    /// [`MmapBufferError::InvalidHeader`] if one of those frames turns out
+    /// to be corrupted, and keeps failing for every range overlapping it.
+    pub fn get(&self, range: Range<usize>) -> Result<&[T], MmapBufferError> {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "range must be within the buffer!"
+        );
+        let size = std::mem::size_of::<T>();
+        let (start, end) = (range.start * size, range.end * size);
+
+        let first = self
+            .frames
+            .partition_point(|frame| frame.start + frame.size <= start);
+        for frame in self.frames[first..]
+            .iter()
+            .take_while(|frame| frame.start < end)
+        {
+            self.decode(frame)?;
+        }
+
+        // SAFETY: every frame overlapping the range has been decompressed,
+        // and is never written again
+        let bytes =
+            unsafe { std::slice::from_raw_parts(self.output.as_ptr().add(start), end - start) };
+        Ok(try_cast_slice(bytes).unwrap())
+    }
+
+    /// Decompress every frame which wasn't already, returning the whole
+    /// buffer, see [`get`](Self::get).
+    pub fn decode_all(&self) -> Result<&[T], MmapBufferError> {
+        self.get(0..self.len)
+    }
+
+    fn decode(&self, frame: &Frame) -> Result<(), MmapBufferError> {
+        let result = frame.decoded.get_or_init(|| {
+            // SAFETY: frames don't overlap in the output, and only this
+            // initializer ever writes to this one's, before anything reads
+            // from it
+            let region = unsafe {
+                std::slice::from_raw_parts_mut(
+                    self.output.as_mut_ptr().add(frame.start),
+                    frame.size,
+                )
+            };
+            decode_frame(&self.input, frame, region)
+                .map(drop)
+                .map_err(Damage::from)
+        });
+
+        Ok((*result)?)
+    }
+}
+
+/// Most bytes `frame` can decompress to
+fn frame_bound(frame: &Frame) -> Result<usize, MmapBufferError> {
+    match frame.content_size {
+        Some(size) => Ok(size),
+        None => frame
+            .blocks
+            .checked_mul(frame.max_block_size)
+            .ok_or(MmapBufferError::CapacityOverflow),
+    }
+}
+
+/// Parse the header of the next LZ4 frame starting at `pos`, skipping any
+/// skippable frames before it, and move `pos` past its end. Returns `None`
+/// at the end of the file.
+fn read_frame(input: &[u8], pos: &mut usize) -> Result<Option<Frame>, MmapBufferError> {
+    loop {
+        if *pos == input.len() {
+            return Ok(None);
+        }
+
+        match take_u32(input, pos)? {
+            LZ4_MAGIC => break,
+            magic if magic & !0xF == SKIPPABLE_MAGIC => {
+                let len = take_u32(input, pos)? as usize;
+                take(input, pos, len)?;
+            }
+            ZSTD_MAGIC => return Err(unsupported("zstd frames aren't supported")),
+            _ => return Err(MmapBufferError::InvalidHeader),
+        }
+    }
+
+    let descriptor_start = *pos;
+    let (flags, block_descriptor) = match take(input, pos, 2)? {
+        &[flags, block_descriptor] => (flags, block_descriptor),
+        _ => unreachable!(),
+    };
+
+    // Only version 01 exists, and reserved bits must be zero
+    if flags >> 6 != 0b01 || flags & 0b10 != 0 || block_descriptor & 0x8F != 0 {
+        return Err(MmapBufferError::InvalidHeader);
+    }
+    if flags & DICT_ID != 0 {
+        return Err(unsupported("LZ4 frames with dictionaries aren't supported"));
+    }
+
+    let max_block_size = match block_descriptor >> 4 {
+        4 => 64 << 10,
+        5 => 256 << 10,
+        6 => 1 << 20,
+        7 => 4 << 20,
+        _ => return Err(MmapBufferError::InvalidHeader),
+    };
+
+    let content_size = if flags & CONTENT_SIZE != 0 {
+        let size = u64::from_le_bytes(take(input, pos, 8)?.try_into().unwrap());
+        Some(usize::try_from(size).map_err(|_| MmapBufferError::CapacityOverflow)?)
+    } else {
+        None
+    };
+
+    let descriptor = &input[descriptor_start..*pos];
+    if take(input, pos, 1)?[0] != (xxh32(descriptor, 0) >> 8) as u8 {
+        return Err(MmapBufferError::ChecksumMismatch);
+    }
+
+    let mut frame = Frame {
+        flags,
+        max_block_size,
+        content_size,
+        blocks_start: *pos,
+        blocks: 0,
+        start: 0,
+        size: 0,
+        decoded: OnceLock::new(),
+    };
+    while read_block(input, pos, &frame, false)?.is_some() {
+        frame.blocks += 1;
+    }
+    if flags & CONTENT_CHECKSUM != 0 {
+        take_u32(input, pos)?;
+    }
+
+    if let Some(size) = frame.content_size {
+        if size > frame.blocks.saturating_mul(max_block_size) {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+    }
+
+    Ok(Some(frame))
+}
+
+/// Decompress every block of `frame` into the start of `output`, returning
+/// the number of bytes written
+fn decode_frame(input: &[u8], frame: &Frame, output: &mut [u8]) -> Result<usize, MmapBufferError> {
+    let mut pos = frame.blocks_start;
+    let mut len = 0;
+
+    while let Some((block, compressed)) = read_block(input, &mut pos, frame, true)? {
+        let end = usize::min(len + frame.max_block_size, output.len());
+        len = if compressed {
+            // Linked blocks may refer back to earlier blocks of the frame
+            let history = if frame.flags & BLOCK_INDEPENDENT != 0 {
+                len
+            } else {
+                0
+            };
+            decode_block(block, &mut output[..end], len, history)?
+        } else {
+            output[..end]
+                .get_mut(len..len + block.len())
+                .ok_or(MmapBufferError::InvalidHeader)?
+                .copy_from_slice(block);
+            len + block.len()
+        };
+    }
+
+    if frame.content_size.is_some_and(|size| size != len) {
+        return Err(MmapBufferError::InvalidHeader);
+    }
+    if frame.flags & CONTENT_CHECKSUM != 0 && take_u32(input, &mut pos)? != xxh32(&output[..len], 0)
+    {
+        return Err(MmapBufferError::ChecksumMismatch);
+    }
+
+    Ok(len)
+}
+
+/// Read the block starting at `pos` and move `pos` past it, returning its
+/// data and whether it is compressed, or `None` at the end mark. Its
+/// checksum is only checked if `verify` is set.
+fn read_block<'a>(
+    input: &'a [u8],
+    pos: &mut usize,
+    frame: &Frame,
+    verify: bool,
+) -> Result<Option<(&'a [u8], bool)>, MmapBufferError> {
+    let size = take_u32(input, pos)?;
+    if size == 0 {
+        return Ok(None);
+    }
+
+    let len = (size & !UNCOMPRESSED) as usize;
+    if len > frame.max_block_size {
+        return Err(MmapBufferError::InvalidHeader);
+    }
+
+    let block = take(input, pos, len)?;
+    if frame.flags & BLOCK_CHECKSUM != 0 {
+        let checksum = take_u32(input, pos)?;
+        if verify && checksum != xxh32(block, 0) {
+            return Err(MmapBufferError::ChecksumMismatch);
+        }
+    }
+
+    Ok(Some((block, size & UNCOMPRESSED == 0)))
+}
+
+/// Decompress an LZ4 block onto `output[..pos]`, returning the new end of
+/// the output. Matches may copy from anywhere in `output[history..]`.
+fn decode_block(
+    block: &[u8],
+    output: &mut [u8],
+    mut pos: usize,
+    history: usize,
+) -> Result<usize, MmapBufferError> {
+    let mut i = 0;
+    loop {
+        let token = take(block, &mut i, 1)?[0];
+        let literals = sequence_length(block, &mut i, token >> 4)?;
+        output
+            .get_mut(pos..pos.saturating_add(literals))
+            .ok_or(MmapBufferError::InvalidHeader)?
+            .copy_from_slice(take(block, &mut i, literals)?);
+        pos += literals;
+
+        // The last sequence is only literals
+        if i == block.len() {
+            return Ok(pos);
+        }
+
+        let offset = u16::from_le_bytes(take(block, &mut i, 2)?.try_into().unwrap()) as usize;
+        let len = sequence_length(block, &mut i, token & 0xF)? + 4;
+        if offset == 0 || offset > pos - history || len > output.len() - pos {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        // A match may overlap its own output, repeating the last `offset`
+        // bytes, so copy in runs which only read bytes already written
+        let start = pos - offset;
+        let end = pos + len;
+        while pos < end {
+            let run = usize::min(pos - start, end - pos);
+            output.copy_within(start..start + run, pos);
+            pos += run;
+        }
+    }
+}
+
+/// Literal or match length of a sequence, starting from the four bits in
+/// its token and continued by bytes of 255 in the block
+fn sequence_length(block: &[u8], i: &mut usize, nibble: u8) -> Result<usize, MmapBufferError> {
+    let mut len = nibble as usize;
+    if nibble == 0xF {
+        loop {
+            let byte = take(block, i, 1)?[0];
+            len += byte as usize;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+
+    Ok(len)
+}
+
+/// The `len` bytes at `pos`, moving `pos` past them
+fn take<'a>(input: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], MmapBufferError> {
+    let bytes = input
+        .get(*pos..pos.saturating_add(len))
+        .ok_or(MmapBufferError::InvalidHeader)?;
+    *pos += len;
+    Ok(bytes)
+}
+
+fn take_u32(input: &[u8], pos: &mut usize) -> Result<u32, MmapBufferError> {
+    Ok(u32::from_le_bytes(take(input, pos, 4)?.try_into().unwrap()))
+}
+
+fn unsupported(message: &str) -> MmapBufferError {
+    MmapBufferError::Io(io::Error::new(io::ErrorKind::Unsupported, message))
+}
+
+impl<T: Pod> Deref for CompressedBuffer<T> {
+    type Target = [T];
+
+    /// The whole buffer, see [`decode_all`](CompressedBuffer::decode_all).
+    /// Panics if any frame is corrupted.
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.decode_all().expect("compressed data is corrupted!")
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for CompressedBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self.deref()
+    }
+}
+
+impl<T: Pod> Drop for CompressedBuffer<T> {
+    fn drop(&mut self) {
+        // Ignore the error, advisory locks are still kind of sus
+        self.file.unlock().unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompressedBuffer;
+    use crate::MmapBufferError;
+    use std::{error::Error, io, path::Path};
+
+    /// A skippable frame, then 40000 `u32`s counting up to 15 over and over
+    /// in 64 KiB linked blocks with block checksums and the content size,
+    /// then four `u32`s in a frame with a single uncompressed block, all
+    /// made with the `lz4` command line tool
+    const FIXTURE: &[u8] = include_bytes!("../testdata/pattern.lz4");
+
+    #[test]
+    fn decompress() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        std::fs::write(&file_path, FIXTURE)?;
+        let buf = CompressedBuffer::<u32>::load(&file_path)?;
+        assert_eq!(buf.len(), 40004);
+
+        // Only the frame without a content size was decompressed up front,
+        // and each access only decompresses the frames it touches
+        let decoded = |buf: &CompressedBuffer<u32>| {
+            buf.frames
+                .iter()
+                .map(|frame| frame.decoded.get().is_some())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(decoded(&buf), [false, true]);
+        assert_eq!(
+            buf.get(40000..40004)?,
+            [0xdeadbeef, 0x01234567, 0x89abcdef, 0xfeedface]
+        );
+        assert_eq!(decoded(&buf), [false, true]);
+        assert_eq!(buf.get(39990..40001)?[10..], [0xdeadbeef]);
+        assert_eq!(decoded(&buf), [true, true]);
+        assert!(buf[..40000]
+            .iter()
+            .enumerate()
+            .all(|(i, &x)| x == i as u32 % 16));
+
+        let err = CompressedBuffer::<[u8; 3]>::load(&file_path).err().unwrap();
+        assert!(matches!(err, MmapBufferError::SizeMismatch { .. }));
+
+        // Damage inside the second block only shows once its frame is
+        // decompressed, and keeps failing after that
+        let mut damaged = FIXTURE.to_vec();
+        damaged[400] ^= 1;
+        std::fs::write(&file_path, &damaged)?;
+        let buf = CompressedBuffer::<u32>::load(&file_path)?;
+        assert_eq!(buf.get(40000..40004)?[0], 0xdeadbeef);
+        for _ in 0..2 {
+            let err = buf.get(0..1).unwrap_err();
+            assert!(matches!(err, MmapBufferError::ChecksumMismatch));
+        }
+        drop(buf);
+
+        std::fs::write(&file_path, &FIXTURE[..FIXTURE.len() - 1])?;
+        let err = CompressedBuffer::<u32>::load(&file_path).err().unwrap();
+        assert!(matches!(err, MmapBufferError::InvalidHeader));
+
+        std::fs::write(&file_path, 0xFD2FB528u32.to_le_bytes())?;
+        let err = CompressedBuffer::<u32>::load(&file_path).err().unwrap();
+        assert!(
+            matches!(err, MmapBufferError::Io(err) if err.kind() == io::ErrorKind::Unsupported)
+        );
+
+        std::fs::write(&file_path, [])?;
+        assert!(CompressedBuffer::<u32>::load(&file_path)?.is_empty());
+
+        Ok(())
+    }
+}
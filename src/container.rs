@@ -0,0 +1,300 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+};
+
+use bytemuck::{Pod, Zeroable};
+use fs2::FileExt;
+use memmap2::{MmapMut, MmapOptions};
+
+use crate::{byte_size, header::type_hash, MmapBufferError};
+
+/// Identifies container files
+const MAGIC: [u8; 8] = *b"MMAPCONT";
+
+/// Bumped whenever the layout of the table of contents changes
+const VERSION: u32 = 1;
+
+/// Size of the table of contents at the start of the file. Sections start
+/// on multiples of this, so they are suitably aligned for any `T`.
+const TOC_SIZE: usize = 4096;
+
+/// Size of the fixed fields before the first section entry
+const PREAMBLE_SIZE: usize = 16;
+
+/// Maximum number of sections in one container
+pub const MAX_SECTIONS: usize = (TOC_SIZE - PREAMBLE_SIZE) / std::mem::size_of::<Entry>();
+
+/// Maximum length of a section name in bytes
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Describes one section in the table of contents
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    /// Zero-padded UTF-8 name
+    name: [u8; MAX_NAME_LEN],
+    element_size: u32,
+    align: u32,
+    type_hash: u64,
+    /// Byte offset of the section in the file
+    offset: u64,
+    /// Number of elements in the section
+    len: u64,
+}
+
+// SAFETY: `repr(C)` with no padding, and all fields are `Pod`
+unsafe impl Zeroable for Entry {}
+unsafe impl Pod for Entry {}
+
+impl Entry {
+    fn name(&self) -> &str {
+        let len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(MAX_NAME_LEN);
+        std::str::from_utf8(&self.name[..len]).unwrap_or_default()
+    }
+}
+
+/// A single file holding several named sections, each a slice of its own
+/// element type.
+///
+/// The file starts with a table of contents recording the name, element
+/// type, position and length of every section, followed by the sections
+/// themselves, each aligned to 4 KiB. Like a [header](crate::BackedBufferBuilder::header),
+/// the table of contents catches opening a section as the wrong type.
+/// Sections have a fixed length once added, but more can be added at any
+/// time, up to [`MAX_SECTIONS`].
+///
+/// ```
+/// use mmap_buffer::Container;
+///
+/// # let dir = tempfile::tempdir().unwrap();
+/// # let path = dir.path().join("data");
+/// let mut container = Container::create(&path).unwrap();
+/// container.add_section::<f32>("embeddings", 1024).unwrap();
+/// container.add_section::<u32>("ids", 8).unwrap()[0] = 7;
+///
+/// assert_eq!(container.section::<u32>("ids").unwrap()[0], 7);
+/// assert_eq!(container.section::<f32>("embeddings").unwrap().len(), 1024);
+/// ```
+pub struct Container {
+    mmap: MmapMut,
+    file: File,
+}
+
+impl Container {
+    /// Create a new, empty container at the given path. Any existing file
+    /// is truncated.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path)?;
+
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+        file.set_len(TOC_SIZE as u64)?;
+
+        // SAFETY: we hold an exclusive lock on the file
+        let mut mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+        mmap[..8].copy_from_slice(&MAGIC);
+        mmap[8..12].copy_from_slice(&VERSION.to_ne_bytes());
+
+        Ok(Self { mmap, file })
+    }
+
+    /// Open an existing container.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+
+        // SAFETY: we hold an exclusive lock on the file
+        let mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+        if mmap.len() < TOC_SIZE || mmap[..8] != MAGIC || mmap[8..12] != VERSION.to_ne_bytes() {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        let container = Self { mmap, file };
+        if container.count() > MAX_SECTIONS {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        Ok(container)
+    }
+
+    /// Add a new, zeroed section of `len` elements of `T` at the end of the
+    /// file, returning it. Fails if the name is taken or too long, or the
+    /// table of contents is full.
+    pub fn add_section<T: Pod>(
+        &mut self,
+        name: &str,
+        len: usize,
+    ) -> Result<&mut [T], MmapBufferError> {
+        if name.is_empty() || name.len() > MAX_NAME_LEN || name.contains('\0') {
+            return Err(invalid_input(
+                "section names must be 1 to 32 bytes, without nulls",
+            ));
+        }
+
+        if self.entries().any(|entry| entry.name() == name) {
+            return Err(invalid_input("a section with this name already exists"));
+        }
+
+        let count = self.count();
+        if count == MAX_SECTIONS {
+            return Err(invalid_input("the container is full"));
+        }
+
+        let offset = self.mmap.len();
+        let end = byte_size::<T>(offset, len)?
+            .checked_next_multiple_of(TOC_SIZE)
+            .ok_or(MmapBufferError::CapacityOverflow)?;
+        self.file.set_len(end as u64)?;
+
+        // SAFETY: we hold an exclusive lock on the file, and the old mapping
+        // is dropped once replaced
+        self.mmap = unsafe { MmapOptions::new().map_mut(&self.file)? };
+
+        let mut entry = Entry {
+            name: [0; MAX_NAME_LEN],
+            element_size: std::mem::size_of::<T>() as u32,
+            align: std::mem::align_of::<T>() as u32,
+            type_hash: type_hash::<T>(),
+            offset: offset as u64,
+            len: len as u64,
+        };
+        entry.name[..name.len()].copy_from_slice(name.as_bytes());
+
+        let start = entry_offset(count);
+        self.mmap[start..start + std::mem::size_of::<Entry>()]
+            .copy_from_slice(bytemuck::bytes_of(&entry));
+        self.mmap[12..16].copy_from_slice(&(count as u32 + 1).to_ne_bytes());
+
+        self.section_mut(name)
+    }
+
+    /// View the section with the given name, failing with
+    /// [`MmapBufferError::TypeMismatch`] if it doesn't hold elements of `T`.
+    pub fn section<T: Pod>(&self, name: &str) -> Result<&[T], MmapBufferError> {
+        let (start, end) = self.section_range::<T>(name)?;
+        Ok(bytemuck::cast_slice(&self.mmap[start..end]))
+    }
+
+    /// Mutably view the section with the given name, failing with
+    /// [`MmapBufferError::TypeMismatch`] if it doesn't hold elements of `T`.
+    pub fn section_mut<T: Pod>(&mut self, name: &str) -> Result<&mut [T], MmapBufferError> {
+        let (start, end) = self.section_range::<T>(name)?;
+        Ok(bytemuck::cast_slice_mut(&mut self.mmap[start..end]))
+    }
+
+    /// Names of all sections, in the order they were added
+    pub fn section_names(&self) -> impl Iterator<Item = &str> {
+        (0..self.count()).map(|i| self.entry(i).name())
+    }
+
+    /// Synchronously write any outstanding changes, including the table of
+    /// contents, back to the file.
+    pub fn flush(&self) -> Result<(), MmapBufferError> {
+        Ok(self.mmap.flush()?)
+    }
+
+    fn count(&self) -> usize {
+        u32::from_ne_bytes(self.mmap[12..16].try_into().unwrap()) as usize
+    }
+
+    fn entry(&self, index: usize) -> &Entry {
+        let start = entry_offset(index);
+        bytemuck::from_bytes(&self.mmap[start..start + std::mem::size_of::<Entry>()])
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &Entry> {
+        (0..self.count()).map(|i| self.entry(i))
+    }
+
+    /// Byte range of a section, after checking its type
+    fn section_range<T: Pod>(&self, name: &str) -> Result<(usize, usize), MmapBufferError> {
+        let entry = self
+            .entries()
+            .find(|entry| entry.name() == name)
+            .ok_or_else(|| {
+                MmapBufferError::Io(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no section named {name:?}"),
+                ))
+            })?;
+
+        if entry.element_size as usize != std::mem::size_of::<T>()
+            || entry.align as usize != std::mem::align_of::<T>()
+            || entry.type_hash != type_hash::<T>()
+        {
+            return Err(MmapBufferError::TypeMismatch);
+        }
+
+        let start = entry.offset as usize;
+        let end = byte_size::<T>(start, entry.len as usize)?;
+        if !start.is_multiple_of(TOC_SIZE) || end > self.mmap.len() {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        Ok((start, end))
+    }
+}
+
+impl Drop for Container {
+    fn drop(&mut self) {
+        // Ignore the error, advisory locks are still kind of sus
+        self.file.unlock().unwrap_or(());
+    }
+}
+
+fn entry_offset(index: usize) -> usize {
+    PREAMBLE_SIZE + index * std::mem::size_of::<Entry>()
+}
+
+fn invalid_input(message: &str) -> MmapBufferError {
+    MmapBufferError::Io(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Container;
+    use crate::MmapBufferError;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn sections() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        {
+            let mut container = Container::create(file_path.clone())?;
+            container
+                .add_section::<f32>("embeddings", 3)?
+                .copy_from_slice(&[1.0, 2.0, 3.0]);
+            container
+                .add_section::<u64>("ids", 2)?
+                .copy_from_slice(&[10, 20]);
+            assert!(container.add_section::<u8>("ids", 1).is_err());
+        }
+
+        let mut container = Container::open(file_path)?;
+        assert!(container.section_names().eq(["embeddings", "ids"]));
+        assert_eq!(container.section::<f32>("embeddings")?, &[1.0, 2.0, 3.0]);
+        container.section_mut::<u64>("ids")?[1] = 30;
+        assert_eq!(container.section::<u64>("ids")?, &[10, 30]);
+
+        let err = container.section::<u32>("ids").unwrap_err();
+        assert!(matches!(err, MmapBufferError::TypeMismatch));
+        assert!(container.section::<u32>("missing").is_err());
+
+        Ok(())
+    }
+}
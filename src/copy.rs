@@ -0,0 +1,185 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use bytemuck::Pod;
+
+use crate::{header::Header, BackedBuffer, BackedBufferBuilder, MmapBufferError};
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Write a consistent copy of the buffer to the given path, which can be
+    /// loaded with the same options. Any existing file is truncated.
+    ///
+    /// The buffer is flushed first, then the file is cloned with `FICLONE`
+    /// on filesystems which share extents between files (btrfs, XFS), or
+    /// copied in the kernel with `copy_file_range`, so the copy takes
+    /// milliseconds even for large buffers and writing can resume right
+    /// after. Anonymous and copy-on-write buffers, and buffers loaded with
+    /// [`load_range`](Self::load_range), are written out from the mapping
+    /// instead.
+    pub fn snapshot_to(&self, path: impl AsRef<Path>) -> Result<(), MmapBufferError> {
+        let snapshot = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path)?;
+
+        self.copy_into(&snapshot)?;
+        snapshot.sync_all()?;
+        Ok(())
+    }
+
+    /// Write a copy of the buffer to the given path like
+    /// [`snapshot_to`](Self::snapshot_to), but atomically: the copy is
+    /// written to a temporary file in the same directory, synced to disk and
+    /// then renamed over the destination. Other programs, and the path after
+    /// a crash, only ever see the previous file or the complete copy.
+    pub fn save_as_atomic(&self, path: impl AsRef<Path>) -> Result<(), MmapBufferError> {
+        let path = path.as_ref();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let temp = tempfile::NamedTempFile::new_in(dir)?;
+        self.copy_into(temp.as_file())?;
+        temp.as_file().sync_all()?;
+        temp.persist(path).map_err(|err| err.error)?;
+
+        // Make the rename itself durable
+        #[cfg(unix)]
+        File::open(dir)?.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Write the bytes a copy of the buffer consists of to an empty file
+    fn copy_into(&self, dst: &File) -> Result<(), MmapBufferError> {
+        match &self.file {
+            Some(file) if !self.copy_on_write && !self.window => {
+                self.flush()?;
+                copy_file(file, dst)?;
+            }
+            _ => {
+                // Windows into a larger file hold just their elements
+                let start = if self.window { self.offset } else { 0 };
+                (&*dst).write_all(&self.mmap[start..])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copy the buffer to a new file at the given path, see
+    /// [`snapshot_to`](Self::snapshot_to), and load the copy as an
+    /// independent buffer with the default options. With extent sharing,
+    /// even multi-gigabyte buffers are cloned in milliseconds, and blocks
+    /// are only duplicated once either side writes to them.
+    pub fn clone_to(&self, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let path = path.as_ref();
+        self.snapshot_to(path)?;
+
+        let mut options = BackedBufferBuilder::new();
+        if self.header {
+            options.header(true).align(Header::load_align(&self.mmap));
+        }
+        options.load(path)
+    }
+}
+
+/// Replace the contents of `dst` with those of `src`, sharing their extents
+/// where the filesystem supports it. Otherwise `io::copy` falls back to
+/// `copy_file_range` on Linux, and to a plain copy elsewhere.
+pub(crate) fn copy_file(mut src: &File, mut dst: &File) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        // SAFETY: both descriptors are valid
+        if unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) } == 0 {
+            return Ok(());
+        }
+    }
+
+    dst.set_len(0)?;
+    src.seek(SeekFrom::Start(0))?;
+    dst.seek(SeekFrom::Start(0))?;
+    io::copy(&mut src, &mut dst)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, BackedBufferBuilder};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn snapshot() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        let snapshot_path = Path::join(tempdir.path(), "snapshot");
+
+        let mut options = BackedBufferBuilder::new();
+        options.header(true);
+        let mut buf = options.create::<u32>(1000, file_path.clone())?;
+        buf[999] = 1;
+        buf.snapshot_to(&snapshot_path)?;
+
+        // Later writes don't reach the snapshot
+        buf[999] = 2;
+        assert_eq!(options.load::<u32>(&snapshot_path)?[999], 1);
+
+        let mut anon = BackedBuffer::<u32>::anonymous(10)?;
+        anon[9] = 9;
+        anon.snapshot_to(&snapshot_path)?;
+        assert_eq!(&BackedBuffer::<u32>::load(&snapshot_path)?[..], &anon[..]);
+
+        drop(buf);
+        let window = BackedBuffer::<u32>::load_range(&file_path, 1000, 2)?;
+        window.snapshot_to(&snapshot_path)?;
+        assert_eq!(&BackedBuffer::<u32>::load(&snapshot_path)?[..], &window[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn save_as_atomic() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        let export_path = Path::join(tempdir.path(), "export");
+        std::fs::write(&export_path, "previous contents")?;
+
+        let mut buf = BackedBuffer::<u8>::new(4, file_path)?;
+        buf.copy_from_slice(b"data");
+        buf.save_as_atomic(&export_path)?;
+        assert_eq!(std::fs::read(&export_path)?, b"data");
+
+        // Only the destination is left behind
+        assert_eq!(std::fs::read_dir(tempdir.path())?.count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn clone_to() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        let clone_path = Path::join(tempdir.path(), "clone");
+
+        let mut options = BackedBufferBuilder::new();
+        options.header(true).align(64);
+        let mut buf = options.create::<u16>(100, file_path)?;
+        buf[50] = 50;
+        buf.resize(60)?;
+
+        let mut clone = buf.clone_to(&clone_path)?;
+        assert_eq!(&clone[..], &buf[..]);
+        clone[50] = 0;
+        assert_eq!(buf[50], 50);
+
+        Ok(())
+    }
+}
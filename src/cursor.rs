@@ -0,0 +1,167 @@
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+use crate::BackedBuffer;
+
+/// A cursor over a mapped byte buffer, implementing [`Read`], [`BufRead`],
+/// [`Write`] and [`Seek`] so it can be handed to code expecting a stream.
+///
+/// Works like [`std::io::Cursor`] over a fixed-size slice: writes never grow
+/// the buffer, and stop short at its end. Any byte buffer can be wrapped,
+/// including a borrowed `&mut BackedBuffer<u8>` or a
+/// [`ReadOnlyBuffer<u8>`](crate::ReadOnlyBuffer) for reading only.
+///
+/// ```
+/// use mmap_buffer::{BackedBuffer, BufferCursor};
+/// use std::io::{Read, Write};
+///
+/// let mut buf = BackedBuffer::<u8>::anonymous(16).unwrap();
+/// BufferCursor::new(&mut buf).write_all(b"hello").unwrap();
+///
+/// let mut hello = String::new();
+/// BufferCursor::new(&buf).take(5).read_to_string(&mut hello).unwrap();
+/// assert_eq!(hello, "hello");
+/// ```
+#[derive(Debug)]
+pub struct BufferCursor<B = BackedBuffer<u8>> {
+    inner: B,
+    position: u64,
+}
+
+impl<B> BufferCursor<B> {
+    /// Wrap a buffer, starting at position zero
+    pub fn new(inner: B) -> Self {
+        Self { inner, position: 0 }
+    }
+
+    /// Current position in bytes
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Set the position in bytes, which may be past the end of the buffer
+    pub fn set_position(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    /// Get a reference to the underlying buffer
+    pub fn get_ref(&self) -> &B {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying buffer
+    pub fn get_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    /// Unwrap the underlying buffer
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: AsRef<[u8]>> BufferCursor<B> {
+    /// Bytes from the current position to the end of the buffer
+    fn remaining(&self) -> &[u8] {
+        let bytes = self.inner.as_ref();
+        let start = self.position.min(bytes.len() as u64) as usize;
+        &bytes[start..]
+    }
+}
+
+impl<B: AsRef<[u8]>> Read for BufferCursor<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.remaining();
+        let count = remaining.len().min(buf.len());
+        buf[..count].copy_from_slice(&remaining[..count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<B: AsRef<[u8]>> BufRead for BufferCursor<B> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.remaining())
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position += amount as u64;
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Write for BufferCursor<B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bytes = self.inner.as_mut();
+        let start = self.position.min(bytes.len() as u64) as usize;
+        let count = (bytes.len() - start).min(buf.len());
+        bytes[start..start + count].copy_from_slice(&buf[..count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    /// Does nothing, as writes go straight to the mapping. Use the buffer's
+    /// own `flush` to write them back to the file.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<B: AsRef<[u8]>> Seek for BufferCursor<B> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(position) => {
+                self.position = position;
+                return Ok(position);
+            }
+            SeekFrom::End(offset) => (self.inner.as_ref().len() as u64, offset),
+            SeekFrom::Current(offset) => (self.position, offset),
+        };
+
+        self.position = base.checked_add_signed(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferCursor;
+    use crate::BackedBuffer;
+    use std::{
+        error::Error,
+        io::{BufRead, Read, Seek, SeekFrom, Write},
+        path::Path,
+    };
+
+    #[test]
+    fn cursor_io() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let buf = BackedBuffer::<u8>::new(8, file_path.clone())?;
+        let mut cursor = BufferCursor::new(buf);
+        cursor.write_all(b"ab\ncd")?;
+        assert!(cursor.write_all(b"efgh").is_err());
+        assert_eq!(cursor.position(), 8);
+
+        cursor.seek(SeekFrom::Start(0))?;
+        let mut line = String::new();
+        cursor.read_line(&mut line)?;
+        assert_eq!(line, "ab\n");
+
+        assert_eq!(cursor.seek(SeekFrom::End(-2))?, 6);
+        let mut rest = Vec::new();
+        cursor.read_to_end(&mut rest)?;
+        assert_eq!(rest, b"fg");
+        assert!(cursor.seek(SeekFrom::Current(-9)).is_err());
+        drop(cursor.into_inner());
+
+        let buf = BackedBuffer::<u8>::load(file_path)?;
+        assert_eq!(&buf[..], b"ab\ncdefg" as &[u8]);
+
+        Ok(())
+    }
+}
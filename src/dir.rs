@@ -0,0 +1,177 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+use bytemuck::Pod;
+use fs2::FileExt;
+
+use crate::{checksum, BackedBuffer, BackedBufferBuilder, MmapBufferError};
+
+/// Extension of the files holding buffers in a [`BufferDir`]
+const EXTENSION: &str = "buf";
+
+/// Name of the lock file guarding a [`BufferDir`]
+const LOCK_FILE: &str = ".lock";
+
+/// A directory of [`BackedBuffer`]s addressed by string keys, for column
+/// store style layouts with one file per column.
+///
+/// Every buffer is stored as `<key>.buf` with a
+/// [header](BackedBufferBuilder::header), so opening a key as the wrong
+/// element type fails with [`MmapBufferError::TypeMismatch`]. The directory
+/// itself is guarded by an exclusive lock on a `.lock` file inside it, so
+/// only one `BufferDir` manages it at a time, and each buffer is locked as
+/// usual on top of that.
+pub struct BufferDir {
+    path: PathBuf,
+    lock: File,
+}
+
+impl BufferDir {
+    /// Manage the directory at the given path, creating it if it doesn't
+    /// exist yet.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let path = path.as_ref().to_owned();
+        fs::create_dir_all(&path)?;
+
+        let lock = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.join(LOCK_FILE))?;
+        lock.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+
+        Ok(Self { path, lock })
+    }
+
+    /// Create a new buffer with a fixed capacity under `key`, replacing any
+    /// existing buffer with that key.
+    pub fn create<T: Pod>(
+        &self,
+        key: &str,
+        capacity: usize,
+    ) -> Result<BackedBuffer<T>, MmapBufferError> {
+        BackedBufferBuilder::new()
+            .header(true)
+            .create(capacity, self.key_path(key)?)
+    }
+
+    /// Open the existing buffer under `key`.
+    pub fn open<T: Pod>(&self, key: &str) -> Result<BackedBuffer<T>, MmapBufferError> {
+        BackedBufferBuilder::new()
+            .header(true)
+            .load(self.key_path(key)?)
+    }
+
+    /// Returns `true` if there is a buffer under `key`
+    pub fn contains(&self, key: &str) -> bool {
+        self.key_path(key).is_ok_and(|path| path.exists())
+    }
+
+    /// Delete the buffer under `key`, along with any checksum sidecar.
+    /// Fails if it doesn't exist or is currently open.
+    pub fn remove(&self, key: &str) -> Result<(), MmapBufferError> {
+        let path = self.key_path(key)?;
+
+        // Make sure nobody has the buffer open before deleting it
+        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+
+        fs::remove_file(&path)?;
+        match fs::remove_file(checksum::sidecar_path(&path)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Keys of all buffers in the directory, in no particular order
+    pub fn keys(&self) -> Result<Vec<String>, MmapBufferError> {
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                if let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    keys.push(key.to_owned());
+                }
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Path of the directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of the file holding the buffer under `key`. Keys may not be
+    /// empty, start with a dot, or contain path separators.
+    fn key_path(&self, key: &str) -> Result<PathBuf, MmapBufferError> {
+        let valid = !key.is_empty()
+            && !key.starts_with('.')
+            && !key.contains(['/', '\\', '\0'])
+            && !key.contains(std::path::MAIN_SEPARATOR);
+        if !valid {
+            return Err(MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid buffer key {key:?}"),
+            )));
+        }
+
+        Ok(self.path.join(format!("{key}.{EXTENSION}")))
+    }
+}
+
+impl Drop for BufferDir {
+    fn drop(&mut self) {
+        // Ignore the error, advisory locks are still kind of sus
+        self.lock.unlock().unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferDir;
+    use crate::MmapBufferError;
+    use std::error::Error;
+
+    #[test]
+    fn keyed_buffers() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("columns");
+
+        {
+            let dir = BufferDir::new(&path)?;
+            assert!(matches!(
+                BufferDir::new(&path),
+                Err(MmapBufferError::Locked)
+            ));
+
+            dir.create::<u64>("offsets", 4)?[3] = 3;
+            dir.create::<f32>("vectors", 16)?;
+            assert!(dir.create::<u8>("../escape", 1).is_err());
+        }
+
+        let dir = BufferDir::new(&path)?;
+        let mut keys = dir.keys()?;
+        keys.sort();
+        assert_eq!(keys, ["offsets", "vectors"]);
+
+        assert_eq!(dir.open::<u64>("offsets")?[3], 3);
+        let err = dir.open::<u32>("offsets").unwrap_err();
+        assert!(matches!(err, MmapBufferError::TypeMismatch));
+
+        let buf = dir.open::<f32>("vectors")?;
+        assert!(dir.remove("vectors").is_err());
+        drop(buf);
+        dir.remove("vectors")?;
+        assert!(!dir.contains("vectors"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+/// Granularity of dirty tracking, the system page size where known
+pub(crate) fn page_size() -> usize {
+    #[cfg(unix)]
+    {
+        // SAFETY: `sysconf` has no preconditions
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    #[cfg(not(unix))]
+    {
+        4096
+    }
+}
+
+/// Bitmap of pages of a mapping which were modified since the last flush
+#[derive(Debug, Default)]
+pub(crate) struct DirtyPages {
+    bits: Vec<u64>,
+}
+
+impl DirtyPages {
+    /// Mark the pages overlapping the given byte range
+    pub(crate) fn mark(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+
+        let page_size = page_size();
+        for page in start / page_size..=(end - 1) / page_size {
+            if page / 64 >= self.bits.len() {
+                self.bits.resize(page / 64 + 1, 0);
+            }
+            self.bits[page / 64] |= 1 << (page % 64);
+        }
+    }
+
+    fn is_marked(&self, page: usize) -> bool {
+        self.bits
+            .get(page / 64)
+            .is_some_and(|word| word & (1 << (page % 64)) != 0)
+    }
+
+    fn count(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.bits.clear();
+    }
+}
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Copy `values` into the buffer starting at element `index`, recording
+    /// which pages were modified so [`flush_dirty`](Self::flush_dirty) can
+    /// skip the rest.
+    ///
+    /// Writes through `DerefMut` (e.g. `buf[i] = x`) aren't tracked, they
+    /// only reach the file on [`flush`](Self::flush) or when the buffer is
+    /// dropped.
+    pub fn write(&mut self, index: usize, values: &[T]) {
+        let (offset, _) = self.byte_range(index..index + values.len());
+        self.write_bytes(offset, bytemuck::cast_slice(values));
+    }
+
+    /// Copy `bytes` into the mapping starting at byte `offset`, header
+    /// included, tracked like [`write`](Self::write)
+    pub(crate) fn write_bytes(&mut self, offset: usize, bytes: &[u8]) {
+        self.mark_dirty();
+        self.mmap[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.dirty.mark(offset, offset + bytes.len());
+    }
+
+    /// Number of pages modified through tracked writes since the last flush
+    pub fn dirty_pages(&self) -> usize {
+        self.dirty.count()
+    }
+
+    /// Synchronously flush only the pages modified through
+    /// [`write`](Self::write), [`set_len`](Self::set_len) and
+    /// [`resize`](Self::resize) since the last flush. Writes through
+    /// `DerefMut` need a full [`flush`](Self::flush) instead.
+    pub fn flush_dirty(&mut self) -> Result<(), MmapBufferError> {
+        self.update_checksum()?;
+
+        // Flush contiguous runs of dirty pages at once
+        let page_size = page_size();
+        let pages = self.mmap.len().div_ceil(page_size);
+        let mut page = 0;
+        while page < pages {
+            if !self.dirty.is_marked(page) {
+                page += 1;
+                continue;
+            }
+
+            let start = page;
+            while page < pages && self.dirty.is_marked(page) {
+                page += 1;
+            }
+
+            let offset = start * page_size;
+            let end = usize::min(page * page_size, self.mmap.len());
+            self.mmap.flush_range(offset, end - offset)?;
+        }
+
+        self.dirty.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::page_size;
+    use crate::BackedBuffer;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn track_dirty_pages() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u8>::new(16 * page_size(), file_path.clone())?;
+        assert_eq!(buf.dirty_pages(), 0);
+
+        buf.write(0, &[1]);
+        buf.write(3 * page_size() - 1, &[2, 3]);
+        assert_eq!(buf.dirty_pages(), 3);
+
+        buf.flush_dirty()?;
+        assert_eq!(buf.dirty_pages(), 0);
+
+        // Untracked writes stay out of the bitmap
+        buf[5] = 4;
+        assert_eq!(buf.dirty_pages(), 0);
+
+        drop(buf);
+        let contents = std::fs::read(file_path.clone())?;
+        assert_eq!(contents[0], 1);
+        assert_eq!(contents[3 * page_size()], 3);
+        assert_eq!(contents[5], 4);
+
+        // Zeroing exposed elements is tracked
+        let mut buf = BackedBuffer::<u8>::load(file_path)?;
+        buf.set_len(page_size());
+        buf.set_len(2 * page_size());
+        assert_eq!(buf.dirty_pages(), 1);
+        buf.resize(4 * page_size())?;
+        assert_eq!(buf.dirty_pages(), 3);
+        buf.flush_dirty()?;
+        assert_eq!(buf[3 * page_size()], 0);
+
+        Ok(())
+    }
+}
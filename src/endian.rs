@@ -0,0 +1,199 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use bytemuck::{Pod, Zeroable};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Numbers which [`LE`] and [`BE`] can store in a fixed byte order. This
+/// trait is sealed.
+pub trait EndianScalar: private::Sealed + Pod {
+    /// Reverse the order of the bytes
+    fn swap_bytes(self) -> Self;
+}
+
+macro_rules! endian_scalar {
+    ($($int:ty),*) => {
+        $(
+            impl private::Sealed for $int {}
+            impl EndianScalar for $int {
+                #[inline]
+                fn swap_bytes(self) -> Self {
+                    <$int>::swap_bytes(self)
+                }
+            }
+        )*
+    };
+}
+
+endian_scalar!(u16, u32, u64, u128, i16, i32, i64, i128);
+
+impl private::Sealed for f32 {}
+impl EndianScalar for f32 {
+    #[inline]
+    fn swap_bytes(self) -> Self {
+        f32::from_bits(self.to_bits().swap_bytes())
+    }
+}
+
+impl private::Sealed for f64 {}
+impl EndianScalar for f64 {
+    #[inline]
+    fn swap_bytes(self) -> Self {
+        f64::from_bits(self.to_bits().swap_bytes())
+    }
+}
+
+/// Wrappers holding a number in a fixed byte order whatever the target,
+/// converting it on access, so files stay portable between little and big
+/// endian machines. Comparisons, hashing and formatting all go by the
+/// value rather than its bytes.
+macro_rules! endian_wrapper {
+    ($($wrapper:ident, $endian:literal;)*) => {
+        $(
+            #[doc = concat!("A number stored in ", $endian, " endian byte order, e.g. as")]
+            #[doc = concat!("the element of a `BackedBuffer<", stringify!($wrapper), "<u32>>`, which is")]
+            /// converted to the byte order of the target whenever it is
+            /// read or written
+            #[derive(Clone, Copy, Default)]
+            #[repr(transparent)]
+            pub struct $wrapper<T: EndianScalar>(T);
+
+            // SAFETY: `repr(transparent)` over a `Pod` type
+            unsafe impl<T: EndianScalar> Zeroable for $wrapper<T> {}
+            unsafe impl<T: EndianScalar> Pod for $wrapper<T> {}
+
+            impl<T: EndianScalar> $wrapper<T> {
+                /// Store `value` in this byte order
+                #[inline]
+                pub fn new(value: T) -> Self {
+                    Self(Self::convert(value))
+                }
+
+                /// The value, in the byte order of the target
+                #[inline]
+                pub fn get(self) -> T {
+                    Self::convert(self.0)
+                }
+
+                /// Replace the value
+                #[inline]
+                pub fn set(&mut self, value: T) {
+                    *self = Self::new(value);
+                }
+
+                /// Store each of `values` into `dst`, like
+                /// [`copy_from_slice`](slice::copy_from_slice)
+                ///
+                /// # Panics
+                ///
+                /// If the slices have different lengths.
+                pub fn copy_from_native(dst: &mut [Self], values: &[T]) {
+                    assert_eq!(dst.len(), values.len(), "slices must have the same length!");
+                    for (dst, &value) in dst.iter_mut().zip(values) {
+                        *dst = Self::new(value);
+                    }
+                }
+
+                /// The values of `src`, in the byte order of the target
+                pub fn to_native_vec(src: &[Self]) -> Vec<T> {
+                    src.iter().map(|x| x.get()).collect()
+                }
+
+                /// Swap between this byte order and that of the target, in
+                /// either direction
+                #[inline]
+                fn convert(value: T) -> T {
+                    if cfg!(target_endian = $endian) {
+                        value
+                    } else {
+                        value.swap_bytes()
+                    }
+                }
+            }
+
+            impl<T: EndianScalar> From<T> for $wrapper<T> {
+                fn from(value: T) -> Self {
+                    Self::new(value)
+                }
+            }
+
+            impl<T: EndianScalar + PartialEq> PartialEq for $wrapper<T> {
+                fn eq(&self, other: &Self) -> bool {
+                    self.get() == other.get()
+                }
+            }
+
+            impl<T: EndianScalar + Eq> Eq for $wrapper<T> {}
+
+            impl<T: EndianScalar + PartialOrd> PartialOrd for $wrapper<T> {
+                fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                    self.get().partial_cmp(&other.get())
+                }
+            }
+
+            impl<T: EndianScalar + Ord> Ord for $wrapper<T> {
+                fn cmp(&self, other: &Self) -> Ordering {
+                    self.get().cmp(&other.get())
+                }
+            }
+
+            impl<T: EndianScalar + Hash> Hash for $wrapper<T> {
+                fn hash<H: Hasher>(&self, state: &mut H) {
+                    self.get().hash(state)
+                }
+            }
+
+            impl<T: EndianScalar + fmt::Debug> fmt::Debug for $wrapper<T> {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    self.get().fmt(f)
+                }
+            }
+
+            impl<T: EndianScalar + fmt::Display> fmt::Display for $wrapper<T> {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    self.get().fmt(f)
+                }
+            }
+        )*
+    };
+}
+
+endian_wrapper! {
+    LE, "little";
+    BE, "big";
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, BE, LE};
+    use std::{error::Error, fs, path::Path};
+
+    #[test]
+    fn byte_order() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<LE<u32>>::new(2, file_path.clone())?;
+        LE::copy_from_native(&mut buf, &[0x01020304, 5]);
+        let next = buf[1].get() + 1;
+        buf[1].set(next);
+        drop(buf);
+        assert_eq!(&fs::read(&file_path)?, &[4, 3, 2, 1, 6, 0, 0, 0]);
+
+        let buf = BackedBuffer::<BE<u32>>::load(&file_path)?;
+        assert_eq!(buf[0].get(), 0x04030201);
+        assert_eq!(BE::to_native_vec(&buf), vec![0x04030201, 0x06000000]);
+
+        assert_eq!(BE::new(1.5f64), 1.5.into());
+        assert!(LE::new(2i64) > LE::new(-1));
+        assert_eq!(format!("{:?}", BE::new(258u16)), "258");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::{error::Error, fmt, io, ops::Range};
+
+use bytemuck::PodCastError;
+
+/// Errors which can occur when creating or loading a buffer
+#[derive(Debug)]
+pub enum MmapBufferError {
+    /// An underlying IO operation failed
+    Io(io::Error),
+    /// The file is already locked, most likely by another buffer
+    Locked,
+    /// The mapping isn't suitably aligned to be viewed as a slice of `T`
+    Alignment,
+    /// The size of the file isn't a multiple of the size of `T`
+    SizeMismatch {
+        /// Size of the file in bytes
+        file_size: usize,
+        /// Size of a single element in bytes
+        element_size: usize,
+    },
+    /// The buffer doesn't hold the number of elements it was expected to
+    LengthMismatch {
+        /// Number of elements which were expected
+        expected: usize,
+        /// Number of elements actually in the buffer
+        actual: usize,
+    },
+    /// The requested capacity in bytes doesn't fit in a `usize`
+    CapacityOverflow,
+    /// The file header is missing or inconsistent with the rest of the file
+    InvalidHeader,
+    /// The file header describes a different element type than `T`
+    TypeMismatch,
+    /// The contents don't match their stored checksum
+    ChecksumMismatch,
+    /// The file was left dirty by a buffer which wasn't shut down cleanly,
+    /// see [`DirtyFlag`](crate::DirtyFlag)
+    UncleanShutdown,
+    /// Some pages don't match their stored checksums, most likely because
+    /// writes to them were torn by a crash or power failure
+    PageChecksumMismatch {
+        /// Ranges of elements on the damaged pages, in ascending order
+        ranges: Vec<Range<usize>>,
+    },
+    /// An element isn't a valid value of its type, see
+    /// [`CheckedBackedBuffer`](crate::CheckedBackedBuffer)
+    InvalidBitPattern {
+        /// Index of the first invalid element
+        index: usize,
+    },
+}
+
+impl MmapBufferError {
+    /// Distinguish lock contention from other IO errors when acquiring
+    /// an advisory lock
+    pub(crate) fn from_lock_error(err: io::Error) -> Self {
+        if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() {
+            Self::Locked
+        } else {
+            Self::Io(err)
+        }
+    }
+
+    /// Convert a failed cast of `file_size` bytes into elements of size
+    /// `element_size`
+    pub(crate) fn from_cast_error(
+        err: PodCastError,
+        file_size: usize,
+        element_size: usize,
+    ) -> Self {
+        match err {
+            PodCastError::OutputSliceWouldHaveSlop | PodCastError::SizeMismatch => {
+                Self::SizeMismatch {
+                    file_size,
+                    element_size,
+                }
+            }
+            PodCastError::TargetAlignmentGreaterAndInputNotAligned
+            | PodCastError::AlignmentMismatch => Self::Alignment,
+        }
+    }
+}
+
+impl fmt::Display for MmapBufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "io error: {err}"),
+            Self::Locked => f.write_str("file is locked by another buffer"),
+            Self::Alignment => f.write_str("mapping is not aligned for the element type"),
+            Self::SizeMismatch {
+                file_size,
+                element_size,
+            } => write!(
+                f,
+                "file size {file_size} is not a multiple of the element size {element_size}"
+            ),
+            Self::LengthMismatch { expected, actual } => {
+                write!(f, "expected {expected} elements, found {actual}")
+            }
+            Self::CapacityOverflow => f.write_str("capacity in bytes overflows `usize`"),
+            Self::InvalidHeader => f.write_str("file header is missing or invalid"),
+            Self::TypeMismatch => f.write_str("file header describes a different element type"),
+            Self::ChecksumMismatch => f.write_str("contents don't match their stored checksum"),
+            Self::UncleanShutdown => f.write_str("file wasn't shut down cleanly"),
+            Self::PageChecksumMismatch { ranges } => {
+                write!(f, "elements {ranges:?} don't match their stored checksums")
+            }
+            Self::InvalidBitPattern { index } => {
+                write!(f, "element {index} isn't a valid value of its type")
+            }
+        }
+    }
+}
+
+impl Error for MmapBufferError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MmapBufferError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
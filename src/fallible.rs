@@ -0,0 +1,137 @@
+use std::io;
+
+use bytemuck::{try_cast_slice, try_cast_slice_mut, Pod};
+
+use crate::{BackedBuffer, MmapBufferError, OnCorruption};
+
+impl<T: Pod> BackedBuffer<T> {
+    /// View the elements like `Deref` does, but fail instead of panicking if
+    /// the mapping can't be viewed as a slice of `T`, and fail with an
+    /// [`io::ErrorKind::UnexpectedEof`] error if another process truncated
+    /// the file so that reading the mapping would crash the program with
+    /// `SIGBUS`. Checking the file size costs a system call, so this is
+    /// meant for services which have to degrade gracefully rather than for
+    /// hot loops.
+    ///
+    /// A truncated file is handled according to
+    /// [`BackedBufferBuilder::on_corruption`](crate::BackedBufferBuilder::on_corruption):
+    /// [`TruncateToValidPrefix`](OnCorruption::TruncateToValidPrefix) only
+    /// views the elements the file still holds, and
+    /// [`ZeroFill`](OnCorruption::ZeroFill) grows the file back to its
+    /// size, so the missing elements read as zeroes.
+    pub fn try_as_slice(&self) -> Result<&[T], MmapBufferError> {
+        let len = self.backed_len()?;
+        let size = std::mem::size_of::<T>();
+        let bytes = &self.mmap[self.offset..self.offset + len * size];
+        try_cast_slice(bytes)
+            .map_err(|err| MmapBufferError::from_cast_error(err, bytes.len(), size))
+    }
+
+    /// Mutable version of [`try_as_slice`](Self::try_as_slice)
+    pub fn try_as_mut_slice(&mut self) -> Result<&mut [T], MmapBufferError> {
+        let len = self.backed_len()?;
+        self.mark_dirty();
+
+        let size = std::mem::size_of::<T>();
+        let bytes = &mut self.mmap[self.offset..self.offset + len * size];
+        let len = bytes.len();
+        try_cast_slice_mut(bytes).map_err(|err| MmapBufferError::from_cast_error(err, len, size))
+    }
+
+    /// The element at `index`, or `None` if it is out of bounds, failing
+    /// like [`try_as_slice`](Self::try_as_slice)
+    pub fn try_get(&self, index: usize) -> Result<Option<&T>, MmapBufferError> {
+        Ok(self.try_as_slice()?.get(index))
+    }
+
+    /// Mutable version of [`try_get`](Self::try_get)
+    pub fn try_get_mut(&mut self, index: usize) -> Result<Option<&mut T>, MmapBufferError> {
+        Ok(self.try_as_mut_slice()?.get_mut(index))
+    }
+
+    /// Number of elements which are safe to view, applying the corruption
+    /// policy if the backing file no longer covers the whole mapping
+    fn backed_len(&self) -> Result<usize, MmapBufferError> {
+        let Some(file) = &self.file else {
+            return Ok(self.len);
+        };
+        let file_size = file.metadata()?.len();
+        let end = self.file_offset + self.mmap.len() as u64;
+        if file_size >= end {
+            return Ok(self.len);
+        }
+
+        let err = io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the file was truncated behind the buffer's back",
+        );
+        match self.on_corruption {
+            OnCorruption::Panic => panic!("{err}"),
+            OnCorruption::Error => Err(err.into()),
+            OnCorruption::TruncateToValidPrefix => {
+                let backed = file_size.saturating_sub(self.file_offset) as usize;
+                let whole = backed.saturating_sub(self.offset) / std::mem::size_of::<T>().max(1);
+                Ok(usize::min(whole, self.len))
+            }
+            OnCorruption::ZeroFill => {
+                file.set_len(end)?;
+                Ok(self.len)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBufferBuilder, LockMode, MmapBufferError, OnCorruption};
+    use std::{error::Error, fs::OpenOptions, path::Path};
+
+    #[test]
+    fn fallible_access() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBufferBuilder::new()
+            .lock(LockMode::Unlocked)
+            .create::<u64>(1024, file_path.clone())?;
+        *buf.try_get_mut(3)?.unwrap() = 3;
+        assert_eq!(buf.try_get(3)?, Some(&3));
+        assert_eq!(buf.try_get(1024)?, None);
+        assert_eq!(buf.try_as_slice()?.len(), 1024);
+
+        // Someone else truncates the file
+        OpenOptions::new()
+            .write(true)
+            .open(&file_path)?
+            .set_len(8)?;
+        let err = buf.try_as_slice().unwrap_err();
+        assert!(matches!(err, MmapBufferError::Io(_)));
+        assert!(buf.try_get_mut(0).is_err());
+        drop(buf);
+
+        let mut options = BackedBufferBuilder::new();
+        options.lock(LockMode::Unlocked);
+        let buf = options
+            .on_corruption(OnCorruption::TruncateToValidPrefix)
+            .create::<u64>(1024, file_path.clone())?;
+        OpenOptions::new()
+            .write(true)
+            .open(&file_path)?
+            .set_len(20)?;
+        assert_eq!(buf.try_as_slice()?.len(), 2);
+        drop(buf);
+
+        let mut buf = options
+            .on_corruption(OnCorruption::ZeroFill)
+            .create::<u64>(1024, file_path.clone())?;
+        buf.fill(1);
+        OpenOptions::new()
+            .write(true)
+            .open(&file_path)?
+            .set_len(8)?;
+        assert_eq!(buf.try_get(1)?, Some(&0));
+        assert_eq!(buf.try_as_slice()?.len(), 1024);
+
+        Ok(())
+    }
+}
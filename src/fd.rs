@@ -0,0 +1,190 @@
+use std::{
+    io,
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::net::UnixStream,
+    },
+};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, BackedBufferBuilder, MmapBufferError, SharedBuffer};
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Load a buffer from an open file descriptor, with the default options.
+    /// The descriptor may come from another process, see
+    /// [`recv_from`](Self::recv_from).
+    ///
+    /// Advisory locks belong to the open file rather than the descriptor,
+    /// so a descriptor shared with a process holding the lock shares its
+    /// lock too. Whichever side is dropped first releases it for both.
+    pub fn from_fd(fd: OwnedFd) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().load_fd(fd)
+    }
+
+    /// The backing file, or `None` for anonymous buffers
+    pub fn as_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.file.as_ref().map(AsFd::as_fd)
+    }
+
+    /// Send the backing file over a Unix socket (`SCM_RIGHTS`), so another
+    /// process can map it with [`recv_from`](Self::recv_from) without
+    /// knowing its path. Fails for anonymous buffers.
+    pub fn send_to(&self, socket: &UnixStream) -> Result<(), MmapBufferError> {
+        let fd = self.as_fd().ok_or_else(|| {
+            MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "anonymous buffers have no file to send",
+            ))
+        })?;
+
+        Ok(send_fd(socket, fd)?)
+    }
+
+    /// Receive a file sent with [`send_to`](Self::send_to) over a Unix
+    /// socket and map it, see [`from_fd`](Self::from_fd)
+    pub fn recv_from(socket: &UnixStream) -> Result<Self, MmapBufferError> {
+        Self::from_fd(recv_fd(socket)?)
+    }
+}
+
+impl<T: Pod> AsFd for SharedBuffer<T> {
+    /// The shared memory object, to pass to other processes
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.buffer
+            .as_fd()
+            .expect("shared memory objects are always open")
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<T: Pod> crate::SealedBuffer<T> {
+    /// Send the sealed memory file over a Unix socket (`SCM_RIGHTS`), see
+    /// [`recv_from`](Self::recv_from)
+    pub fn send_to(&self, socket: &UnixStream) -> Result<(), MmapBufferError> {
+        Ok(send_fd(socket, self.as_fd())?)
+    }
+
+    /// Receive a sealed memory file sent with [`send_to`](Self::send_to)
+    /// over a Unix socket and map it, see [`from_fd`](Self::from_fd)
+    pub fn recv_from(socket: &UnixStream) -> Result<Self, MmapBufferError> {
+        Self::from_fd(recv_fd(socket)?)
+    }
+}
+
+/// Room for the control message carrying a single descriptor
+const CMSG_BUFFER_LEN: usize = 64;
+
+/// Control message buffer, aligned for `cmsghdr`
+#[repr(C, align(8))]
+struct CmsgBuffer([u8; CMSG_BUFFER_LEN]);
+
+/// Send a file descriptor along with a single byte, since some platforms
+/// drop control messages without any data
+pub(crate) fn send_fd(socket: &UnixStream, fd: BorrowedFd<'_>) -> io::Result<()> {
+    let mut data = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    let mut cmsg = CmsgBuffer([0; CMSG_BUFFER_LEN]);
+    let fd_len = std::mem::size_of::<RawFd>() as libc::c_uint;
+
+    // SAFETY: an all-zero `msghdr` is valid, and every pointer in it refers
+    // to a local which outlives the call
+    let result = unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg.0.as_mut_ptr().cast();
+        msg.msg_controllen = libc::CMSG_SPACE(fd_len) as _;
+
+        let header = libc::CMSG_FIRSTHDR(&msg);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(fd_len) as _;
+        libc::CMSG_DATA(header)
+            .cast::<RawFd>()
+            .write_unaligned(fd.as_raw_fd());
+
+        libc::sendmsg(socket.as_raw_fd(), &msg, 0)
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Receive a file descriptor sent with [`send_fd`]
+pub(crate) fn recv_fd(socket: &UnixStream) -> io::Result<OwnedFd> {
+    let mut data = [0u8];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr().cast(),
+        iov_len: data.len(),
+    };
+    let mut cmsg = CmsgBuffer([0; CMSG_BUFFER_LEN]);
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let flags = libc::MSG_CMSG_CLOEXEC;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let flags = 0;
+
+    // SAFETY: an all-zero `msghdr` is valid, and every pointer in it refers
+    // to a local which outlives the call
+    unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = cmsg.0.as_mut_ptr().cast();
+        msg.msg_controllen = CMSG_BUFFER_LEN as _;
+
+        let received = libc::recvmsg(socket.as_raw_fd(), &mut msg, flags);
+        if received < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let header = libc::CMSG_FIRSTHDR(&msg);
+        if received == 0
+            || header.is_null()
+            || (*header).cmsg_level != libc::SOL_SOCKET
+            || (*header).cmsg_type != libc::SCM_RIGHTS
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no file descriptor was received",
+            ));
+        }
+
+        let fd = libc::CMSG_DATA(header).cast::<RawFd>().read_unaligned();
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BackedBuffer;
+    use std::{error::Error, os::unix::net::UnixStream, path::Path};
+
+    #[test]
+    fn pass_fd() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        let (sender, receiver) = UnixStream::pair()?;
+
+        let mut buf = BackedBuffer::<u64>::new(100, file_path)?;
+        buf[10] = 10;
+        buf.send_to(&sender)?;
+
+        let mut received = BackedBuffer::<u64>::recv_from(&receiver)?;
+        assert_eq!(received[10], 10);
+        received[11] = 11;
+        assert_eq!(buf[11], 11);
+
+        assert!(BackedBuffer::<u64>::anonymous(100)?
+            .send_to(&sender)
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use std::{io, mem::ManuallyDrop, ops::Deref};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+/// A [`BackedBuffer`] whose mapping is protected read-only, so any write to
+/// it, even through unsafe code, faults instead of silently corrupting it.
+/// See [`BackedBuffer::freeze`].
+pub struct FrozenBuffer<T: Pod> {
+    buffer: ManuallyDrop<BackedBuffer<T>>,
+}
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Protect the mapping read-only (`mprotect`), e.g. before handing out
+    /// views of it to untrusted code. Writes to it fault with a segmentation
+    /// fault until the buffer is [unfrozen](FrozenBuffer::unfreeze).
+    pub fn freeze(self) -> Result<FrozenBuffer<T>, MmapBufferError> {
+        protect(&self, libc::PROT_READ)?;
+        Ok(FrozenBuffer {
+            buffer: ManuallyDrop::new(self),
+        })
+    }
+}
+
+impl<T: Pod> FrozenBuffer<T> {
+    /// Restore write access to the mapping, returning the buffer.
+    pub fn unfreeze(mut self) -> Result<BackedBuffer<T>, MmapBufferError> {
+        protect(&self.buffer, libc::PROT_READ | libc::PROT_WRITE)?;
+
+        // SAFETY: `self` is forgotten right after, so the buffer is only
+        // dropped by the caller
+        let buffer = unsafe { ManuallyDrop::take(&mut self.buffer) };
+        std::mem::forget(self);
+        Ok(buffer)
+    }
+
+    /// The underlying buffer, for read-only access to its metadata
+    pub fn get_ref(&self) -> &BackedBuffer<T> {
+        &self.buffer
+    }
+}
+
+/// Change the protection of the whole mapping of a buffer
+fn protect<T: Pod>(buffer: &BackedBuffer<T>, prot: libc::c_int) -> Result<(), MmapBufferError> {
+    if buffer.mmap.is_empty() {
+        return Ok(());
+    }
+
+    // SAFETY: the mapping is page aligned and owned by the buffer
+    let result = unsafe { libc::mprotect(buffer.mmap.as_ptr() as *mut _, buffer.mmap.len(), prot) };
+    if result != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+impl<T: Pod> Deref for FrozenBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for FrozenBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T: Pod> Drop for FrozenBuffer<T> {
+    fn drop(&mut self) {
+        // Dropping the buffer may write to it, e.g. to update its header,
+        // so restore write access first. If that fails, leak the buffer
+        // rather than fault.
+        if protect(&self.buffer, libc::PROT_READ | libc::PROT_WRITE).is_ok() {
+            // SAFETY: the buffer is never used again
+            unsafe { ManuallyDrop::drop(&mut self.buffer) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BackedBuffer;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn freeze() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u32>::new(1024, file_path.clone())?;
+        buf[3] = 3;
+
+        let frozen = buf.freeze()?;
+        assert_eq!(frozen[3], 3);
+
+        let mut buf = frozen.unfreeze()?;
+        buf[4] = 4;
+
+        // Dropping while frozen still releases the lock
+        drop(buf.freeze()?);
+        assert_eq!(&BackedBuffer::<u32>::load(file_path)?[3..5], &[3, 4]);
+
+        Ok(())
+    }
+}
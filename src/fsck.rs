@@ -0,0 +1,287 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+};
+
+use bytemuck::Pod;
+use fs2::FileExt;
+use memmap2::MmapMut;
+
+use crate::{
+    builder, checksum,
+    header::{self, Header},
+    BackedBuffer, LockOwner, MmapBufferError,
+};
+
+/// What [`BackedBuffer::validate`] found out about a buffer file
+#[derive(Debug)]
+pub struct ValidationReport {
+    /// Size of the file in bytes
+    pub file_size: u64,
+    /// Whether the file starts with a header written by this crate
+    pub has_header: bool,
+    /// Why the header doesn't describe the file as holding elements of
+    /// type `T`, if it is broken
+    pub header_error: Option<MmapBufferError>,
+    /// Bytes after the last whole element, see
+    /// [`SizePolicy::Truncate`](crate::SizePolicy::Truncate)
+    pub trailing_bytes: usize,
+    /// Whether the header records more elements than the file holds
+    pub len_exceeds_capacity: bool,
+    /// Whether the dirty flag in the header was left set, see
+    /// [`DirtyFlag`](crate::DirtyFlag)
+    pub unclean_shutdown: bool,
+    /// Whether the file has a checksum sidecar
+    pub has_checksum: bool,
+    /// Why the contents don't match their checksum sidecar, if they don't
+    pub checksum_error: Option<MmapBufferError>,
+    /// Whether another buffer currently holds a lock on the file
+    pub locked: bool,
+    /// The process recorded in the pid file, if there is one
+    pub owner: Option<LockOwner>,
+    /// Whether the pid file was left behind by a process which no longer
+    /// exists
+    pub stale_pid_file: bool,
+}
+
+impl ValidationReport {
+    /// Whether nothing is wrong with the file. Locks held by live buffers
+    /// aren't considered a problem.
+    pub fn is_ok(&self) -> bool {
+        self.header_error.is_none()
+            && self.trailing_bytes == 0
+            && !self.len_exceeds_capacity
+            && !self.unclean_shutdown
+            && self.checksum_error.is_none()
+            && !self.stale_pid_file
+    }
+}
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Check the buffer file at `path` for everything that would keep it
+    /// from loading as elements of type `T`, or that points at it not
+    /// having been closed cleanly. A header and checksum sidecar are
+    /// checked if the file has them, whatever options it was created with.
+    /// The file is only read, and fails to validate only if it can't be.
+    pub fn validate(path: impl AsRef<Path>) -> Result<ValidationReport, MmapBufferError> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let locked = is_locked(&file)? || is_locked_by_lock_file(path)?;
+
+        // SAFETY: only read for the duration of this call, and any buffer
+        // modifying it concurrently is reported as holding the lock
+        let bytes = unsafe { memmap2::Mmap::map(&file)? };
+        let mut report = inspect::<T>(&bytes, path)?;
+        report.locked = locked;
+        Ok(report)
+    }
+
+    /// Fix what [`validate`](Self::validate) finds wrong with the buffer
+    /// file at `path` where that is possible without guessing at its
+    /// contents: trailing bytes are truncated, a recorded length beyond the
+    /// capacity is clamped, the dirty flag is cleared, the checksum sidecar
+    /// is rewritten to match the current contents, and a stale pid file is
+    /// removed. A file with a broken header is left alone, apart from its
+    /// pid file, since there is no telling where its elements start.
+    ///
+    /// Returns the report from before repairing. Fails with
+    /// [`MmapBufferError::Locked`] if a buffer has the file open, and
+    /// should only be used once the contents are known to be acceptable,
+    /// e.g. after rebuilding them.
+    pub fn repair(path: impl AsRef<Path>) -> Result<ValidationReport, MmapBufferError> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+
+        let report = {
+            // SAFETY: the exclusive lock keeps out other buffers
+            let bytes = unsafe { memmap2::Mmap::map(&file)? };
+            inspect::<T>(&bytes, path)?
+        };
+
+        let broken_header = report.has_header && report.header_error.is_some();
+        if report.trailing_bytes > 0 && !broken_header {
+            file.set_len(report.file_size - report.trailing_bytes as u64)?;
+        }
+
+        // SAFETY: as above
+        let mut bytes = unsafe { MmapMut::map_mut(&file)? };
+        if report.has_header && report.header_error.is_none() {
+            let offset = Header::size::<T>(Header::load_align(&bytes));
+            let capacity = (bytes.len() - offset) / std::mem::size_of::<T>().max(1);
+            if report.len_exceeds_capacity {
+                Header::store_len(&mut bytes, capacity);
+            }
+            Header::store_dirty(&mut bytes, false);
+        }
+        bytes.flush()?;
+
+        if report.has_checksum && !broken_header {
+            let sidecar = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(checksum::sidecar_path(path))?;
+            checksum::write_checksum(&sidecar, checksum::crc32(&bytes))?;
+            if checksum::has_page_checksums(&sidecar)? {
+                checksum::write_page_checksums(&sidecar, &bytes)?;
+            }
+            sidecar.sync_all()?;
+        }
+
+        if report.stale_pid_file {
+            Self::force_unlock(path)?;
+        }
+
+        file.unlock()?;
+        Ok(report)
+    }
+}
+
+/// Whether another buffer holds an advisory lock on the file
+fn is_locked(file: &File) -> Result<bool, MmapBufferError> {
+    match file.try_lock_exclusive() {
+        Ok(()) => {
+            file.unlock()?;
+            Ok(false)
+        }
+        Err(err) => match MmapBufferError::from_lock_error(err) {
+            MmapBufferError::Locked => Ok(true),
+            err => Err(err),
+        },
+    }
+}
+
+/// Whether another buffer holds the lock file of the file at `path`, see
+/// [`BackedBufferBuilder::lock_file`](crate::BackedBufferBuilder::lock_file)
+fn is_locked_by_lock_file(path: &Path) -> Result<bool, MmapBufferError> {
+    match File::open(builder::lock_file_path(path)) {
+        Ok(lock_file) => is_locked(&lock_file),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Check the contents of the buffer file at `path`, except for its lock
+fn inspect<T: Pod>(bytes: &[u8], path: &Path) -> Result<ValidationReport, MmapBufferError> {
+    let size = std::mem::size_of::<T>().max(1);
+    let has_header = bytes.starts_with(&header::MAGIC);
+
+    let mut offset = 0;
+    let mut header_error = None;
+    let mut len_exceeds_capacity = false;
+    let mut unclean_shutdown = false;
+    if has_header {
+        // Any alignment the header records is fine, as long as it is
+        // consistent with the rest of it
+        let align = if bytes.len() >= std::mem::size_of::<Header>() {
+            Header::load_align(bytes)
+        } else {
+            1
+        };
+        match Header::validate::<T>(bytes, align) {
+            Ok(len) => {
+                offset = Header::size::<T>(Header::load_align(bytes));
+                let capacity = bytes.len().saturating_sub(offset) / size;
+                len_exceeds_capacity = len > capacity;
+                unclean_shutdown = Header::load_dirty(bytes);
+            }
+            Err(err) => header_error = Some(err),
+        }
+    }
+    let trailing_bytes = bytes.len().saturating_sub(offset) % size;
+
+    let (has_checksum, checksum_error) = match File::open(checksum::sidecar_path(path)) {
+        Ok(sidecar) => (true, checksum::check(&sidecar, bytes, offset, size).err()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (false, None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let owner = BackedBuffer::<T>::lock_owner(path)?;
+    let stale_pid_file = owner.as_ref().is_some_and(LockOwner::is_stale);
+
+    Ok(ValidationReport {
+        file_size: bytes.len() as u64,
+        has_header,
+        header_error,
+        trailing_bytes,
+        len_exceeds_capacity,
+        unclean_shutdown,
+        has_checksum,
+        checksum_error,
+        locked: false,
+        owner,
+        stale_pid_file,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        checksum::sidecar_path, BackedBuffer, BackedBufferBuilder, DirtyFlag, LockMode,
+        MmapBufferError,
+    };
+    use std::{error::Error, fs::OpenOptions, io::Write, path::Path};
+
+    #[test]
+    fn validate_and_repair() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options
+            .header(true)
+            .checksum(true)
+            .dirty_flag(DirtyFlag::Refuse);
+        let buf = options.create::<u32>(16, file_path.clone())?;
+        assert!(BackedBuffer::<u32>::validate(&file_path)?.locked);
+        assert!(matches!(
+            BackedBuffer::<u32>::repair(&file_path).unwrap_err(),
+            MmapBufferError::Locked
+        ));
+        drop(buf);
+
+        let report = BackedBuffer::<u32>::validate(&file_path)?;
+        assert!(report.is_ok(), "{report:?}");
+        assert!(report.has_header && report.has_checksum && !report.locked);
+
+        let report = BackedBuffer::<u64>::validate(&file_path)?;
+        assert!(matches!(
+            report.header_error,
+            Some(MmapBufferError::TypeMismatch)
+        ));
+
+        // Files with a broken header are left as they are, trailing bytes
+        // and stale checksum included
+        let file = OpenOptions::new().append(true).open(&file_path)?;
+        (&file).write_all(&[0xff])?;
+        let contents = std::fs::read(&file_path)?;
+        let sidecar = std::fs::read(sidecar_path(&file_path))?;
+        assert!(!BackedBuffer::<u64>::repair(&file_path)?.is_ok());
+        assert_eq!(std::fs::read(&file_path)?, contents);
+        assert_eq!(std::fs::read(sidecar_path(&file_path))?, sidecar);
+        file.set_len(contents.len() as u64 - 1)?;
+
+        // Crash in the middle of writing, leaving a partial element behind
+        options.lock(LockMode::Unlocked);
+        let mut buf = options.load::<u32>(file_path.clone())?;
+        buf[0] = 1;
+        std::mem::forget(buf);
+        OpenOptions::new()
+            .append(true)
+            .open(&file_path)?
+            .write_all(&[0xff])?;
+
+        let report = BackedBuffer::<u32>::validate(&file_path)?;
+        assert_eq!(report.trailing_bytes, 1);
+        assert!(report.unclean_shutdown);
+        assert!(report.checksum_error.is_some());
+
+        assert!(!BackedBuffer::<u32>::repair(&file_path)?.is_ok());
+        assert!(BackedBuffer::<u32>::validate(&file_path)?.is_ok());
+        assert_eq!(options.load::<u32>(&file_path)?[0], 1);
+
+        Ok(())
+    }
+}
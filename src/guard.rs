@@ -0,0 +1,219 @@
+use std::io;
+
+use memmap2::MmapMut;
+
+use crate::dirty::page_size;
+
+/// Inaccessible pages directly before and after a mapping, so that running
+/// off either end of it faults instead of touching whatever is mapped next
+/// to it. Unmapped on drop.
+#[derive(Debug)]
+pub(crate) struct GuardPages {
+    before: usize,
+    after: usize,
+}
+
+/// Address space reserved for a mapping of `hole` bytes, with guard pages
+/// already in place on either side of the hole
+struct Reservation {
+    start: usize,
+    hole: usize,
+}
+
+/// Create a mapping of `len` bytes with `map`, surrounded by guard pages
+pub(crate) fn map_guarded(
+    len: usize,
+    map: impl Fn() -> io::Result<MmapMut>,
+) -> io::Result<(MmapMut, GuardPages)> {
+    // The kernel may place the mapping in some other gap which is too
+    // small for the guards, e.g. one left by another thread. Keep such
+    // mappings around while trying again, so the gap is taken.
+    let mut misplaced = Vec::new();
+    loop {
+        let reservation = Reservation::new(len)?;
+        let mmap = map()?;
+        match reservation.settle(mmap.as_ptr(), mmap.len()) {
+            Ok(guards) => return Ok((mmap, guards)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists && misplaced.len() < 8 => {
+                misplaced.push(mmap);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+impl Reservation {
+    /// Reserve room for a mapping of `len` bytes. The middle of the
+    /// reservation is left unmapped, which makes it a likely spot for the
+    /// next mapping of that size.
+    fn new(len: usize) -> io::Result<Self> {
+        let page = page_size();
+        let hole = span(len);
+        let start = map_none(0, hole + 2 * page, 0)?;
+
+        // SAFETY: the hole lies within the reservation we just made
+        unsafe { libc::munmap((start + page) as *mut _, hole) };
+        Ok(Self { start, hole })
+    }
+
+    /// Guard a mapping of `len` bytes at `ptr`, made after reserving room
+    /// for it. If it wasn't placed in the hole, guards are placed on either
+    /// side of it instead, failing if something else is mapped there.
+    fn settle(self, ptr: *const u8, len: usize) -> io::Result<GuardPages> {
+        let page = page_size();
+        let reservation = std::mem::ManuallyDrop::new(self);
+
+        if ptr as usize == reservation.start + page && span(len) == reservation.hole {
+            return Ok(GuardPages {
+                before: reservation.start,
+                after: reservation.start + page + reservation.hole,
+            });
+        }
+
+        drop(std::mem::ManuallyDrop::into_inner(reservation));
+        GuardPages::around(ptr, len)
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        let page = page_size();
+
+        // SAFETY: we own both guard pages
+        unsafe {
+            libc::munmap(self.start as *mut _, page);
+            libc::munmap((self.start + page + self.hole) as *mut _, page);
+        }
+    }
+}
+
+impl GuardPages {
+    /// Place guard pages on either side of the mapping of `len` bytes at
+    /// `ptr`, failing if the address space there is already in use
+    fn around(ptr: *const u8, len: usize) -> io::Result<Self> {
+        let page = page_size();
+        let start = ptr as usize;
+
+        let before = map_none(start - page, page, fixed_noreplace())?;
+        match map_none(start + span(len), page, fixed_noreplace()) {
+            Ok(after) => Ok(Self { before, after }),
+            Err(err) => {
+                // SAFETY: we own the page we just mapped
+                unsafe { libc::munmap(before as *mut _, page) };
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Drop for GuardPages {
+    fn drop(&mut self) {
+        let page = page_size();
+
+        // SAFETY: we own both guard pages
+        unsafe {
+            libc::munmap(self.before as *mut _, page);
+            libc::munmap(self.after as *mut _, page);
+        }
+    }
+}
+
+/// Address space taken up by a mapping of `len` bytes. Empty mappings
+/// still take up a page.
+fn span(len: usize) -> usize {
+    len.max(1).next_multiple_of(page_size())
+}
+
+/// Refuse to replace existing mappings, where the kernel supports it
+fn fixed_noreplace() -> libc::c_int {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        libc::MAP_FIXED_NOREPLACE
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        0
+    }
+}
+
+/// Map `len` inaccessible bytes at `addr`, or anywhere if it is zero,
+/// failing unless they end up exactly there
+fn map_none(addr: usize, len: usize, flags: libc::c_int) -> io::Result<usize> {
+    // SAFETY: without `MAP_FIXED` existing mappings are never replaced
+    let ptr = unsafe {
+        libc::mmap(
+            addr as *mut _,
+            len,
+            libc::PROT_NONE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+            -1,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Older kernels and other platforms treat the address as a hint
+    if addr != 0 && ptr as usize != addr {
+        // SAFETY: we own the mapping we just made
+        unsafe { libc::munmap(ptr, len) };
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "the address space next to the mapping is already in use",
+        ));
+    }
+
+    Ok(ptr as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::span;
+    use crate::{dirty::page_size, BackedBufferBuilder};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn guard_pages() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.guard_pages(true);
+
+        // Both neighbouring pages are mapped
+        let assert_guarded = |ptr: *const u8, len: usize| {
+            let page = page_size();
+            let start = ptr as usize;
+            for addr in [start - page, start + span(len)] {
+                let mut residency = 0;
+                let result = unsafe { libc::mincore(addr as *mut _, page, &mut residency) };
+                assert_eq!(result, 0);
+            }
+        };
+
+        let mut bufs = Vec::new();
+        for i in 0..8 {
+            let mut buf = options.anonymous::<u8>(10_000 + i)?;
+            buf[0] = 1;
+            assert_guarded(buf.as_ptr(), buf.len());
+            bufs.push(buf);
+        }
+
+        let mut buf = options.create::<u32>(1000, file_path.clone())?;
+        buf[999] = 7;
+        buf.resize(100_000)?;
+        assert_guarded(buf.as_ptr().cast(), buf.capacity_bytes());
+        buf.resize(1000)?;
+        drop(buf);
+        assert_eq!(options.load::<u32>(file_path)?[999], 7);
+
+        let mut buf = bufs.pop().unwrap();
+        buf.resize(50_000)?;
+        assert_guarded(buf.as_ptr(), buf.len());
+        assert_eq!(buf[0], 1);
+
+        Ok(())
+    }
+}
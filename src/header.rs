@@ -0,0 +1,271 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::MmapBufferError;
+
+/// Identifies files written with a header by this crate
+pub(crate) const MAGIC: [u8; 8] = *b"MMAPBUF\0";
+
+/// Bumped whenever the layout of [`Header`] changes
+const VERSION: u32 = 3;
+
+/// Flag set while a buffer may hold writes which weren't flushed yet
+const DIRTY: u64 = 1;
+
+/// Self-describing header at the start of a buffer file, used to catch
+/// loading a file as the wrong element type. It also records the logical
+/// length of the buffer, which may be less than its capacity, and whether
+/// it was left dirty by a session which didn't shut down cleanly.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Header {
+    magic: [u8; 8],
+    version: u32,
+    align: u32,
+    element_size: u64,
+    type_hash: u64,
+    len: u64,
+    flags: u64,
+}
+
+// SAFETY: `repr(C)` with no padding, and all fields are `Pod`
+unsafe impl Zeroable for Header {}
+unsafe impl Pod for Header {}
+
+impl Header {
+    /// Header describing `len` elements of type `T`, stored aligned to at
+    /// least `align` bytes
+    pub(crate) fn new<T: Pod>(len: usize, align: usize) -> Self {
+        Self {
+            magic: MAGIC,
+            version: VERSION,
+            align: align.max(std::mem::align_of::<T>()) as u32,
+            element_size: std::mem::size_of::<T>() as u64,
+            type_hash: type_hash::<T>(),
+            len: len as u64,
+            flags: 0,
+        }
+    }
+
+    /// The same header, describing elements by `type_hash` instead of the
+    /// hash of their type name, see [`MmapRecord`](crate::MmapRecord)
+    pub(crate) fn with_type_hash(self, type_hash: u64) -> Self {
+        Self { type_hash, ..self }
+    }
+
+    /// Rewrite the header at the start of `bytes`, which must already have
+    /// been validated for elements of type `T`, to describe `len` elements
+    /// of type `U` instead, keeping its alignment and flags. Fails if the
+    /// header records a layout fingerprint rather than the type name of
+    /// `T`, or would take up a different number of bytes for `U`.
+    pub(crate) fn recast<T: Pod, U: Pod>(
+        bytes: &mut [u8],
+        len: usize,
+    ) -> Result<(), MmapBufferError> {
+        let header_bytes = &mut bytes[..std::mem::size_of::<Self>()];
+        let header: Self = bytemuck::pod_read_unaligned(header_bytes);
+        let align = header.align as usize;
+        if header.type_hash != type_hash::<T>() || Self::size::<U>(align) != Self::size::<T>(align)
+        {
+            return Err(MmapBufferError::TypeMismatch);
+        }
+
+        let header = Self {
+            align: align.max(std::mem::align_of::<U>()) as u32,
+            element_size: std::mem::size_of::<U>() as u64,
+            type_hash: type_hash::<U>(),
+            len: len as u64,
+            ..header
+        };
+        header_bytes.copy_from_slice(bytemuck::bytes_of(&header));
+        Ok(())
+    }
+
+    /// Number of bytes reserved for the header in a file of `T`, padded so
+    /// the elements after it stay aligned to at least `align` bytes
+    pub(crate) fn size<T: Pod>(align: usize) -> usize {
+        std::mem::size_of::<Self>().next_multiple_of(align.max(std::mem::align_of::<T>()))
+    }
+
+    /// Check that `bytes` starts with a header describing elements of type
+    /// `T` aligned to at least `align` bytes, returning the logical length
+    /// it records
+    pub(crate) fn validate<T: Pod>(bytes: &[u8], align: usize) -> Result<usize, MmapBufferError> {
+        Self::validate_as::<T>(bytes, align, type_hash::<T>())
+    }
+
+    /// Like [`validate`](Self::validate), but expecting the elements to be
+    /// described by `type_hash`
+    pub(crate) fn validate_as<T: Pod>(
+        bytes: &[u8],
+        align: usize,
+        type_hash: u64,
+    ) -> Result<usize, MmapBufferError> {
+        let header: Self = bytes
+            .get(..std::mem::size_of::<Self>())
+            .map(bytemuck::pod_read_unaligned)
+            .ok_or(MmapBufferError::InvalidHeader)?;
+
+        if header.magic != MAGIC || header.version != VERSION {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        let expected = Self::new::<T>(header.len as usize, align).with_type_hash(type_hash);
+        let expected = Self {
+            flags: header.flags,
+            ..expected
+        };
+        let realigned = Self {
+            align: header.align,
+            ..expected
+        };
+
+        // The elements were padded for a different alignment, so they would
+        // be misplaced
+        if header == realigned && header != expected {
+            return Err(MmapBufferError::Alignment);
+        }
+
+        if header != expected {
+            return Err(MmapBufferError::TypeMismatch);
+        }
+
+        Ok(header.len as usize)
+    }
+
+    /// The logical length recorded in the header at the start of `bytes`,
+    /// which must already have been validated
+    pub(crate) fn load_len(bytes: &[u8]) -> usize {
+        let start = std::mem::offset_of!(Self, len);
+        u64::from_ne_bytes(bytes[start..start + 8].try_into().unwrap()) as usize
+    }
+
+    /// The alignment recorded in the header at the start of `bytes`, which
+    /// must already have been validated
+    pub(crate) fn load_align(bytes: &[u8]) -> usize {
+        let start = std::mem::offset_of!(Self, align);
+        u32::from_ne_bytes(bytes[start..start + 4].try_into().unwrap()) as usize
+    }
+
+    /// Overwrite the logical length recorded in the header at the start of
+    /// `bytes`
+    pub(crate) fn store_len(bytes: &mut [u8], len: usize) {
+        let start = std::mem::offset_of!(Self, len);
+        bytes[start..start + 8].copy_from_slice(&(len as u64).to_ne_bytes());
+    }
+
+    /// Whether the header at the start of `bytes`, which must already have
+    /// been validated, has its dirty flag set
+    pub(crate) fn load_dirty(bytes: &[u8]) -> bool {
+        let start = std::mem::offset_of!(Self, flags);
+        u64::from_ne_bytes(bytes[start..start + 8].try_into().unwrap()) & DIRTY != 0
+    }
+
+    /// Set or clear the dirty flag of the header at the start of `bytes`
+    pub(crate) fn store_dirty(bytes: &mut [u8], dirty: bool) {
+        let start = std::mem::offset_of!(Self, flags);
+        let flags = u64::from_ne_bytes(bytes[start..start + 8].try_into().unwrap());
+        let flags = if dirty { flags | DIRTY } else { flags & !DIRTY };
+        bytes[start..start + 8].copy_from_slice(&flags.to_ne_bytes());
+    }
+}
+
+/// 64-bit FNV-1a hash of the type name of `T`. Unlike `DefaultHasher`, this
+/// is stable across Rust releases, though type names themselves may not be.
+pub(crate) fn type_hash<T>() -> u64 {
+    std::any::type_name::<T>()
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, BackedBufferBuilder, DirtyFlag, LockMode, MmapBufferError};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn validate_header() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        {
+            let mut buf = BackedBuffer::<u64>::new_with_header(8, file_path.clone())?;
+            buf[7] = 42;
+        }
+
+        let buf = BackedBuffer::<u64>::load_with_header(file_path.clone())?;
+        assert_eq!(buf.len(), 8);
+        assert_eq!(buf[7], 42);
+        drop(buf);
+
+        let mut buf = BackedBuffer::<u64>::load_with_header(file_path.clone())?;
+        buf.shrink(3);
+        drop(buf);
+
+        // The logical length survives reloading, and growing it again
+        // exposes zeroed elements
+        let mut buf = BackedBuffer::<u64>::load_with_header(file_path.clone())?;
+        assert_eq!((buf.len(), buf.capacity()), (3, 8));
+        buf.set_len(8);
+        assert_eq!(buf[7], 0);
+        drop(buf);
+
+        let err = BackedBuffer::<f64>::load_with_header(file_path.clone()).unwrap_err();
+        assert!(matches!(err, MmapBufferError::TypeMismatch));
+
+        BackedBuffer::<u8>::new(64, file_path.clone())?;
+        let err = BackedBuffer::<u8>::load_with_header(file_path).unwrap_err();
+        assert!(matches!(err, MmapBufferError::InvalidHeader));
+
+        Ok(())
+    }
+
+    #[test]
+    fn dirty_flag() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options
+            .header(true)
+            .lock(LockMode::Unlocked)
+            .dirty_flag(DirtyFlag::Refuse);
+
+        // Dropping cleans up, even after writing
+        let mut buf = options.create::<u32>(16, file_path.clone())?;
+        buf[0] = 1;
+        drop(buf);
+
+        // Crash without dropping
+        let mut buf = options.load::<u32>(file_path.clone())?;
+        assert!(!buf.unclean_shutdown());
+        buf[1] = 2;
+        std::mem::forget(buf);
+
+        let err = options.load::<u32>(file_path.clone()).unwrap_err();
+        assert!(matches!(err, MmapBufferError::UncleanShutdown));
+
+        options.dirty_flag(DirtyFlag::Report);
+        let mut buf = options.load::<u32>(file_path.clone())?;
+        assert!(buf.unclean_shutdown());
+        buf.flush_clean()?;
+        std::mem::forget(buf);
+
+        options.dirty_flag(DirtyFlag::Refuse);
+        assert_eq!(&options.load::<u32>(file_path.clone())?[..2], &[1, 2]);
+
+        // The checksum is written after the flag is cleared, so it covers it
+        options.checksum(true);
+        let mut buf = options.create::<u32>(16, file_path.clone())?;
+        buf[0] = 1;
+        drop(buf);
+        let mut buf = options.load::<u32>(file_path.clone())?;
+        buf[1] = 2;
+        buf.flush_clean()?;
+        std::mem::forget(buf);
+        assert_eq!(&options.load::<u32>(file_path)?[..2], &[1, 2]);
+
+        Ok(())
+    }
+}
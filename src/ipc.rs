@@ -0,0 +1,959 @@
+//! Primitives for exchanging data between processes through a shared file.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    path::Path,
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
+
+use bytemuck::Pod;
+use memmap2::{MmapOptions, MmapRaw};
+
+use crate::{
+    byte_size,
+    dirty::page_size,
+    ring::{ring_size, DoubleMapping},
+    MmapBufferError,
+};
+
+/// Byte offsets of the indices in the header page. They live on separate
+/// cache lines so the producer and consumer don't contend.
+const HEAD_OFFSET: usize = 0;
+const TAIL_OFFSET: usize = 64;
+
+/// Byte offsets of the initialization marker and the lock in the header page
+/// of a [`SharedRwBuffer`]
+const MAGIC_OFFSET: usize = 0;
+const LOCK_OFFSET: usize = 64;
+
+/// Marks a [`SharedRwBuffer`] header whose lock has been initialized
+const RWLOCK_MAGIC: u64 = u64::from_le_bytes(*b"mmrwlock");
+
+/// Byte offset of the sequence counter in the header page of a
+/// [`SeqlockBuffer`], after the marker
+const SEQ_OFFSET: usize = 64;
+
+/// Marks a [`SeqlockBuffer`] header
+const SEQLOCK_MAGIC: u64 = u64::from_le_bytes(*b"mmseqlck");
+
+/// A single-producer, single-consumer queue of `T` in a file shared
+/// between processes.
+///
+/// The first page of the file holds the read and write indices as atomics,
+/// followed by a [`RingBuffer`](crate::RingBuffer)-style double-mapped ring
+/// of elements. Once both sides have opened the queue, exchanging messages
+/// needs no system calls at all.
+///
+/// No advisory lock is taken, since both processes need the file at once.
+/// Exactly one process (or thread) may push and exactly one may pop;
+/// anything else corrupts the queue. Indices which another process left
+/// out of range are clamped, so a corrupted queue yields garbage elements
+/// but never touches memory outside of it.
+pub struct SpscQueue<T: Pod> {
+    header: MmapRaw,
+    ring: DoubleMapping,
+    capacity: usize,
+    _file: File,
+    _ph: PhantomData<T>,
+}
+
+impl<T: Pod> SpscQueue<T> {
+    /// Create a new, empty queue at the given path with room for at least
+    /// `capacity` elements. Any existing file is truncated.
+    pub fn create(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path)?;
+        let ring_bytes = ring_size::<T>(capacity)?;
+        let file_size = ring_bytes
+            .checked_add(page_size())
+            .ok_or(MmapBufferError::CapacityOverflow)?;
+        file.set_len(file_size as u64)?;
+
+        Self::from_file(file)
+    }
+
+    /// Open an existing queue, created by [`create`](Self::create) possibly
+    /// in another process.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Self::from_file(file)
+    }
+
+    fn from_file(file: File) -> Result<Self, MmapBufferError> {
+        let file_size = file.metadata()?.len() as usize;
+        let element_size = std::mem::size_of::<T>();
+        let ring_bytes = file_size.saturating_sub(page_size());
+        if ring_bytes == 0
+            || !ring_bytes.is_multiple_of(page_size())
+            || !ring_bytes.is_multiple_of(element_size)
+        {
+            return Err(MmapBufferError::SizeMismatch {
+                file_size,
+                element_size,
+            });
+        }
+
+        // SAFETY: concurrent access is coordinated through the atomics in
+        // the header
+        let header = MmapOptions::new().len(page_size()).map_raw(&file)?;
+        let ring = unsafe { DoubleMapping::new(&file, page_size(), ring_bytes)? };
+
+        Ok(Self {
+            header,
+            ring,
+            capacity: ring_bytes / element_size,
+            _file: file,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Maximum number of elements the queue can hold
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of elements currently in the queue. May already be out of
+    /// date when it returns, if the other side is active.
+    pub fn len(&self) -> usize {
+        let tail = self.tail().load(Ordering::Acquire);
+        let head = self.head().load(Ordering::Acquire);
+        self.occupied(head, tail)
+    }
+
+    /// Returns `true` if the queue currently holds no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append an element, or hand it back if the queue is full. Must only
+    /// be called by the producer.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        match self.push_slice(std::slice::from_ref(&value)) {
+            0 => Err(value),
+            _ => Ok(()),
+        }
+    }
+
+    /// Append as many elements of `values` as fit, returning how many were
+    /// written. Must only be called by the producer.
+    pub fn push_slice(&mut self, values: &[T]) -> usize {
+        let tail = self.tail().load(Ordering::Relaxed);
+        let head = self.head().load(Ordering::Acquire);
+
+        let free = self.capacity - self.occupied(head, tail);
+        let count = usize::min(free, values.len());
+        self.window(tail, count).copy_from_slice(&values[..count]);
+
+        self.tail()
+            .store(tail.wrapping_add(count as u64), Ordering::Release);
+        count
+    }
+
+    /// Remove the oldest element, or `None` if the queue is empty. Must
+    /// only be called by the consumer.
+    pub fn pop(&mut self) -> Option<T> {
+        let mut value = T::zeroed();
+        match self.pop_slice(std::slice::from_mut(&mut value)) {
+            0 => None,
+            _ => Some(value),
+        }
+    }
+
+    /// Remove as many elements as are available into `out`, oldest first,
+    /// returning how many were read. Must only be called by the consumer.
+    pub fn pop_slice(&mut self, out: &mut [T]) -> usize {
+        let head = self.head().load(Ordering::Relaxed);
+        let tail = self.tail().load(Ordering::Acquire);
+
+        let available = self.occupied(head, tail);
+        let count = usize::min(available, out.len());
+        out[..count].copy_from_slice(self.window(head, count));
+
+        self.head()
+            .store(head.wrapping_add(count as u64), Ordering::Release);
+        count
+    }
+
+    /// Index of the next element to pop, only ever advanced by the consumer
+    fn head(&self) -> &AtomicU64 {
+        self.index(HEAD_OFFSET)
+    }
+
+    /// Index of the next element to push, only ever advanced by the producer
+    fn tail(&self) -> &AtomicU64 {
+        self.index(TAIL_OFFSET)
+    }
+
+    fn index(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: the header is page-aligned and at least a page long, and
+        // is only ever accessed atomically
+        unsafe { &*self.header.as_mut_ptr().add(offset).cast::<AtomicU64>() }
+    }
+
+    /// Number of elements between the indices, which another process may
+    /// have left further apart than the ring is long
+    fn occupied(&self, head: u64, tail: u64) -> usize {
+        tail.wrapping_sub(head).min(self.capacity as u64) as usize
+    }
+
+    /// Contiguous window of `len` elements starting at the unwrapped
+    /// position `start`
+    fn window(&mut self, start: u64, len: usize) -> &mut [T] {
+        let offset = (start % self.capacity as u64) as usize * std::mem::size_of::<T>();
+
+        // SAFETY: the window lies within the double mapping, and the indices
+        // guarantee the other side isn't accessing it
+        unsafe { std::slice::from_raw_parts_mut(self.ring.as_mut_ptr().add(offset).cast(), len) }
+    }
+}
+
+/// A fixed size buffer of `T` in a file shared between processes, guarded
+/// by a process-shared reader-writer lock stored in the first page of the
+/// file.
+///
+/// Any number of processes can read the buffer at once, while writers get
+/// exclusive access, much like a [`RwLock`](std::sync::RwLock). Unlike
+/// whole-file advisory locks, processes keep the buffer mapped throughout
+/// and only hold the lock while accessing it.
+///
+/// The lock is a `pthread_rwlock_t`, whose layout depends on the C library,
+/// so the file can only be shared between processes built against the same
+/// one. A process which dies while holding the lock leaves it held.
+pub struct SharedRwBuffer<T: Pod> {
+    mmap: MmapRaw,
+    len: usize,
+    _file: File,
+    _ph: PhantomData<T>,
+}
+
+/// Shared access to a [`SharedRwBuffer`], released on drop.
+///
+/// Like a [`MutexGuard`](std::sync::MutexGuard), the guard can't be sent to
+/// another thread, since the lock has to be released by the thread which
+/// took it:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<mmap_buffer::ipc::SharedReadGuard<'static, u64>>();
+/// ```
+pub struct SharedReadGuard<'a, T: Pod> {
+    buffer: &'a SharedRwBuffer<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Exclusive access to a [`SharedRwBuffer`], released on drop.
+///
+/// Like a [`MutexGuard`](std::sync::MutexGuard), the guard can't be sent to
+/// another thread, since the lock has to be released by the thread which
+/// took it:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<mmap_buffer::ipc::SharedWriteGuard<'static, u64>>();
+/// ```
+pub struct SharedWriteGuard<'a, T: Pod> {
+    buffer: &'a SharedRwBuffer<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+// SAFETY: sharing a guard only gives out shared references to the elements
+unsafe impl<T: Pod + Sync> Sync for SharedReadGuard<'_, T> {}
+unsafe impl<T: Pod + Sync> Sync for SharedWriteGuard<'_, T> {}
+
+impl<T: Pod> SharedRwBuffer<T> {
+    /// Create a new, zeroed buffer at the given path with a fixed capacity
+    /// in units of `T`. Any existing file is truncated, so this must not
+    /// race with other processes opening it.
+    pub fn create(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path)?;
+        file.set_len(byte_size::<T>(page_size(), capacity)? as u64)?;
+
+        let buffer = Self::from_file(file)?;
+        buffer.init_lock()?;
+        Ok(buffer)
+    }
+
+    /// Open an existing buffer, created by [`create`](Self::create) possibly
+    /// in another process.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let buffer = Self::from_file(file)?;
+
+        if buffer.magic().load(Ordering::Acquire) != RWLOCK_MAGIC {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        Ok(buffer)
+    }
+
+    fn from_file(file: File) -> Result<Self, MmapBufferError> {
+        let file_size = file.metadata()?.len() as usize;
+        let element_size = std::mem::size_of::<T>();
+        let data_bytes = file_size
+            .checked_sub(page_size())
+            .ok_or(MmapBufferError::InvalidHeader)?;
+        if !data_bytes.is_multiple_of(element_size) {
+            return Err(MmapBufferError::SizeMismatch {
+                file_size,
+                element_size,
+            });
+        }
+
+        // Only accessed through raw pointers, coordinated through the lock
+        // in the header
+        let mmap = MmapRaw::map_raw(&file)?;
+
+        Ok(Self {
+            mmap,
+            len: data_bytes / element_size,
+            _file: file,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Number of elements in the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer holds no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Lock the buffer for reading, blocking while a writer holds the lock
+    pub fn read(&self) -> Result<SharedReadGuard<'_, T>, MmapBufferError> {
+        // SAFETY: the lock was initialized by `create`
+        check(unsafe { libc::pthread_rwlock_rdlock(self.lock()) })?;
+        Ok(SharedReadGuard {
+            buffer: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Lock the buffer for reading, or fail with [`MmapBufferError::Locked`]
+    /// if a writer holds the lock
+    pub fn try_read(&self) -> Result<SharedReadGuard<'_, T>, MmapBufferError> {
+        // SAFETY: the lock was initialized by `create`
+        check(unsafe { libc::pthread_rwlock_tryrdlock(self.lock()) })?;
+        Ok(SharedReadGuard {
+            buffer: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Lock the buffer for writing, blocking while anyone else holds the lock
+    pub fn write(&self) -> Result<SharedWriteGuard<'_, T>, MmapBufferError> {
+        // SAFETY: the lock was initialized by `create`
+        check(unsafe { libc::pthread_rwlock_wrlock(self.lock()) })?;
+        Ok(SharedWriteGuard {
+            buffer: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Lock the buffer for writing, or fail with [`MmapBufferError::Locked`]
+    /// if anyone else holds the lock
+    pub fn try_write(&self) -> Result<SharedWriteGuard<'_, T>, MmapBufferError> {
+        // SAFETY: the lock was initialized by `create`
+        check(unsafe { libc::pthread_rwlock_trywrlock(self.lock()) })?;
+        Ok(SharedWriteGuard {
+            buffer: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    fn init_lock(&self) -> Result<(), MmapBufferError> {
+        // SAFETY: nobody else can use the lock before the marker is set, and
+        // the attributes are destroyed right after use
+        unsafe {
+            let mut attr = std::mem::MaybeUninit::<libc::pthread_rwlockattr_t>::uninit();
+            check(libc::pthread_rwlockattr_init(attr.as_mut_ptr()))?;
+            let result = check(libc::pthread_rwlockattr_setpshared(
+                attr.as_mut_ptr(),
+                libc::PTHREAD_PROCESS_SHARED,
+            ))
+            .and_then(|_| check(libc::pthread_rwlock_init(self.lock(), attr.as_ptr())));
+            libc::pthread_rwlockattr_destroy(attr.as_mut_ptr());
+            result?;
+        }
+
+        self.magic().store(RWLOCK_MAGIC, Ordering::Release);
+        Ok(())
+    }
+
+    fn magic(&self) -> &AtomicU64 {
+        // SAFETY: the header is page-aligned and at least a page long, and
+        // the marker is only ever accessed atomically
+        unsafe { &*self.mmap.as_ptr().add(MAGIC_OFFSET).cast::<AtomicU64>() }
+    }
+
+    fn lock(&self) -> *mut libc::pthread_rwlock_t {
+        // SAFETY: the lock lies within the header page
+        unsafe { self.mmap.as_mut_ptr().add(LOCK_OFFSET).cast() }
+    }
+
+    fn data(&self) -> *mut T {
+        // SAFETY: the data starts right after the header page
+        unsafe { self.mmap.as_mut_ptr().add(page_size()).cast() }
+    }
+}
+
+/// Turn the result of a pthread call into an error
+fn check(result: libc::c_int) -> Result<(), MmapBufferError> {
+    match result {
+        0 => Ok(()),
+        libc::EBUSY => Err(MmapBufferError::Locked),
+        err => Err(io::Error::from_raw_os_error(err).into()),
+    }
+}
+
+impl<T: Pod> Deref for SharedReadGuard<'_, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the data is page-aligned, and writers are kept out while
+        // the guard holds the lock
+        unsafe { std::slice::from_raw_parts(self.buffer.data(), self.buffer.len) }
+    }
+}
+
+impl<T: Pod> Deref for SharedWriteGuard<'_, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the data is page-aligned, and the guard has exclusive
+        // access while it holds the lock
+        unsafe { std::slice::from_raw_parts(self.buffer.data(), self.buffer.len) }
+    }
+}
+
+impl<T: Pod> DerefMut for SharedWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the data is page-aligned, and the guard has exclusive
+        // access while it holds the lock
+        unsafe { std::slice::from_raw_parts_mut(self.buffer.data(), self.buffer.len) }
+    }
+}
+
+impl<T: Pod> Drop for SharedReadGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the guard holds the lock
+        unsafe { libc::pthread_rwlock_unlock(self.buffer.lock()) };
+    }
+}
+
+impl<T: Pod> Drop for SharedWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: the guard holds the lock
+        unsafe { libc::pthread_rwlock_unlock(self.buffer.lock()) };
+    }
+}
+
+/// A fixed size buffer of `T` in a file shared between processes, guarded
+/// by a sequence lock, for small records which are read far more often than
+/// they are written, like configuration blocks or market data.
+///
+/// Writers make the sequence counter in the first page of the file odd
+/// while they update the buffer, and even again once done. Readers never
+/// block, or hold anything up: they copy the buffer out and retry if the
+/// counter shows a write overlapped the copy, so every read returns a
+/// consistent snapshot. Writers exclude each other through the counter too,
+/// but reads can be starved by a constant stream of writes. Both sides copy
+/// the data word by word with atomic accesses, since the other may be
+/// touching it at the same time.
+///
+/// A process which dies in the middle of a write leaves the counter odd,
+/// which wedges the file: [`read_into`](Self::read_into) and
+/// [`write`](Self::write) wait for it forever. The `try_` variants give up
+/// after a timeout instead, and once the writer is known to be gone,
+/// [`recover`](Self::recover) unwedges the file.
+pub struct SeqlockBuffer<T: Pod> {
+    mmap: MmapRaw,
+    len: usize,
+    _file: File,
+    _ph: PhantomData<T>,
+}
+
+impl<T: Pod> SeqlockBuffer<T> {
+    /// Create a new, zeroed buffer at the given path with a fixed capacity
+    /// in units of `T`. Any existing file is truncated.
+    pub fn create(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path)?;
+        file.set_len(byte_size::<T>(page_size(), capacity)? as u64)?;
+
+        let buffer = Self::from_file(file)?;
+        buffer
+            .counter(MAGIC_OFFSET)
+            .store(SEQLOCK_MAGIC, Ordering::Release);
+        Ok(buffer)
+    }
+
+    /// Open an existing buffer, created by [`create`](Self::create) possibly
+    /// in another process.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let buffer = Self::from_file(file)?;
+
+        if buffer.counter(MAGIC_OFFSET).load(Ordering::Acquire) != SEQLOCK_MAGIC {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        Ok(buffer)
+    }
+
+    fn from_file(file: File) -> Result<Self, MmapBufferError> {
+        let file_size = file.metadata()?.len() as usize;
+        let element_size = std::mem::size_of::<T>();
+        let data_bytes = file_size
+            .checked_sub(page_size())
+            .ok_or(MmapBufferError::InvalidHeader)?;
+        if !data_bytes.is_multiple_of(element_size) {
+            return Err(MmapBufferError::SizeMismatch {
+                file_size,
+                element_size,
+            });
+        }
+
+        // Only accessed through raw pointers, coordinated through the
+        // sequence counter in the header
+        let mmap = MmapRaw::map_raw(&file)?;
+
+        Ok(Self {
+            mmap,
+            len: data_bytes / element_size,
+            _file: file,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Number of elements in the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer holds no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy a consistent snapshot of the buffer into `out`, retrying while
+    /// writes overlap the copy. Panics if `out` isn't as long as the buffer.
+    /// Never returns if a writer died mid-write, see
+    /// [`try_read_into`](Self::try_read_into).
+    pub fn read_into(&self, out: &mut [T]) {
+        self.read_until(out, None).unwrap();
+    }
+
+    /// A consistent snapshot of the buffer, see [`read_into`](Self::read_into)
+    pub fn read(&self) -> Vec<T> {
+        let mut out = vec![T::zeroed(); self.len];
+        self.read_into(&mut out);
+        out
+    }
+
+    /// Like [`read_into`](Self::read_into), but failing with
+    /// [`MmapBufferError::Locked`] if no consistent snapshot could be taken
+    /// within `timeout`, e.g. because a writer died mid-write.
+    pub fn try_read_into(&self, out: &mut [T], timeout: Duration) -> Result<(), MmapBufferError> {
+        self.read_until(out, Instant::now().checked_add(timeout))
+    }
+
+    /// Like [`read`](Self::read), but giving up after `timeout`, see
+    /// [`try_read_into`](Self::try_read_into)
+    pub fn try_read(&self, timeout: Duration) -> Result<Vec<T>, MmapBufferError> {
+        let mut out = vec![T::zeroed(); self.len];
+        self.try_read_into(&mut out, timeout)?;
+        Ok(out)
+    }
+
+    /// Update the buffer, waiting for any other writer to finish first.
+    /// `f` works on a private copy of the contents, which is only published
+    /// once it returns, and readers retry until that is complete. If `f`
+    /// panics, the buffer is left as it was. Never returns if a writer died
+    /// mid-write, see [`try_write`](Self::try_write).
+    pub fn write<R>(&self, f: impl FnOnce(&mut [T]) -> R) -> R {
+        self.write_until(None, f).unwrap()
+    }
+
+    /// Like [`write`](Self::write), but failing with
+    /// [`MmapBufferError::Locked`] if another writer held the buffer for
+    /// the whole of `timeout`.
+    pub fn try_write<R>(
+        &self,
+        timeout: Duration,
+        f: impl FnOnce(&mut [T]) -> R,
+    ) -> Result<R, MmapBufferError> {
+        self.write_until(Instant::now().checked_add(timeout), f)
+    }
+
+    /// Release the buffer from a writer which died mid-write, returning
+    /// whether it was held. Only call this once that writer is known to be
+    /// gone, e.g. through its [pid file](crate::BackedBufferBuilder::pid_file),
+    /// since a live writer would then race with others. Its update may have
+    /// been published partway, so the contents should be rewritten.
+    pub fn recover(&self) -> bool {
+        let seq = self.counter(SEQ_OFFSET);
+        let current = seq.load(Ordering::Relaxed);
+        current % 2 == 1
+            && seq
+                .compare_exchange(current, current + 1, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+    }
+
+    fn read_until(&self, out: &mut [T], deadline: Option<Instant>) -> Result<(), MmapBufferError> {
+        assert_eq!(out.len(), self.len, "output must be as long as the buffer!");
+        let seq = self.counter(SEQ_OFFSET);
+        let mut backoff = Backoff::new(deadline);
+
+        loop {
+            let before = seq.load(Ordering::Acquire);
+            if before.is_multiple_of(2) {
+                // A concurrent write may tear the copy, but that is detected
+                // below and the copy thrown away
+                self.load_data(out);
+
+                std::sync::atomic::fence(Ordering::Acquire);
+                if seq.load(Ordering::Relaxed) == before {
+                    return Ok(());
+                }
+            }
+
+            backoff.wait()?;
+        }
+    }
+
+    fn write_until<R>(
+        &self,
+        deadline: Option<Instant>,
+        f: impl FnOnce(&mut [T]) -> R,
+    ) -> Result<R, MmapBufferError> {
+        let seq = self.counter(SEQ_OFFSET);
+        let mut backoff = Backoff::new(deadline);
+
+        // Claim the buffer by making the counter odd
+        let mut current = seq.load(Ordering::Relaxed);
+        loop {
+            if current.is_multiple_of(2) {
+                match seq.compare_exchange_weak(
+                    current,
+                    current + 1,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(actual) => {
+                        current = actual;
+                        continue;
+                    }
+                }
+            }
+
+            backoff.wait()?;
+            current = seq.load(Ordering::Relaxed);
+        }
+        // Keep the writes below from becoming visible before the odd counter
+        std::sync::atomic::fence(Ordering::Release);
+
+        // Put the counter back if `f` panics, since nothing was written yet
+        let mut release = Release { seq, next: current };
+
+        // Other writers are kept out, so this copy is consistent
+        let mut data = vec![T::zeroed(); self.len];
+        self.load_data(&mut data);
+        let result = f(&mut data);
+
+        self.store_data(&data);
+        release.next = current + 2;
+        Ok(result)
+    }
+
+    /// Copy the data into `out` with relaxed atomic loads
+    fn load_data(&self, out: &mut [T]) {
+        let out = bytemuck::cast_slice_mut::<T, u8>(out);
+        let (words, bytes) = self.data_words();
+        for (chunk, word) in out.chunks_exact_mut(8).zip(words) {
+            chunk.copy_from_slice(&word.load(Ordering::Relaxed).to_ne_bytes());
+        }
+        for (byte, atomic) in out
+            .chunks_exact_mut(8)
+            .into_remainder()
+            .iter_mut()
+            .zip(bytes)
+        {
+            *byte = atomic.load(Ordering::Relaxed);
+        }
+    }
+
+    /// Copy `data` into the buffer with relaxed atomic stores
+    fn store_data(&self, data: &[T]) {
+        let data = bytemuck::cast_slice::<T, u8>(data);
+        let (words, bytes) = self.data_words();
+        for (chunk, word) in data.chunks_exact(8).zip(words) {
+            word.store(
+                u64::from_ne_bytes(chunk.try_into().unwrap()),
+                Ordering::Relaxed,
+            );
+        }
+        for (&byte, atomic) in data.chunks_exact(8).remainder().iter().zip(bytes) {
+            atomic.store(byte, Ordering::Relaxed);
+        }
+    }
+
+    fn counter(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: the header is page-aligned and at least a page long, and
+        // the counters are only ever accessed atomically
+        unsafe { &*self.mmap.as_ptr().add(offset).cast::<AtomicU64>() }
+    }
+
+    /// The data as whole words, followed by the bytes left over at the end
+    fn data_words(&self) -> (&[AtomicU64], &[AtomicU8]) {
+        let len = self.len * std::mem::size_of::<T>();
+
+        // SAFETY: the data starts right after the header page, so words are
+        // aligned, and it is only ever accessed atomically, through words
+        // and leftover bytes which don't overlap
+        unsafe {
+            let data = self.mmap.as_ptr().add(page_size());
+            (
+                std::slice::from_raw_parts(data.cast(), len / 8),
+                std::slice::from_raw_parts(data.add(len / 8 * 8).cast(), len % 8),
+            )
+        }
+    }
+}
+
+/// Waits between attempts at a [`SeqlockBuffer`], spinning at first and
+/// then yielding, until an optional deadline
+struct Backoff {
+    spins: u32,
+    deadline: Option<Instant>,
+}
+
+impl Backoff {
+    fn new(deadline: Option<Instant>) -> Self {
+        Self { spins: 0, deadline }
+    }
+
+    fn wait(&mut self) -> Result<(), MmapBufferError> {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(MmapBufferError::Locked);
+        }
+
+        if self.spins < 64 {
+            self.spins += 1;
+            std::hint::spin_loop();
+        } else {
+            std::thread::yield_now();
+        }
+        Ok(())
+    }
+}
+
+/// Ends a [`SeqlockBuffer`] write by storing the next even counter value
+struct Release<'a> {
+    seq: &'a AtomicU64,
+    next: u64,
+}
+
+impl Drop for Release<'_> {
+    fn drop(&mut self) {
+        self.seq.store(self.next, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SeqlockBuffer, SharedRwBuffer, SpscQueue, SEQ_OFFSET, TAIL_OFFSET};
+    use crate::MmapBufferError;
+    use std::{
+        error::Error, fs::OpenOptions, os::unix::fs::FileExt, path::Path, sync::atomic::Ordering,
+        time::Duration,
+    };
+
+    #[test]
+    fn producer_consumer() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut producer = SpscQueue::<u64>::create(16, file_path.clone())?;
+        let mut consumer = SpscQueue::<u64>::open(file_path)?;
+        let capacity = producer.capacity();
+
+        // Fill up completely, forcing later pushes across the wrap point
+        assert_eq!(producer.push_slice(&vec![0; capacity + 1]), capacity);
+        assert_eq!(producer.push(1), Err(1));
+        assert_eq!(consumer.pop_slice(&mut vec![0; capacity - 2]), capacity - 2);
+
+        let thread = std::thread::spawn(move || {
+            for i in 0..10_000 {
+                while producer.push(i).is_err() {
+                    std::hint::spin_loop();
+                }
+            }
+        });
+
+        assert_eq!(consumer.pop_slice(&mut [0; 2]), 2);
+        for i in 0..10_000 {
+            let value = loop {
+                if let Some(value) = consumer.pop() {
+                    break value;
+                }
+                std::hint::spin_loop();
+            };
+            assert_eq!(value, i);
+        }
+
+        thread.join().unwrap();
+        assert!(consumer.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn corrupted_indices() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut queue = SpscQueue::<u64>::create(16, file_path.clone())?;
+        let capacity = queue.capacity();
+
+        // Another process moves the tail far past the head
+        let file = OpenOptions::new().write(true).open(&file_path)?;
+        file.write_all_at(&(10 * capacity as u64).to_ne_bytes(), TAIL_OFFSET as u64)?;
+
+        assert_eq!(queue.len(), capacity);
+        assert_eq!(queue.push(1), Err(1));
+        assert_eq!(queue.pop_slice(&mut vec![0; 2 * capacity]), capacity);
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_rwlock() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let writer = SharedRwBuffer::<u64>::create(100, file_path.clone())?;
+        let reader = SharedRwBuffer::<u64>::open(file_path.clone())?;
+        assert_eq!(reader.len(), 100);
+
+        // Readers coexist, but keep writers out
+        let read_1 = reader.read()?;
+        let read_2 = writer.try_read()?;
+        assert!(matches!(writer.try_write(), Err(MmapBufferError::Locked)));
+        drop((read_1, read_2));
+
+        let thread = std::thread::spawn(move || -> Result<(), MmapBufferError> {
+            for _ in 0..1000 {
+                let mut data = writer.write()?;
+                let next = data[0] + 1;
+                data.fill(next);
+            }
+            Ok(())
+        });
+
+        // Every element is always updated at once
+        loop {
+            let data = reader.read()?;
+            assert!(data.iter().all(|&x| x == data[0]));
+            if data[0] == 1000 {
+                break;
+            }
+        }
+        thread.join().unwrap()?;
+
+        // Files without an initialized lock are rejected
+        std::fs::write(&file_path, vec![0; 2 * crate::dirty::page_size()])?;
+        assert!(SharedRwBuffer::<u64>::open(file_path).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn seqlock() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let writer = SeqlockBuffer::<u64>::create(64, file_path.clone())?;
+        let reader = SeqlockBuffer::<u64>::open(file_path)?;
+
+        let thread = std::thread::spawn(move || {
+            for i in 1..=10_000 {
+                writer.write(|data| data.fill(i));
+            }
+        });
+
+        // Snapshots are never torn
+        let mut snapshot = vec![0; reader.len()];
+        loop {
+            reader.read_into(&mut snapshot);
+            assert!(snapshot.iter().all(|&x| x == snapshot[0]));
+            if snapshot[0] == 10_000 {
+                break;
+            }
+        }
+        thread.join().unwrap();
+        assert_eq!(reader.read(), vec![10_000; 64]);
+
+        // A panicking writer leaves the contents alone and lets others in
+        let result = std::panic::catch_unwind(|| {
+            reader.write(|data| {
+                data[0] = 1;
+                panic!()
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(reader.read(), vec![10_000; 64]);
+        reader.write(|data| data.fill(1));
+        assert_eq!(reader.read(), vec![1; 64]);
+
+        // A writer dying mid-write wedges the buffer until recovered
+        reader.counter(SEQ_OFFSET).fetch_add(1, Ordering::Relaxed);
+        let timeout = Duration::from_millis(10);
+        assert!(matches!(
+            reader.try_read(timeout),
+            Err(MmapBufferError::Locked)
+        ));
+        assert!(matches!(
+            reader.try_write(timeout, |_| ()),
+            Err(MmapBufferError::Locked)
+        ));
+        assert!(reader.recover());
+        assert!(!reader.recover());
+        reader.try_write(timeout, |data| data.fill(2))?;
+        assert_eq!(reader.try_read(timeout)?, vec![2; 64]);
+
+        // Sizes which aren't a whole number of words
+        let file_path = Path::join(tempdir.path(), "bytes");
+        let bytes = SeqlockBuffer::<[u8; 3]>::create(5, file_path)?;
+        bytes.write(|data| data.fill([1, 2, 3]));
+        assert_eq!(bytes.read(), vec![[1, 2, 3]; 5]);
+
+        Ok(())
+    }
+}
@@ -25,19 +25,145 @@
 //! ```
 
 #![deny(missing_docs)]
+
+// Lets the code `#[derive(MmapRecord)]` generates name this crate from
+// within it, for its own tests
+extern crate self as mmap_buffer;
+
 use std::{
-    error::Error,
-    fs::{File, OpenOptions},
-    io::{Seek, SeekFrom, Write},
+    fs::File,
+    io::{self, Read, Write},
     marker::PhantomData,
-    ops::{Deref, DerefMut},
-    path::Path,
+    ops::{Deref, DerefMut, Range},
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
 use bytemuck::{try_cast_slice, try_cast_slice_mut, Pod};
-use fs2::FileExt;
+#[cfg(unix)]
+use memmap2::Advice;
 use memmap2::MmapOptions;
 
+mod arc;
+mod array;
+mod atomic;
+mod builder;
+mod cast;
+mod cell;
+mod checked;
+mod checksum;
+mod cmp;
+mod compressed;
+mod container;
+mod copy;
+mod cursor;
+mod dir;
+mod dirty;
+mod endian;
+mod error;
+mod fallible;
+#[cfg(unix)]
+mod fd;
+#[cfg(unix)]
+mod freeze;
+mod fsck;
+#[cfg(unix)]
+mod guard;
+mod header;
+#[cfg(all(unix, target_has_atomic = "64"))]
+pub mod ipc;
+mod log;
+mod matrix;
+#[cfg(target_os = "linux")]
+mod memfd;
+mod merkle;
+mod migrate;
+mod npy;
+mod nt;
+#[cfg(target_os = "linux")]
+mod numa;
+mod pid_file;
+#[cfg(target_os = "linux")]
+mod pmem;
+#[cfg(unix)]
+mod range_lock;
+mod read_only;
+mod record;
+#[cfg(unix)]
+mod residency;
+#[cfg(unix)]
+mod ring;
+mod safetensors;
+#[cfg(unix)]
+mod shared;
+mod soa;
+mod sparse;
+mod swap;
+mod transaction;
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+mod uring;
+mod vec;
+mod wal;
+mod watch;
+mod zeroize;
+
+pub use arc::ArcBuffer;
+pub use array::BackedArray;
+pub use atomic::AtomicElement;
+pub use builder::{
+    BackedBufferBuilder, DirtyFlag, FlushOnDrop, LockMode, OnCorruption, SizePolicy, ZeroizeOnDrop,
+};
+pub use cell::BackedCell;
+pub use checked::CheckedBackedBuffer;
+pub use compressed::CompressedBuffer;
+pub use container::{Container, MAX_NAME_LEN, MAX_SECTIONS};
+pub use cursor::BufferCursor;
+pub use dir::BufferDir;
+pub use endian::{EndianScalar, BE, LE};
+pub use error::MmapBufferError;
+#[cfg(unix)]
+pub use freeze::FrozenBuffer;
+pub use fsck::ValidationReport;
+pub use log::BackedLog;
+pub use matrix::{Matrix, MatrixMut};
+#[cfg(target_os = "linux")]
+pub use memfd::{MemfdBuffer, SealedBuffer};
+pub use merkle::MerkleTree;
+pub use npy::{NpyElement, NpyShape};
+#[cfg(target_os = "linux")]
+pub use numa::NumaPolicy;
+pub use pid_file::LockOwner;
+#[cfg(target_os = "linux")]
+pub use pmem::PmemBuffer;
+#[cfg(unix)]
+pub use range_lock::RangeLock;
+pub use read_only::ReadOnlyBuffer;
+pub use record::MmapRecord;
+#[cfg(unix)]
+pub use residency::Residency;
+#[cfg(unix)]
+pub use ring::RingBuffer;
+pub use safetensors::{SafeTensorsFile, TensorElement, TensorInfo};
+#[cfg(unix)]
+pub use shared::SharedBuffer;
+pub use soa::{Column, ColumnTypes, SoaBuffer};
+pub use transaction::{Transaction, TransactionalBuffer};
+#[cfg(all(target_os = "linux", feature = "io_uring"))]
+pub use uring::IoUring;
+pub use vec::BackedVec;
+pub use wal::WalBuffer;
+pub use watch::{FileEvent, FileWatcher};
+
+/// Derive [`MmapRecord`] for a `#[repr(C)]` struct with named fields
+pub use mmap_buffer_derive::MmapRecord;
+
+#[doc(hidden)]
+pub use record::fingerprint as __private;
+
+/// Bytes moved per call by [`BackedBuffer::read_from`] and
+/// [`BackedBuffer::write_to`]
+const STREAM_CHUNK_SIZE: usize = 1 << 20;
+
 /// Helpful abstraction for some buffer, either backed by
 /// a file, or stored in memory
 pub enum Buffer<T: Pod> {
@@ -45,12 +171,25 @@ pub enum Buffer<T: Pod> {
     Disk(BackedBuffer<T>),
     /// In-memory buffer
     Memory(Vec<T>),
+    /// In-memory buffer backed by an anonymous mapping
+    Anonymous(BackedBuffer<T>),
+}
+
+/// How [`Buffer::try_clone`] copies a [`Buffer::Disk`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiskClone {
+    /// Copy the contents into a [`Buffer::Anonymous`], leaving the file to
+    /// the original
+    #[default]
+    Anonymous,
+    /// Fail, for callers which never expect to copy a file's worth of data
+    Refuse,
 }
 
 impl<T: Pod> Buffer<T> {
     /// Create a new buffer at the given path with a fixed capacity.
     /// This capacity is in units of `T`, not in bytes
-    pub fn new_on_disk(capacity: usize, path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+    pub fn new_on_disk(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
         Ok(Self::Disk(BackedBuffer::new(capacity, path)?))
     }
 
@@ -59,8 +198,14 @@ impl<T: Pod> Buffer<T> {
         Self::Memory(vec![T::zeroed(); capacity])
     }
 
+    /// Create a new buffer with fixed capacity backed by an anonymous
+    /// mapping, see [`BackedBuffer::anonymous`]
+    pub fn new_anonymous(capacity: usize) -> Result<Self, MmapBufferError> {
+        Ok(Self::Anonymous(BackedBuffer::anonymous(capacity)?))
+    }
+
     /// Load a buffer from an existing path.
-    pub fn load_from_disk(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+    pub fn load_from_disk(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
         Ok(Self::Disk(BackedBuffer::load(path)?))
     }
 
@@ -71,10 +216,60 @@ impl<T: Pod> Buffer<T> {
 
     /// Creates a new buffer at the given path and copies the contents of
     /// the slice to it. The created buffer will be the same size as the slice.
-    pub fn from_slice_on_disk(data: &[T], path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+    pub fn from_slice_on_disk(data: &[T], path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
         Ok(Self::Disk(BackedBuffer::copy_from_slice(data, path)?))
     }
 
+    /// Copy the buffer: memory buffers are copied as they are, and anonymous
+    /// ones into a new anonymous mapping. A [`Buffer::Disk`] is handled
+    /// according to `policy`, since a second buffer can't own the same
+    /// file, failing for [`DiskClone::Refuse`].
+    pub fn try_clone(&self, policy: DiskClone) -> Result<Self, MmapBufferError> {
+        match (self, policy) {
+            (Self::Memory(buffer), _) => Ok(Self::Memory(buffer.clone())),
+            (Self::Disk(_), DiskClone::Refuse) => Err(MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "disk buffers can't be cloned with `DiskClone::Refuse`",
+            ))),
+            (Self::Disk(buffer) | Self::Anonymous(buffer), _) => {
+                let mut clone = BackedBuffer::anonymous(buffer.len())?;
+                clone.copy_from_slice(buffer);
+                Ok(Self::Anonymous(clone))
+            }
+        }
+    }
+
+    /// Grow or truncate the buffer to `new_capacity` elements. New elements
+    /// are zeroed, see [`BackedBuffer::resize`].
+    pub fn resize(&mut self, new_capacity: usize) -> Result<(), MmapBufferError> {
+        match self {
+            Self::Disk(buffer) | Self::Anonymous(buffer) => buffer.resize(new_capacity),
+            Self::Memory(buffer) => {
+                buffer.resize(new_capacity, T::zeroed());
+                Ok(())
+            }
+        }
+    }
+
+    /// Move the contents into a new file at the given path, turning this
+    /// into a [`Buffer::Disk`]. Does nothing if the buffer is already on disk.
+    pub fn spill_to_disk(&mut self, path: impl AsRef<Path>) -> Result<(), MmapBufferError> {
+        if !matches!(self, Self::Disk(_)) {
+            *self = Self::from_slice_on_disk(self, path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy the contents into memory, turning this into a
+    /// [`Buffer::Memory`]. The backing file of a [`Buffer::Disk`] is left in
+    /// place, but unlocked. Does nothing if the buffer is already in memory.
+    pub fn load_into_memory(&mut self) {
+        if !matches!(self, Self::Memory(_)) {
+            *self = Self::Memory(self.to_vec());
+        }
+    }
+
     /// Shrink the `Buffer` so that users cannot access past this new
     /// length. In the case that the buffer is backed, doesn't actually reduce
     /// the size of the file, this is a very low cost operation.
@@ -84,7 +279,7 @@ impl<T: Pod> Buffer<T> {
             "`new_len` must be less than current length!"
         );
         match self {
-            Self::Disk(buffer) => buffer.shrink(new_len),
+            Self::Disk(buffer) | Self::Anonymous(buffer) => buffer.shrink(new_len),
             Self::Memory(buffer) => buffer.resize(new_len, T::zeroed()),
         }
     }
@@ -93,60 +288,358 @@ impl<T: Pod> Buffer<T> {
 /// A fixed size, mutable buffer of `T` backed by a file.
 /// In order to avoid copying when reading and writing from such
 /// a buffer, we require that `T: Pod`.
+///
+/// # Threads
+///
+/// Buffers are `Send` and `Sync`, like a `Vec<T>`: the mapping is owned by
+/// the buffer and only unmapped when it is dropped, reading needs a shared
+/// reference and writing an exclusive one, and the methods taking `&self`
+/// which touch the file, like [`flush`](Self::flush), are safe to call
+/// concurrently. Advisory locks belong to the open file rather than the
+/// thread, so moving a buffer to another thread keeps its lock. To share
+/// one mapping between several threads, see [`ArcBuffer`].
+///
+/// To write to one buffer from several threads, split it into disjoint
+/// mutable slices with the slice methods, such as
+/// [`split_at_mut`](slice::split_at_mut) and
+/// [`chunks_exact_mut`](slice::chunks_exact_mut), and hand them to scoped
+/// threads. The writes reach the file on [`flush`](Self::flush) or when
+/// the buffer is dropped like any others, but aren't seen by
+/// [`flush_dirty`](Self::flush_dirty).
+///
+/// ```
+/// use mmap_buffer::BackedBuffer;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut buf = BackedBuffer::<u64>::anonymous(1 << 16)?;
+/// std::thread::scope(|scope| {
+///     for (i, chunk) in buf.chunks_exact_mut(1 << 14).enumerate() {
+///         scope.spawn(move || chunk.fill(i as u64));
+///     }
+/// });
+/// assert_eq!(buf[(1 << 16) - 1], 3);
+/// # Ok(())
+/// # }
+/// ```
 pub struct BackedBuffer<T: Pod> {
     mmap: memmap2::MmapMut,
+    /// Inaccessible pages on either side of the mapping, if enabled
+    #[cfg(unix)]
+    guards: Option<guard::GuardPages>,
+    /// Byte offset of the first element in the mapping
+    offset: usize,
+    /// Byte offset of the mapping in the file
+    file_offset: u64,
+    /// Whether the mapping is a window into part of the file, see
+    /// [`load_range`](Self::load_range)
+    window: bool,
+    /// Whether the mapping starts with a header recording `len`
+    header: bool,
+    /// Whether changes stay private to the mapping instead of reaching the
+    /// file
+    copy_on_write: bool,
+    /// Whether the mapping is backed by explicit huge pages, see
+    /// [`uses_huge_pages`](Self::uses_huge_pages)
+    huge_pages: bool,
     len: usize,
     file: Option<File>,
+    /// Path of the backing file, if it has one
+    path: Option<PathBuf>,
+    /// Whether to delete the file at `path` on drop
+    delete_on_drop: bool,
+    flush_on_drop: FlushOnDrop,
+    fsync_on_drop: bool,
+    zeroize_on_drop: ZeroizeOnDrop,
+    /// Whether to keep the dirty flag in the header, see [`DirtyFlag`]
+    dirty_flag: bool,
+    /// Whether the dirty flag was set when the file was loaded
+    unclean_shutdown: bool,
+    /// Advisory lock held on the file, or its lock file
+    lock: LockMode,
+    /// What the fallible accessors do when the file was truncated
+    on_corruption: OnCorruption,
+    /// Sidecar file holding the checksum, if enabled
+    checksum: Option<File>,
+    /// Separate file holding the advisory lock, if enabled
+    lock_file: Option<File>,
+    /// Pid file recording this process as the owner, if enabled
+    pid_file: Option<pid_file::PidFile>,
+    dirty: dirty::DirtyPages,
     _ph: PhantomData<T>,
 }
 
 impl<T: Pod> BackedBuffer<T> {
     /// Create a new buffer at the given path with a fixed capacity.
     /// This capacity is in units of `T`, not in bytes
-    pub fn new(capacity: usize, path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(path)?;
+    pub fn new(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().create(capacity, path)
+    }
 
-        let capacity_bytes = capacity * std::mem::size_of::<T>();
+    /// Create a new buffer with a fixed capacity which isn't backed by any
+    /// file. Unlike a `Vec`, the memory is page-aligned and only faulted in
+    /// lazily as it is touched, which makes this well suited to large
+    /// scratch buffers. Contents are lost when the buffer is dropped.
+    pub fn anonymous(capacity: usize) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().anonymous(capacity)
+    }
 
-        // Expand the file
-        file.seek(SeekFrom::Start(0))?;
-        file.allocate(capacity_bytes as u64)?;
+    /// Create a new buffer with a fixed capacity backed by a temporary file
+    /// in the system's temporary directory. The file has no name and is
+    /// deleted by the operating system once the buffer is dropped, even if
+    /// the process panics or is killed. Unlike an
+    /// [anonymous](Self::anonymous) buffer, the contents can be paged out
+    /// to disk under memory pressure.
+    pub fn temp(capacity: usize) -> Result<Self, MmapBufferError> {
+        Self::temp_in(capacity, std::env::temp_dir())
+    }
 
-        // Fill with zeroes (still unsure if there's a better way)
-        const BLOCK_SIZE: usize = 4096;
-        const BLOCK: [u8; BLOCK_SIZE] = [0; BLOCK_SIZE];
+    /// Create a new buffer like [`temp`](Self::temp), but with the temporary
+    /// file in the given directory.
+    pub fn temp_in(capacity: usize, dir: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().create_temp(capacity, dir)
+    }
+
+    /// Load a buffer from an existing path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().load(path)
+    }
+
+    /// Create a new buffer like [`new`](Self::new), but fail with an
+    /// [`io::ErrorKind::AlreadyExists`](std::io::ErrorKind::AlreadyExists)
+    /// error instead of truncating a file which already exists at the path.
+    pub fn create_new(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new()
+            .create_new(true)
+            .create(capacity, path)
+    }
+
+    /// Load the buffer at the given path if a file exists there, or create
+    /// it with a fixed capacity otherwise. The existence check and creation
+    /// happen under the advisory lock, so racing processes can't clobber
+    /// each other. Fails with [`MmapBufferError::LengthMismatch`] if an
+    /// existing file doesn't hold exactly `capacity` elements, or with
+    /// [`MmapBufferError::SizeMismatch`] if it doesn't hold whole elements.
+    pub fn open_or_create(
+        capacity: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().open_or_create(capacity, path)
+    }
 
-        // Convert size to bytes
-        let mut size = capacity_bytes;
-        while size > 0 {
-            let block = usize::min(size, BLOCK_SIZE);
-            file.write_all(&BLOCK[..block])?;
-            size = size.checked_sub(block).unwrap();
+    /// Load a window of `len` elements starting at element `offset` of an
+    /// existing file, without mapping the rest of it. Useful for files
+    /// holding several arrays back to back.
+    pub fn load_range(
+        path: impl AsRef<Path>,
+        offset: usize,
+        len: usize,
+    ) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().load_range(path, offset, len)
+    }
+
+    /// Load a buffer from an existing path, failing with
+    /// [`MmapBufferError::LengthMismatch`] unless it holds exactly
+    /// `expected_len` elements.
+    pub fn load_with_len(
+        path: impl AsRef<Path>,
+        expected_len: usize,
+    ) -> Result<Self, MmapBufferError> {
+        let buffer = Self::load(path)?;
+        if buffer.len() != expected_len {
+            return Err(MmapBufferError::LengthMismatch {
+                expected: expected_len,
+                actual: buffer.len(),
+            });
         }
 
-        unsafe { Self::from_file(file) }
+        Ok(buffer)
     }
 
-    /// Load a buffer from an existing path.
-    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
-        let file = OpenOptions::new().read(true).write(true).open(path)?;
+    /// Create a new buffer like [`new`](Self::new), but with a small header
+    /// describing the element type at the start of the file. Such files must
+    /// be loaded with [`load_with_header`](Self::load_with_header).
+    pub fn new_with_header(
+        capacity: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new()
+            .header(true)
+            .create(capacity, path)
+    }
+
+    /// Load a buffer created with [`new_with_header`](Self::new_with_header),
+    /// checking that the header matches the element type `T`.
+    pub fn load_with_header(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().header(true).load(path)
+    }
+
+    /// Load a buffer from an existing path, waiting for the advisory lock
+    /// instead of failing immediately if it is held elsewhere. Gives up with
+    /// [`MmapBufferError::Locked`] once `timeout` passes, or waits forever
+    /// if there is no timeout.
+    pub fn load_blocking(
+        path: impl AsRef<Path>,
+        timeout: Option<Duration>,
+    ) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().wait_for_lock(timeout).load(path)
+    }
+
+    /// Load a buffer from an existing path without taking any advisory
+    /// lock, so it can be opened while another program holds a lock on
+    /// it. It is the caller's responsibility to make sure nothing else
+    /// modifies the file while this buffer is alive.
+    pub fn load_unlocked(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new()
+            .lock(LockMode::Unlocked)
+            .load(path)
+    }
+
+    /// Load a private, copy-on-write buffer from an existing path. Writes
+    /// to the buffer are visible only to this process and are never carried
+    /// through to the file. Only a shared advisory lock is taken, so this can
+    /// coexist with other readers.
+    pub fn load_cow(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new()
+            .lock(LockMode::Shared)
+            .copy_on_write(true)
+            .load(path)
+    }
 
-        // SAFETY: exclusive locks work internally when files read from path
-        unsafe { Self::from_file(file) }
+    /// Load a read-only view of an existing path, taking only a shared
+    /// advisory lock so any number of readers can map the same file at
+    /// once, see [`ReadOnlyBuffer`]. Writers are still kept out.
+    pub fn load_shared(path: impl AsRef<Path>) -> Result<ReadOnlyBuffer<T>, MmapBufferError> {
+        ReadOnlyBuffer::load(path)
     }
 
     /// Creates a new buffer at the given path and copies the contents of
     /// the slice to it. The created buffer will be the same size as the slice.
-    pub fn copy_from_slice(slice: &[T], path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
-        let mut buf = Self::new(slice.len(), path)?;
-        buf.copy_from_slice(slice);
+    pub fn copy_from_slice(slice: &[T], path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().create_from_slice(slice, path)
+    }
+
+    /// Creates a new buffer at the given path holding the contents of the
+    /// vector, like [`copy_from_slice`](Self::copy_from_slice).
+    pub fn from_vec(vec: Vec<T>, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Self::copy_from_slice(&vec, path)
+    }
+
+    /// Copy the contents into a new `Vec`, leaving the buffer untouched.
+    pub fn to_vec(&self) -> Vec<T> {
+        self.deref().to_vec()
+    }
+
+    /// Copy the contents into a new `Vec` and close the buffer, releasing
+    /// its lock. The backing file, if any, is left in place.
+    pub fn into_vec(self) -> Vec<T> {
+        self.to_vec()
+    }
+
+    /// Creates a new buffer at the given path holding the contents of a
+    /// reader, read straight into the mapping a chunk at a time. Reads at
+    /// most `capacity` elements; if the reader ends before that, the buffer
+    /// and its file are truncated to what was read. Fails with
+    /// [`io::ErrorKind::UnexpectedEof`] if the reader ends partway through an
+    /// element.
+    pub fn read_from(
+        mut reader: impl Read,
+        capacity: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, MmapBufferError> {
+        let mut buffer = Self::new(capacity, path)?;
+
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut buffer);
+        let mut filled = 0;
+        while filled < bytes.len() {
+            let end = bytes.len().min(filled + STREAM_CHUNK_SIZE);
+            match reader.read(&mut bytes[filled..end]) {
+                Ok(0) => break,
+                Ok(count) => filled += count,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
 
-        Ok(buf)
+        let size = std::mem::size_of::<T>();
+        if filled % size != 0 {
+            return Err(MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "reader ended partway through an element",
+            )));
+        }
+
+        if filled / size < capacity {
+            buffer.resize(filled / size)?;
+        }
+
+        Ok(buffer)
+    }
+
+    /// Write the contents to a writer a chunk at a time.
+    pub fn write_to(&self, mut writer: impl Write) -> Result<(), MmapBufferError> {
+        let bytes: &[u8] = bytemuck::cast_slice(self);
+        for chunk in bytes.chunks(STREAM_CHUNK_SIZE) {
+            writer.write_all(chunk)?;
+        }
+
+        Ok(writer.flush()?)
+    }
+
+    /// Path of the backing file, or `None` for anonymous and temporary
+    /// buffers
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Metadata of the backing file, e.g. its size or modification time.
+    /// Fails for anonymous buffers.
+    pub fn file_metadata(&self) -> Result<std::fs::Metadata, MmapBufferError> {
+        let file = self.file.as_ref().ok_or_else(|| {
+            MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "anonymous buffers have no file to inspect",
+            ))
+        })?;
+
+        Ok(file.metadata()?)
+    }
+
+    /// Whether the buffer holds an advisory lock on its file, see
+    /// [`BackedBufferBuilder::lock`]
+    pub fn is_locked(&self) -> bool {
+        self.file.is_some() && self.lock != LockMode::Unlocked
+    }
+
+    /// Flush and close the buffer, keeping the backing file even if it was
+    /// created with [`delete_on_drop`](BackedBufferBuilder::delete_on_drop),
+    /// and return its path. Fails for buffers without a path.
+    pub fn persist(mut self) -> Result<PathBuf, MmapBufferError> {
+        self.flush()?;
+        self.delete_on_drop = false;
+        self.path.take().ok_or_else(|| {
+            MmapBufferError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "buffer has no backing path",
+            ))
+        })
+    }
+
+    /// Flush and close the buffer, returning the open backing file with
+    /// its lock released. The file is kept even if it was created with
+    /// [`delete_on_drop`](BackedBufferBuilder::delete_on_drop). Fails for
+    /// anonymous buffers.
+    pub fn into_file(mut self) -> Result<File, MmapBufferError> {
+        self.flush()?;
+        self.delete_on_drop = false;
+        let file = self.file.take().ok_or_else(|| {
+            MmapBufferError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "buffer has no backing file",
+            ))
+        })?;
+
+        file.unlock()?;
+        Ok(file)
     }
 
     /// Shrink the `BackedBuffer` so that users cannot access past this new
@@ -157,25 +650,480 @@ impl<T: Pod> BackedBuffer<T> {
             new_len <= self.len(),
             "`new_len` must be less than current length!"
         );
+        self.set_len(new_len);
+    }
+
+    /// Change the logical length of the buffer without remapping it, which
+    /// may be anything up to [`capacity`](Self::capacity). Elements exposed
+    /// by growing the length are zeroed. For buffers with a header, the
+    /// length is stored in the file and restored when it is loaded.
+    pub fn set_len(&mut self, new_len: usize) {
+        assert!(
+            new_len <= self.capacity(),
+            "`new_len` must not exceed the capacity!"
+        );
+
+        if new_len > self.len {
+            let size = std::mem::size_of::<T>();
+            let exposed = self.offset + self.len * size..self.offset + new_len * size;
+            self.dirty.mark(exposed.start, exposed.end);
+            self.mmap[exposed].fill(0);
+        }
+
         self.len = new_len;
+        self.store_len();
     }
 
-    /// SAFETY: cannot `guarantee` advisory locks will work in this case, even
-    /// within the same program (File clone does weird stuff)
-    unsafe fn from_file(file: File) -> Result<Self, Box<dyn Error>> {
-        // Establish advisory lock
-        file.try_lock_exclusive()?;
+    /// Record the logical length in the header, if there is one
+    fn store_len(&mut self) {
+        if self.header {
+            header::Header::store_len(&mut self.mmap, self.len);
+            self.dirty.mark(0, std::mem::size_of::<header::Header>());
+        }
+    }
 
-        // Catch alignment issues ahead of time
-        let mmap = unsafe { MmapOptions::new().populate().map_mut(&file)? };
-        let len = try_cast_slice::<u8, T>(&mmap[..])?.len();
+    /// Set the dirty flag in the header before anything is written, if
+    /// enabled and not set already
+    pub(crate) fn mark_dirty(&mut self) {
+        if self.dirty_flag && !header::Header::load_dirty(&self.mmap) {
+            header::Header::store_dirty(&mut self.mmap, true);
 
-        Ok(Self {
-            mmap,
-            file: Some(file),
-            len,
-            _ph: PhantomData,
-        })
+            // The flag has to reach the file before any of the writes it
+            // covers, there's no way to report failing to do so here
+            self.mmap
+                .flush_range(0, std::mem::size_of::<header::Header>())
+                .unwrap_or(());
+        }
+    }
+
+    /// Grow or truncate the buffer to `new_capacity` elements, resizing the
+    /// backing file and remapping it. New elements are zeroed. Guard pages
+    /// are put up around the new mapping, but other options set through
+    /// [`BackedBufferBuilder`] aren't reapplied to it.
+    ///
+    /// Buffers loaded with [`load_range`](Self::load_range) only remap their
+    /// window, extending the file if needed but never truncating it, so
+    /// growing one exposes whatever data follows it in the file.
+    ///
+    /// Copy-on-write buffers never change the file, so they can't grow past
+    /// its end, and their private changes are copied over to the new
+    /// mapping, which writes every page of it.
+    pub fn resize(&mut self, new_capacity: usize) -> Result<(), MmapBufferError> {
+        let size = std::mem::size_of::<T>();
+        let capacity_bytes = byte_size::<T>(0, new_capacity)?;
+        let kept = usize::min(self.len, new_capacity) * size;
+
+        // Anything past the logical length which becomes visible again, in
+        // case the buffer was previously shrunk, has to be zeroed
+        let visible = usize::min(new_capacity, self.capacity());
+        let exposed = self.offset + kept..self.offset + usize::max(visible * size, kept);
+        self.dirty.mark(exposed.start, exposed.end);
+
+        let (mmap, guards) = match &self.file {
+            Some(file) if self.copy_on_write => {
+                let mapping_bytes = byte_size::<T>(self.offset, new_capacity)?;
+                if self.file_offset + mapping_bytes as u64 > file.metadata()?.len() {
+                    return Err(MmapBufferError::Io(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "copy-on-write buffers can't grow past the end of the file",
+                    )));
+                }
+
+                // SAFETY: we still hold the lock on the file
+                let (mut mmap, guards) = self.map_like(mapping_bytes, || unsafe {
+                    MmapOptions::new()
+                        .offset(self.file_offset)
+                        .len(mapping_bytes)
+                        .map_copy(file)
+                })?;
+                mmap[..self.offset + kept].copy_from_slice(&self.mmap[..self.offset + kept]);
+                mmap[exposed].fill(0);
+                (mmap, guards)
+            }
+            Some(file) => {
+                self.mmap[exposed].fill(0);
+
+                let mapping_bytes = byte_size::<T>(self.offset, new_capacity)?;
+                let end = self.file_offset + mapping_bytes as u64;
+                if !self.window || end > file.metadata()?.len() {
+                    file.set_len(end)?;
+                }
+
+                // SAFETY: we still hold the lock on the file
+                self.map_like(mapping_bytes, || unsafe {
+                    MmapOptions::new()
+                        .offset(self.file_offset)
+                        .len(mapping_bytes)
+                        .map_mut(file)
+                })?
+            }
+            None => {
+                let (mut mmap, guards) = self.map_like(capacity_bytes, || {
+                    MmapOptions::new().len(capacity_bytes).map_anon()
+                })?;
+                mmap[..kept].copy_from_slice(&self.mmap[..kept]);
+                self.huge_pages = false;
+                (mmap, guards)
+            }
+        };
+
+        self.mmap = mmap;
+        #[cfg(unix)]
+        {
+            self.guards = guards;
+        }
+        #[cfg(not(unix))]
+        let _ = guards;
+
+        self.len = new_capacity;
+        self.store_len();
+        Ok(())
+    }
+
+    /// Map the file again if its size changed since it was mapped, returning
+    /// whether it did. This lets a reader follow a file which another
+    /// process grows, `tail -f` style, without reopening it or giving up its
+    /// lock, which must not be exclusive for the writer to get in. The
+    /// length becomes however many whole elements the file holds, or what
+    /// the header records if enabled.
+    ///
+    /// Buffers loaded with [`load_range`](Self::load_range) and anonymous
+    /// buffers are never remapped.
+    pub fn remap(&mut self) -> Result<bool, MmapBufferError> {
+        let Some(file) = &self.file else {
+            return Ok(false);
+        };
+        let file_size = file.metadata()?.len() as usize;
+        if self.window || file_size == self.mmap.len() {
+            return Ok(false);
+        }
+        if file_size < self.offset {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        // SAFETY: the file is locked just as before
+        let (mmap, guards) = self.map_like(file_size, || unsafe {
+            if self.copy_on_write {
+                MmapOptions::new().map_copy(file)
+            } else {
+                MmapOptions::new().map_mut(file)
+            }
+        })?;
+
+        self.mmap = mmap;
+        #[cfg(unix)]
+        {
+            self.guards = guards;
+        }
+        #[cfg(not(unix))]
+        let _ = guards;
+
+        self.len = if self.header {
+            usize::min(header::Header::load_len(&self.mmap), self.capacity())
+        } else {
+            self.capacity()
+        };
+        Ok(true)
+    }
+
+    /// Create a mapping of `len` bytes with `map` to replace the current
+    /// one, with guard pages around it if the current one has them
+    #[cfg(unix)]
+    fn map_like(
+        &self,
+        len: usize,
+        map: impl Fn() -> io::Result<memmap2::MmapMut>,
+    ) -> Result<(memmap2::MmapMut, Option<guard::GuardPages>), MmapBufferError> {
+        if self.guards.is_none() {
+            return Ok((map()?, None));
+        }
+
+        let (mmap, guards) = guard::map_guarded(len, map)?;
+        Ok((mmap, Some(guards)))
+    }
+
+    #[cfg(not(unix))]
+    fn map_like(
+        &self,
+        _len: usize,
+        map: impl Fn() -> io::Result<memmap2::MmapMut>,
+    ) -> Result<(memmap2::MmapMut, ()), MmapBufferError> {
+        Ok((map()?, ()))
+    }
+
+    /// Number of elements which fit in the current mapping, which may be
+    /// more than [`len`](slice::len) if the buffer was shrunk.
+    pub fn capacity(&self) -> usize {
+        (self.mmap.len() - self.offset) / std::mem::size_of::<T>()
+    }
+
+    /// Size of [`capacity`](Self::capacity) elements in bytes, not counting
+    /// any header
+    pub fn capacity_bytes(&self) -> usize {
+        self.capacity() * std::mem::size_of::<T>()
+    }
+
+    /// Split the buffer into chunks of `N` elements, followed by the
+    /// leftover elements which don't fill a whole chunk. Together with
+    /// [`align`](BackedBufferBuilder::align), the chunks start on aligned
+    /// addresses whenever `N` elements span a multiple of the alignment, as
+    /// suits SIMD processing.
+    pub fn as_aligned_chunks<const N: usize>(&self) -> (&[[T; N]], &[T]) {
+        self.as_chunks()
+    }
+
+    /// Mutable version of [`as_aligned_chunks`](Self::as_aligned_chunks)
+    pub fn as_aligned_chunks_mut<const N: usize>(&mut self) -> (&mut [[T; N]], &mut [T]) {
+        self.as_chunks_mut()
+    }
+
+    /// Synchronously write any outstanding changes in the mapping back to
+    /// the file, blocking until they have been written.
+    pub fn flush(&self) -> Result<(), MmapBufferError> {
+        self.update_checksum()?;
+        Ok(self.mmap.flush()?)
+    }
+
+    /// Start writing outstanding changes in the mapping back to the file,
+    /// without waiting for the writes to complete.
+    pub fn flush_async(&self) -> Result<(), MmapBufferError> {
+        self.update_checksum()?;
+        Ok(self.mmap.flush_async()?)
+    }
+
+    /// Synchronously flush a range of the buffer. `offset` and `len` are
+    /// in units of `T`, not in bytes. The checksum sidecar, if any, is
+    /// still updated for the whole buffer.
+    pub fn flush_range(&self, offset: usize, len: usize) -> Result<(), MmapBufferError> {
+        let (offset, len) = self.byte_range(offset..offset + len);
+        self.update_checksum()?;
+        Ok(self.mmap.flush_range(offset, len)?)
+    }
+
+    /// Synchronously flush the buffer like [`flush`](Self::flush), then
+    /// clear the dirty flag, see [`BackedBufferBuilder::dirty_flag`]. Until
+    /// the next write, loading the file again won't report an unclean
+    /// shutdown.
+    pub fn flush_clean(&mut self) -> Result<(), MmapBufferError> {
+        self.mmap.flush()?;
+        self.clear_dirty_flag()
+    }
+
+    /// Clear the dirty flag once the data it covers is on disk, then bring
+    /// the checksum up to date with the final header
+    fn clear_dirty_flag(&mut self) -> Result<(), MmapBufferError> {
+        if self.dirty_flag && header::Header::load_dirty(&self.mmap) {
+            header::Header::store_dirty(&mut self.mmap, false);
+            self.mmap
+                .flush_range(0, std::mem::size_of::<header::Header>())?;
+        }
+
+        self.update_checksum()
+    }
+
+    /// Flush on drop as configured, synchronously if the dirty flag has to
+    /// be cleared afterwards or the file synced
+    fn flush_data_then_header(&mut self) -> Result<(), MmapBufferError> {
+        if self.dirty_flag {
+            return self.flush_clean();
+        }
+
+        match self.flush_on_drop {
+            _ if self.fsync_on_drop => self.mmap.flush()?,
+            FlushOnDrop::None => {}
+            FlushOnDrop::FlushAsync => self.mmap.flush_async()?,
+            FlushOnDrop::FlushSync => self.mmap.flush()?,
+        }
+        self.update_checksum()
+    }
+
+    /// Whether the dirty flag was set when the buffer was loaded, meaning
+    /// the previous session didn't shut down cleanly and the contents may
+    /// need rebuilding. Always `false` without
+    /// [`BackedBufferBuilder::dirty_flag`].
+    pub fn unclean_shutdown(&self) -> bool {
+        self.unclean_shutdown
+    }
+
+    /// Whether the mapping is backed by explicit huge pages from the
+    /// kernel's hugetlb pool. Only anonymous buffers built with
+    /// [`BackedBufferBuilder::huge_pages`] get them, and only while the
+    /// pool has room. There's no telling whether the kernel acted on the
+    /// transparent huge page hint given to other mappings.
+    pub fn uses_huge_pages(&self) -> bool {
+        self.huge_pages
+    }
+
+    /// Flush the mapping and then `fsync` the underlying file, so that
+    /// both the contents and file metadata are durable.
+    pub fn sync_all(&self) -> Result<(), MmapBufferError> {
+        self.flush()?;
+        self.sync_files()
+    }
+
+    /// `fsync` the underlying file and checksum sidecar, without flushing
+    /// the mapping first
+    fn sync_files(&self) -> Result<(), MmapBufferError> {
+        if let Some(file) = &self.file {
+            file.sync_all()?;
+        }
+
+        if let Some(sidecar) = &self.checksum {
+            sidecar.sync_all()?;
+        }
+
+        Ok(())
+    }
+
+    /// Hint to the kernel that the buffer will be accessed sequentially,
+    /// so it can read ahead aggressively.
+    #[cfg(unix)]
+    pub fn advise_sequential(&self) -> Result<(), MmapBufferError> {
+        Ok(self.mmap.advise(Advice::Sequential)?)
+    }
+
+    /// Hint to the kernel that the buffer will be accessed in random
+    /// order, so read-ahead is of little use.
+    #[cfg(unix)]
+    pub fn advise_random(&self) -> Result<(), MmapBufferError> {
+        Ok(self.mmap.advise(Advice::Random)?)
+    }
+
+    /// Hint to the kernel that a range of the buffer will be accessed soon.
+    /// The range is in units of `T`, not in bytes.
+    #[cfg(unix)]
+    pub fn advise_willneed(&self, range: Range<usize>) -> Result<(), MmapBufferError> {
+        let (offset, len) = self.byte_range(range);
+        Ok(self.mmap.advise_range(Advice::WillNeed, offset, len)?)
+    }
+
+    /// Start reading a range of the buffer into memory in the background,
+    /// so later accesses don't block on disk IO. The range is in units of
+    /// `T`, not in bytes. A no-op on platforms without `madvise`.
+    ///
+    /// Mappings are populated lazily unless
+    /// [`populate`](BackedBufferBuilder::populate) is enabled, so this can
+    /// warm just the parts of a large file that will actually be used.
+    pub fn prefetch(&self, range: Range<usize>) -> Result<(), MmapBufferError> {
+        let (offset, len) = self.byte_range(range);
+
+        #[cfg(unix)]
+        self.mmap.advise_range(Advice::WillNeed, offset, len)?;
+
+        #[cfg(not(unix))]
+        let _ = (offset, len);
+
+        Ok(())
+    }
+
+    /// Start reading the whole buffer into memory in the background, see
+    /// [`prefetch`](Self::prefetch).
+    pub fn prefetch_all(&self) -> Result<(), MmapBufferError> {
+        self.prefetch(0..self.len())
+    }
+
+    /// Hint to the kernel that a range of the buffer won't be accessed
+    /// soon, so its pages can be reclaimed. The range is in units of `T`,
+    /// not in bytes.
+    ///
+    /// For file-backed buffers, the contents are reloaded from the file on
+    /// the next access. For anonymous and copy-on-write buffers, the
+    /// contents of the range are **discarded** and read back as zeroes.
+    #[cfg(unix)]
+    pub fn advise_dontneed(&mut self, range: Range<usize>) -> Result<(), MmapBufferError> {
+        let (offset, len) = self.byte_range(range);
+        Ok(self.mmap.advise_range(Advice::DontNeed, offset, len)?)
+    }
+
+    /// Let the kernel reclaim the memory behind a range of the buffer
+    /// without unmapping it, e.g. for cold regions of a long-lived buffer.
+    /// The range is flushed first, so its pages are clean and can be
+    /// dropped from the page cache right away, and is transparently read
+    /// back from the file on the next access. The range is in units of
+    /// `T`, not in bytes.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] for anonymous and
+    /// copy-on-write buffers, whose contents only live in memory. Use
+    /// [`advise_dontneed`](Self::advise_dontneed) to discard those instead.
+    #[cfg(unix)]
+    pub fn release_memory(&mut self, range: Range<usize>) -> Result<(), MmapBufferError> {
+        if self.file.is_none() || self.copy_on_write {
+            return Err(MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "releasing memory would discard the contents of the buffer",
+            )));
+        }
+
+        let (offset, len) = self.byte_range(range);
+        self.mmap.flush_range(offset, len)?;
+        Ok(self.mmap.advise_range(Advice::DontNeed, offset, len)?)
+    }
+
+    /// Pin the pages of the buffer in RAM (`mlock`), so accesses never
+    /// page fault on disk IO. This is subject to the process's locked
+    /// memory limit.
+    #[cfg(unix)]
+    pub fn lock_in_memory(&mut self) -> Result<(), MmapBufferError> {
+        Ok(self.mmap.lock()?)
+    }
+
+    /// Release pages previously pinned with [`lock_in_memory`](Self::lock_in_memory)
+    #[cfg(unix)]
+    pub fn unlock_memory(&mut self) -> Result<(), MmapBufferError> {
+        Ok(self.mmap.unlock()?)
+    }
+
+    /// Convert a range of elements into a byte offset and length
+    fn byte_range(&self, range: Range<usize>) -> (usize, usize) {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "range must be within the buffer!"
+        );
+        let size = std::mem::size_of::<T>();
+        (
+            self.offset + range.start * size,
+            (range.end - range.start) * size,
+        )
+    }
+
+    /// Reinterpret the buffer as holding `len` elements of type `U`, moving
+    /// every field over as is. Checking that the mapping and any header
+    /// suit `U` is up to the caller.
+    pub(crate) fn with_element_type<U: Pod>(self, len: usize) -> BackedBuffer<U> {
+        // Skip `Drop`, which would release the lock
+        let this = std::mem::ManuallyDrop::new(self);
+
+        // SAFETY: `this` is never used or dropped again, so every field is
+        // moved out exactly once
+        unsafe {
+            BackedBuffer {
+                mmap: std::ptr::read(&this.mmap),
+                #[cfg(unix)]
+                guards: std::ptr::read(&this.guards),
+                offset: this.offset,
+                file_offset: this.file_offset,
+                window: this.window,
+                header: this.header,
+                copy_on_write: this.copy_on_write,
+                huge_pages: this.huge_pages,
+                len,
+                file: std::ptr::read(&this.file),
+                path: std::ptr::read(&this.path),
+                delete_on_drop: this.delete_on_drop,
+                flush_on_drop: this.flush_on_drop,
+                fsync_on_drop: this.fsync_on_drop,
+                zeroize_on_drop: this.zeroize_on_drop,
+                dirty_flag: this.dirty_flag,
+                unclean_shutdown: this.unclean_shutdown,
+                lock: this.lock,
+                on_corruption: this.on_corruption,
+                checksum: std::ptr::read(&this.checksum),
+                lock_file: std::ptr::read(&this.lock_file),
+                pid_file: std::ptr::read(&this.pid_file),
+                dirty: std::ptr::read(&this.dirty),
+                _ph: PhantomData,
+            }
+        }
     }
 }
 
@@ -191,21 +1139,36 @@ impl<T: Pod> AsMut<[T]> for BackedBuffer<T> {
     }
 }
 
+/// Size in bytes of `header` bytes followed by `count` elements of `T`,
+/// or [`MmapBufferError::CapacityOverflow`] if that doesn't fit in a `usize`
+pub(crate) fn byte_size<T>(header: usize, count: usize) -> Result<usize, MmapBufferError> {
+    count
+        .checked_mul(std::mem::size_of::<T>())
+        .and_then(|bytes| bytes.checked_add(header))
+        .ok_or(MmapBufferError::CapacityOverflow)
+}
+
 impl<T: Pod> Deref for BackedBuffer<T> {
     type Target = [T];
 
     #[inline]
     fn deref(&self) -> &Self::Target {
         // SAFETY: should predictably panic if file corrupted
-        &try_cast_slice(&self.mmap[..]).unwrap()[..self.len]
+        try_cast_slice(&self.mmap[self.offset..self.offset + self.len * std::mem::size_of::<T>()])
+            .unwrap()
     }
 }
 
 impl<T: Pod> DerefMut for BackedBuffer<T> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
+        self.mark_dirty();
+
         // SAFETY: should predictably panic if file corrupted
-        &mut try_cast_slice_mut(&mut self.mmap[..]).unwrap()[..self.len]
+        try_cast_slice_mut(
+            &mut self.mmap[self.offset..self.offset + self.len * std::mem::size_of::<T>()],
+        )
+        .unwrap()
     }
 }
 
@@ -215,7 +1178,7 @@ impl<T: Pod> Deref for Buffer<T> {
     #[inline]
     fn deref(&self) -> &Self::Target {
         match self {
-            Self::Disk(backed_buffer) => backed_buffer.deref(),
+            Self::Disk(backed_buffer) | Self::Anonymous(backed_buffer) => backed_buffer.deref(),
             Self::Memory(vector) => vector.deref(),
         }
     }
@@ -225,16 +1188,29 @@ impl<T: Pod> DerefMut for Buffer<T> {
     #[inline]
     fn deref_mut(&mut self) -> &mut Self::Target {
         match self {
-            Self::Disk(backed_buffer) => backed_buffer.deref_mut(),
+            Self::Disk(backed_buffer) | Self::Anonymous(backed_buffer) => backed_buffer.deref_mut(),
             Self::Memory(vector) => vector.deref_mut(),
         }
     }
 }
 
+/// Copies the buffer like [`Buffer::try_clone`] with [`DiskClone::Anonymous`]
+///
+/// # Panics
+///
+/// If the anonymous mapping for a copy can't be created, much like `Vec`
+/// aborts when it runs out of memory.
+impl<T: Pod> Clone for Buffer<T> {
+    fn clone(&self) -> Self {
+        self.try_clone(DiskClone::Anonymous)
+            .expect("failed to map memory for the clone!")
+    }
+}
+
 impl<T: Pod> AsRef<[T]> for Buffer<T> {
     fn as_ref(&self) -> &[T] {
         match self {
-            Self::Disk(data) => data.deref(),
+            Self::Disk(data) | Self::Anonymous(data) => data.deref(),
             Self::Memory(data) => data.deref(),
         }
     }
@@ -243,32 +1219,89 @@ impl<T: Pod> AsRef<[T]> for Buffer<T> {
 impl<T: Pod> AsMut<[T]> for Buffer<T> {
     fn as_mut(&mut self) -> &mut [T] {
         match self {
-            Self::Disk(data) => data.deref_mut(),
+            Self::Disk(data) | Self::Anonymous(data) => data.deref_mut(),
             Self::Memory(data) => data.deref_mut(),
         }
     }
 }
 
+// Threads are supported through the fields, without any unsafe impls, see
+// the `BackedBuffer` docs. Catch a field which would take that away.
+const _: () = {
+    const fn assert_send_sync<X: Send + Sync>() {}
+    assert_send_sync::<BackedBuffer<u8>>();
+    assert_send_sync::<ReadOnlyBuffer<u8>>();
+    assert_send_sync::<Buffer<u8>>();
+    assert_send_sync::<ArcBuffer<u8>>();
+};
+
 impl<T: Pod> Drop for BackedBuffer<T> {
     fn drop(&mut self) {
+        // Wipe first, so the checksum covers the zeroes
+        self.zeroize_on_drop().unwrap_or(());
+
+        // Flush the data once, then the header and checksum describing it.
+        // Nothing sensible to do with an error here, a stale checksum or a
+        // dirty flag left set will be caught on the next load.
+        self.flush_data_then_header().unwrap_or(());
+
+        if self.fsync_on_drop {
+            self.sync_files().unwrap_or(());
+        }
+
         if let Some(file) = self.file.take() {
             // Ignore the error, advisory locks are still kind of sus
             file.unlock().unwrap_or(());
         }
+
+        if let (true, Some(path)) = (self.delete_on_drop, &self.path) {
+            // Ignore the errors, there's no way to report them
+            std::fs::remove_file(path).unwrap_or(());
+            if self.checksum.is_some() {
+                std::fs::remove_file(checksum::sidecar_path(path)).unwrap_or(());
+            }
+            if self.lock_file.is_some() {
+                std::fs::remove_file(builder::lock_file_path(path)).unwrap_or(());
+            }
+        }
     }
 }
 
-#[cfg(test)]
+/// Describes the buffer without its contents, which may be large or
+/// sensitive
 impl<T: Pod> std::fmt::Debug for BackedBuffer<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("{:?} of length {}", self.file, self.len()))
+        f.debug_struct("BackedBuffer")
+            .field("path", &self.path)
+            .field("element_type", &std::any::type_name::<T>())
+            .field("len", &self.len)
+            .field("capacity", &self.capacity())
+            .field("lock", &self.lock)
+            .field("copy_on_write", &self.copy_on_write)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Summarizes the buffer in one line, e.g. `8 x u32 at data.bin`, leaving
+/// out the contents
+impl<T: Pod> std::fmt::Display for BackedBuffer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} x {}", self.len, std::any::type_name::<T>())?;
+        match (&self.path, &self.file) {
+            (Some(path), _) => write!(f, " at {}", path.display()),
+            (None, Some(_)) => f.write_str(" in a temporary file"),
+            (None, None) => f.write_str(" in anonymous memory"),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::BackedBuffer;
-    use std::{error::Error, fs::File, io::Write, path::Path};
+    use super::{
+        BackedBuffer, BackedBufferBuilder, Buffer, DiskClone, FlushOnDrop, LockMode,
+        MmapBufferError, OnCorruption, SizePolicy,
+    };
+    use std::{error::Error, fs::File, io::Write, path::Path, time::Duration};
 
     #[test]
     fn read() -> Result<(), Box<dyn Error>> {
@@ -276,7 +1309,7 @@ mod tests {
         let file_path = Path::join(tempdir.path(), "test");
         File::create(file_path.clone())
             .unwrap()
-            .write("hello, world!".as_bytes())?;
+            .write_all("hello, world!".as_bytes())?;
 
         let mmap = BackedBuffer::<u8>::load(file_path).expect("");
         assert_eq!(&mmap[..], "hello, world!".as_bytes());
@@ -290,7 +1323,7 @@ mod tests {
         let file_path = Path::join(tempdir.path(), "test");
         File::create(file_path.clone())
             .unwrap()
-            .write("hello, world!".as_bytes())?;
+            .write_all("hello, world!".as_bytes())?;
 
         let mut mmap = BackedBuffer::<u8>::load(file_path).expect("");
         mmap.copy_from_slice("halle, werld!".as_bytes());
@@ -315,4 +1348,626 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn error_variants() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        File::create(file_path.clone())?.write_all(&[0; 7])?;
+
+        let err = BackedBuffer::<u32>::load(file_path.clone()).expect_err("");
+        assert!(matches!(
+            err,
+            MmapBufferError::SizeMismatch {
+                file_size: 7,
+                element_size: 4
+            }
+        ));
+
+        let _mmap = BackedBuffer::<u8>::load(file_path.clone()).expect("");
+        let err = BackedBuffer::<u8>::load(file_path.clone()).expect_err("");
+        assert!(matches!(err, MmapBufferError::Locked));
+
+        Ok(())
+    }
+
+    #[test]
+    fn flush() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u8>::new(4, file_path.clone())?;
+        buf.copy_from_slice(&[1, 2, 3, 4]);
+        buf.flush_range(1, 2)?;
+        buf.flush_async()?;
+        buf.sync_all()?;
+
+        assert_eq!(std::fs::read(file_path)?, [1, 2, 3, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn copy_on_write() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        File::create(file_path.clone())?.write_all("hello, world!".as_bytes())?;
+
+        let mut cow_1 = BackedBuffer::<u8>::load_cow(file_path.clone())?;
+        let cow_2 = BackedBuffer::<u8>::load_cow(file_path.clone())?;
+        cow_1.copy_from_slice("halle, werld!".as_bytes());
+
+        assert_eq!(&cow_1[..], "halle, werld!".as_bytes());
+        assert_eq!(&cow_2[..], "hello, world!".as_bytes());
+        assert_eq!(std::fs::read(file_path)?, "hello, world!".as_bytes());
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_shared() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        drop(BackedBuffer::copy_from_slice(
+            &[1u32, 2, 3],
+            file_path.clone(),
+        )?);
+
+        let reader_1 = BackedBuffer::<u32>::load_shared(file_path.clone())?;
+        let reader_2 = BackedBuffer::<u32>::load_shared(file_path.clone())?;
+        assert_eq!(&reader_1[..], &[1, 2, 3]);
+        assert_eq!(&reader_1[..], &reader_2[..]);
+        assert!(BackedBuffer::<u32>::load(file_path).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn anonymous() -> Result<(), Box<dyn Error>> {
+        let mut buf = BackedBuffer::<u64>::anonymous(1024)?;
+        assert_eq!(buf.len(), 1024);
+        assert!(buf.iter().all(|&x| x == 0));
+
+        buf[1000] = 42;
+        assert_eq!(buf[1000], 42);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn advise() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u32>::new(4096, file_path)?;
+        buf.fill(7);
+        buf.advise_sequential()?;
+        buf.advise_random()?;
+        buf.advise_willneed(100..2000)?;
+        buf.advise_dontneed(0..4096)?;
+
+        // Shared file mappings keep their contents
+        assert!(buf.iter().all(|&x| x == 7));
+
+        Ok(())
+    }
+
+    #[test]
+    fn prefetch() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        BackedBuffer::<u64>::new(1 << 16, file_path.clone())?.fill(3);
+
+        let buf = BackedBuffer::<u64>::load(file_path)?;
+        buf.prefetch(1000..2000)?;
+        buf.prefetch_all()?;
+        assert!(buf.iter().all(|&x| x == 3));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn release_memory() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u32>::new(1 << 16, file_path)?;
+        buf.fill(9);
+        buf.release_memory(0..1 << 15)?;
+        assert!(buf.iter().all(|&x| x == 9));
+
+        let mut buf = BackedBuffer::<u32>::anonymous(16)?;
+        assert!(buf.release_memory(0..16).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn memory_locking() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBufferBuilder::new()
+            .lock_in_memory(true)
+            .create::<u8>(4096, file_path)?;
+        buf.unlock_memory()?;
+        buf.lock_in_memory()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn huge_pages() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBufferBuilder::new()
+            .huge_pages(true)
+            .anonymous::<u64>(1000)?;
+        assert_eq!(buf.len(), 1000);
+        buf[999] = 1;
+
+        // Explicit huge pages only come whole, and only if the pool has any
+        // to spare
+        if buf.uses_huge_pages() {
+            assert!(buf.capacity() > 1000);
+        } else {
+            assert_eq!(buf.capacity(), 1000);
+        }
+
+        // Files only get a hint
+        let buf = BackedBufferBuilder::new()
+            .huge_pages(true)
+            .create::<u64>(1000, file_path)?;
+        assert!(!buf.uses_huge_pages());
+
+        Ok(())
+    }
+
+    #[test]
+    fn resize() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u32>::copy_from_slice(&[1, 2, 3, 4], file_path.clone())?;
+        buf.shrink(2);
+        buf.resize(6)?;
+        assert_eq!(&buf[..], &[1, 2, 0, 0, 0, 0]);
+
+        buf.resize(1)?;
+        drop(buf);
+        assert_eq!(std::fs::metadata(&file_path)?.len(), 4);
+
+        let mut buf = BackedBuffer::<u32>::anonymous(2)?;
+        buf[1] = 5;
+        buf.resize(1024)?;
+        assert_eq!(&buf[..3], &[0, 5, 0]);
+
+        // Copy-on-write buffers keep their private changes, and leave the
+        // file alone
+        BackedBuffer::<u32>::copy_from_slice(&[1, 2, 3, 4], file_path.clone())?;
+        let mut buf = BackedBuffer::<u32>::load_cow(&file_path)?;
+        buf[0] = 9;
+        buf.shrink(1);
+        buf.resize(3)?;
+        assert_eq!(&buf[..], &[9, 0, 0]);
+        assert!(buf.resize(5).is_err());
+        drop(buf);
+        assert_eq!(&BackedBuffer::<u32>::load(&file_path)?[..], &[1, 2, 3, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn remap() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.lock(LockMode::Shared).header(true);
+        let mut writer = options.create::<u32>(4, file_path.clone())?;
+        let mut reader = options.load::<u32>(file_path)?;
+        assert!(!reader.remap()?);
+
+        writer.resize(8)?;
+        writer[7] = 7;
+        assert!(reader.remap()?);
+        assert_eq!(reader.len(), 8);
+        assert_eq!(reader[7], 7);
+
+        writer.resize(2)?;
+        assert!(reader.remap()?);
+        assert_eq!(reader.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn blocking_lock() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        let buf = BackedBuffer::<u8>::new(4, file_path.clone())?;
+
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            drop(buf);
+        });
+
+        BackedBuffer::<u8>::load_blocking(file_path, Some(Duration::from_secs(10)))?;
+        handle.join().unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn zero_length() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let buf = BackedBuffer::<u32>::new(0, file_path.clone())?;
+        assert!(buf.is_empty());
+        buf.sync_all()?;
+        drop(buf);
+
+        let mut buf = BackedBuffer::<u32>::load(file_path.clone())?;
+        assert_eq!(buf.capacity(), 0);
+        buf.resize(2)?;
+        assert_eq!(&buf[..], &[0, 0]);
+        drop(buf);
+
+        let buf = BackedBuffer::<u32>::copy_from_slice(&[], file_path)?;
+        assert_eq!(buf.checksum(), 0);
+        assert!(BackedBuffer::<u32>::anonymous(0)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_new() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u8>::create_new(4, file_path.clone())?;
+        buf[0] = 1;
+        drop(buf);
+
+        match BackedBuffer::<u8>::create_new(4, file_path.clone()) {
+            Err(MmapBufferError::Io(err)) => {
+                assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists)
+            }
+            _ => panic!("existing file must not be overwritten"),
+        }
+        assert_eq!(BackedBuffer::<u8>::load(file_path)?[0], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn size_policy() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        File::create(file_path.clone())?.write_all(&[1, 0, 2, 0, 3])?;
+
+        let err = BackedBuffer::<u16>::load(file_path.clone()).unwrap_err();
+        assert!(matches!(err, MmapBufferError::SizeMismatch { .. }));
+
+        let buf = BackedBufferBuilder::new()
+            .size_policy(SizePolicy::Prefix)
+            .load::<u16>(file_path.clone())?;
+        assert_eq!(&buf[..], &[1, 2]);
+        drop(buf);
+        assert_eq!(std::fs::metadata(&file_path)?.len(), 5);
+
+        BackedBufferBuilder::new()
+            .size_policy(SizePolicy::Truncate)
+            .load::<u16>(file_path.clone())?;
+        assert_eq!(std::fs::metadata(&file_path)?.len(), 4);
+
+        BackedBuffer::<u16>::load_with_len(file_path.clone(), 2)?;
+        let err = BackedBuffer::<u16>::load_with_len(file_path, 3).unwrap_err();
+        assert!(matches!(
+            err,
+            MmapBufferError::LengthMismatch {
+                expected: 3,
+                actual: 2
+            }
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn on_corruption() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        File::create(file_path.clone())?.write_all(&[1, 0, 2, 0, 3])?;
+
+        let buf = BackedBufferBuilder::new()
+            .on_corruption(OnCorruption::ZeroFill)
+            .load::<u16>(file_path.clone())?;
+        assert_eq!(&buf[..], &[1, 2, 3]);
+        drop(buf);
+
+        let mut options = BackedBufferBuilder::new();
+        options.checksum(true);
+        let mut buf = options.create::<u16>(3, file_path.clone())?;
+        buf.copy_from_slice(&[1, 2, 3]);
+        drop(buf);
+        File::options()
+            .write(true)
+            .open(&file_path)?
+            .write_all(&[4])?;
+
+        let err = options.load::<u16>(file_path.clone()).unwrap_err();
+        assert!(matches!(err, MmapBufferError::ChecksumMismatch));
+
+        // Without page checksums, nothing is known to be intact
+        let buf = options
+            .on_corruption(OnCorruption::ZeroFill)
+            .load::<u16>(file_path.clone())?;
+        assert_eq!(&buf[..], &[0, 0, 0]);
+        drop(buf);
+
+        // The repair is covered by the checksum again
+        options.on_corruption(OnCorruption::Error);
+        let mut buf = options.load::<u16>(file_path.clone())?;
+        buf.copy_from_slice(&[1, 2, 3]);
+        drop(buf);
+        File::options()
+            .write(true)
+            .open(&file_path)?
+            .write_all(&[4])?;
+
+        let buf = options
+            .on_corruption(OnCorruption::TruncateToValidPrefix)
+            .load::<u16>(file_path.clone())?;
+        assert!(buf.is_empty());
+        drop(buf);
+        assert_eq!(std::fs::metadata(&file_path)?.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn capacity_overflow() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let err = BackedBuffer::<u64>::new(usize::MAX / 4, file_path).unwrap_err();
+        assert!(matches!(err, MmapBufferError::CapacityOverflow));
+
+        let err = BackedBuffer::<u64>::anonymous(usize::MAX / 4).unwrap_err();
+        assert!(matches!(err, MmapBufferError::CapacityOverflow));
+    }
+
+    #[test]
+    fn load_range() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let data: Vec<u64> = (0..2000).collect();
+        drop(BackedBuffer::copy_from_slice(&data, file_path.clone())?);
+
+        let mut buf = BackedBuffer::<u64>::load_range(file_path.clone(), 1000, 10)?;
+        assert_eq!(&buf[..], &data[1000..1010]);
+        buf[0] = 42;
+        drop(buf);
+        assert_eq!(BackedBuffer::<u64>::load(file_path.clone())?[1000], 42);
+
+        assert!(BackedBuffer::<u64>::load_range(file_path, 1995, 10).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn spill_buffer() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = Buffer::from_vec_in_memory(vec![1u32, 2, 3]);
+        buf.spill_to_disk(file_path.clone())?;
+        assert!(matches!(buf, Buffer::Disk(_)));
+        buf[0] = 4;
+
+        buf.load_into_memory();
+        assert!(matches!(buf, Buffer::Memory(_)));
+        assert_eq!(&buf[..], &[4, 2, 3]);
+        assert_eq!(&BackedBuffer::<u32>::load(file_path)?[..], &[4, 2, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn vec_conversions() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let buf = BackedBuffer::from_vec(vec![1u16, 2, 3], file_path.clone())?;
+        assert_eq!(buf.to_vec(), [1, 2, 3]);
+        assert_eq!(buf.into_vec(), [1, 2, 3]);
+
+        // The lock was released
+        BackedBuffer::<u16>::load(file_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn streaming() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let data: Vec<u8> = (0..=255).cycle().take(3 << 20).collect();
+        let buf = BackedBuffer::<u32>::read_from(&data[..], 1 << 19, file_path.clone())?;
+        assert_eq!(buf.len(), 1 << 19);
+        drop(buf);
+
+        let buf = BackedBuffer::<u32>::read_from(&data[..12], 8, file_path.clone())?;
+        assert_eq!(buf.len(), 3);
+        drop(buf);
+        assert_eq!(std::fs::metadata(&file_path)?.len(), 12);
+
+        assert!(BackedBuffer::<u32>::read_from(&data[..6], 8, file_path.clone()).is_err());
+
+        let buf = BackedBuffer::<u8>::read_from(&data[..], data.len(), file_path)?;
+        let mut out = Vec::new();
+        buf.write_to(&mut out)?;
+        assert_eq!(out, data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn temp_buffer() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+
+        let mut buf = BackedBuffer::<u64>::temp_in(1024, tempdir.path())?;
+        buf[1023] = 7;
+        buf.resize(2048)?;
+        assert_eq!(buf[1023], 7);
+        assert_eq!(std::fs::read_dir(tempdir.path())?.count(), 0);
+        drop(buf);
+
+        assert_eq!(BackedBuffer::<u8>::temp(16)?.len(), 16);
+
+        Ok(())
+    }
+
+    #[test]
+    fn delete_on_drop() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.delete_on_drop(true);
+
+        drop(options.create::<u8>(16, file_path.clone())?);
+        assert!(!file_path.exists());
+
+        let mut buf = options.create::<u8>(16, file_path.clone())?;
+        buf[0] = 1;
+        assert_eq!(buf.persist()?, file_path);
+        assert_eq!(BackedBuffer::<u8>::load(file_path.clone())?[0], 1);
+
+        let mut file = options.load::<u8>(file_path.clone())?.into_file()?;
+        assert!(file_path.exists());
+        let mut contents = Vec::new();
+        std::io::Read::read_to_end(&mut file, &mut contents)?;
+        assert_eq!(contents[0], 1);
+
+        assert!(BackedBuffer::<u8>::anonymous(1)?.persist().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn flush_on_drop() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options
+            .flush_on_drop(FlushOnDrop::FlushSync)
+            .fsync_on_drop(true);
+
+        let mut buf = options.create::<u32>(8, file_path.clone())?;
+        buf[7] = 7;
+        drop(buf);
+
+        assert_eq!(std::fs::read(file_path)?[28..], 7u32.to_ne_bytes());
+
+        Ok(())
+    }
+
+    #[test]
+    fn metadata() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBufferBuilder::new()
+            .header(true)
+            .create::<u32>(8, file_path.clone())?;
+        buf.shrink(2);
+        assert_eq!(buf.path(), Some(file_path.as_path()));
+        assert_eq!((buf.len(), buf.capacity_bytes()), (2, 32));
+        assert!(buf.file_metadata()?.len() > 32);
+        assert!(buf.is_locked());
+        drop(buf);
+
+        let buf = BackedBufferBuilder::new()
+            .lock(LockMode::Unlocked)
+            .load::<u8>(file_path)?;
+        assert!(!buf.is_locked());
+
+        let anon = BackedBuffer::<u8>::anonymous(1)?;
+        assert!(!anon.is_locked());
+        assert!(anon.file_metadata().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn debug_and_display() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u32>::new(8, file_path.clone())?;
+        buf.fill(0xdeadbeef);
+        assert_eq!(
+            buf.to_string(),
+            format!("8 x u32 at {}", file_path.display())
+        );
+
+        let debug = format!("{buf:?}");
+        assert!(debug.contains("len: 8") && debug.contains("Exclusive"));
+        assert!(!debug.contains(&0xdeadbeefu32.to_string()));
+
+        let anon = BackedBuffer::<u8>::anonymous(2)?;
+        assert_eq!(anon.to_string(), "2 x u8 in anonymous memory");
+
+        Ok(())
+    }
+
+    #[test]
+    fn scoped_split() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u32>::new(1000, file_path.clone())?;
+        let (left, right) = buf.split_at_mut(400);
+        std::thread::scope(|scope| {
+            scope.spawn(|| left.fill(1));
+            scope.spawn(|| right.fill(2));
+        });
+        drop(buf);
+
+        let buf = BackedBuffer::<u32>::load(file_path)?;
+        assert!(buf[..400].iter().all(|&x| x == 1));
+        assert!(buf[400..].iter().all(|&x| x == 2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn clone_buffer() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let disk = Buffer::from_slice_on_disk(&[1u16, 2, 3], file_path)?;
+        let mut clone = disk.clone();
+        assert!(matches!(clone, Buffer::Anonymous(_)));
+        clone[0] = 10;
+        assert_eq!((disk[0], clone[0]), (1, 10));
+
+        assert!(disk.try_clone(DiskClone::Refuse).is_err());
+        assert!(clone.try_clone(DiskClone::Refuse)? == [10, 2, 3]);
+        assert!(Buffer::from_vec_in_memory(vec![4u8]).clone() == [4]);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,136 @@
+use std::{ops::Deref, path::Path};
+
+use bytemuck::Pod;
+
+use crate::{BackedVec, MmapBufferError};
+
+/// Number of elements the file grows by when full, unless configured
+/// otherwise
+const DEFAULT_CHUNK_SIZE: usize = 1024;
+
+/// An append-only log of `T` backed by a file, for event logs, metrics and
+/// the like.
+///
+/// Entries can be appended and read back, but never modified or removed.
+/// Like [`BackedVec`], the length is stored in a header in the file, so
+/// appended entries survive reloading. The file grows in fixed chunks
+/// rather than geometrically, which keeps its size predictable.
+pub struct BackedLog<T: Pod> {
+    entries: BackedVec<T>,
+    chunk_size: usize,
+}
+
+impl<T: Pod> BackedLog<T> {
+    /// Create a new, empty log at the given path. Any existing file is
+    /// truncated.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE, path)
+    }
+
+    /// Create a new, empty log at the given path which grows the file by
+    /// `chunk_size` elements at a time.
+    pub fn with_chunk_size(
+        chunk_size: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, MmapBufferError> {
+        assert!(chunk_size > 0, "`chunk_size` must be positive!");
+
+        Ok(Self {
+            entries: BackedVec::with_capacity(chunk_size, path)?,
+            chunk_size,
+        })
+    }
+
+    /// Load a log from an existing path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Self::load_with_chunk_size(DEFAULT_CHUNK_SIZE, path)
+    }
+
+    /// Load a log from an existing path, which grows the file by
+    /// `chunk_size` elements at a time from now on.
+    pub fn load_with_chunk_size(
+        chunk_size: usize,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, MmapBufferError> {
+        assert!(chunk_size > 0, "`chunk_size` must be positive!");
+
+        Ok(Self {
+            entries: BackedVec::load(path)?,
+            chunk_size,
+        })
+    }
+
+    /// Append an entry, returning its index in the log.
+    pub fn append(&mut self, value: &T) -> Result<u64, MmapBufferError> {
+        let index = self.entries.len();
+        if index == self.entries.capacity() {
+            self.entries.reserve_exact(self.chunk_size)?;
+        }
+
+        self.entries.push(*value)?;
+        Ok(index as u64)
+    }
+
+    /// Iterate over all entries, oldest first
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.entries.iter()
+    }
+
+    /// Synchronously write appended entries and the length back to the
+    /// file, so they survive a crash.
+    pub fn flush(&self) -> Result<(), MmapBufferError> {
+        self.entries.flush()
+    }
+}
+
+impl<T: Pod> Deref for BackedLog<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.entries.deref()
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for BackedLog<T> {
+    fn as_ref(&self) -> &[T] {
+        self.deref()
+    }
+}
+
+impl<'a, T: Pod> IntoIterator for &'a BackedLog<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BackedLog;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn append_reload() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        {
+            let mut log = BackedLog::<u32>::with_chunk_size(4, file_path.clone())?;
+            for i in 0..10 {
+                assert_eq!(log.append(&(i * i))?, i as u64);
+            }
+
+            // Grown in chunks of 4
+            assert_eq!(std::fs::metadata(&file_path)?.len(), 8 + 12 * 4);
+        }
+
+        let log = BackedLog::<u32>::load(file_path)?;
+        assert_eq!(log.len(), 10);
+        assert!(log.iter().copied().eq((0..10).map(|i| i * i)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::ops::{Index, IndexMut};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+impl<T: Pod> BackedBuffer<T> {
+    /// View the buffer as a row-major matrix with the given dimensions,
+    /// failing with [`MmapBufferError::LengthMismatch`] unless
+    /// `rows * cols` is exactly the length of the buffer.
+    ///
+    /// ```
+    /// use mmap_buffer::BackedBuffer;
+    ///
+    /// let mut buf = BackedBuffer::<u32>::anonymous(6).unwrap();
+    /// buf.as_matrix_mut(2, 3).unwrap()[(1, 2)] = 5;
+    ///
+    /// let matrix = buf.as_matrix(2, 3).unwrap();
+    /// assert_eq!(matrix.row(1), &[0, 0, 5]);
+    /// assert!(matrix.column(2).eq(&[0, 5]));
+    /// ```
+    pub fn as_matrix(&self, rows: usize, cols: usize) -> Result<Matrix<'_, T>, MmapBufferError> {
+        check_dimensions(rows, cols, self.len())?;
+        Ok(Matrix {
+            data: self,
+            rows,
+            cols,
+        })
+    }
+
+    /// Mutably view the buffer as a row-major matrix with the given
+    /// dimensions, failing with [`MmapBufferError::LengthMismatch`] unless
+    /// `rows * cols` is exactly the length of the buffer.
+    pub fn as_matrix_mut(
+        &mut self,
+        rows: usize,
+        cols: usize,
+    ) -> Result<MatrixMut<'_, T>, MmapBufferError> {
+        check_dimensions(rows, cols, self.len())?;
+        Ok(MatrixMut {
+            data: self,
+            rows,
+            cols,
+        })
+    }
+}
+
+fn check_dimensions(rows: usize, cols: usize, len: usize) -> Result<(), MmapBufferError> {
+    let expected = rows
+        .checked_mul(cols)
+        .ok_or(MmapBufferError::CapacityOverflow)?;
+    if expected != len {
+        return Err(MmapBufferError::LengthMismatch {
+            expected,
+            actual: len,
+        });
+    }
+
+    Ok(())
+}
+
+/// A row-major matrix view of a [`BackedBuffer`], see
+/// [`BackedBuffer::as_matrix`]
+#[derive(Clone, Copy)]
+pub struct Matrix<'a, T> {
+    data: &'a [T],
+    rows: usize,
+    cols: usize,
+}
+
+impl<'a, T> Matrix<'a, T> {
+    /// Number of rows
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Number of columns
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Element at row `r` and column `c`, or `None` if out of bounds
+    pub fn get(&self, r: usize, c: usize) -> Option<&'a T> {
+        (r < self.rows && c < self.cols).then(|| &self.data[r * self.cols + c])
+    }
+
+    /// Row `r` as a contiguous slice. Panics if out of bounds.
+    pub fn row(&self, r: usize) -> &'a [T] {
+        assert!(r < self.rows, "row index out of bounds!");
+        &self.data[r * self.cols..(r + 1) * self.cols]
+    }
+
+    /// Iterate over all rows, in order
+    pub fn iter_rows(&self) -> impl Iterator<Item = &'a [T]> + '_ {
+        (0..self.rows).map(|r| self.row(r))
+    }
+
+    /// Iterate over column `c`, top to bottom. Panics if out of bounds.
+    pub fn column(&self, c: usize) -> impl Iterator<Item = &'a T> {
+        assert!(c < self.cols, "column index out of bounds!");
+        self.data[c..].iter().step_by(self.cols)
+    }
+
+    /// The underlying row-major slice
+    pub fn as_slice(&self) -> &'a [T] {
+        self.data
+    }
+}
+
+impl<T> Index<(usize, usize)> for Matrix<'_, T> {
+    type Output = T;
+
+    fn index(&self, (r, c): (usize, usize)) -> &T {
+        self.get(r, c).expect("matrix index out of bounds!")
+    }
+}
+
+/// A mutable row-major matrix view of a [`BackedBuffer`], see
+/// [`BackedBuffer::as_matrix_mut`]
+pub struct MatrixMut<'a, T> {
+    data: &'a mut [T],
+    rows: usize,
+    cols: usize,
+}
+
+impl<T> MatrixMut<'_, T> {
+    /// Number of rows
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Number of columns
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Reborrow as an immutable view
+    pub fn as_matrix(&self) -> Matrix<'_, T> {
+        Matrix {
+            data: self.data,
+            rows: self.rows,
+            cols: self.cols,
+        }
+    }
+
+    /// Element at row `r` and column `c`, or `None` if out of bounds
+    pub fn get(&self, r: usize, c: usize) -> Option<&T> {
+        (r < self.rows && c < self.cols).then(|| &self.data[r * self.cols + c])
+    }
+
+    /// Mutable element at row `r` and column `c`, or `None` if out of bounds
+    pub fn get_mut(&mut self, r: usize, c: usize) -> Option<&mut T> {
+        (r < self.rows && c < self.cols).then(|| &mut self.data[r * self.cols + c])
+    }
+
+    /// Row `r` as a contiguous slice. Panics if out of bounds.
+    pub fn row(&self, r: usize) -> &[T] {
+        self.as_matrix().row(r)
+    }
+
+    /// Row `r` as a contiguous mutable slice. Panics if out of bounds.
+    pub fn row_mut(&mut self, r: usize) -> &mut [T] {
+        assert!(r < self.rows, "row index out of bounds!");
+        &mut self.data[r * self.cols..(r + 1) * self.cols]
+    }
+
+    /// Iterate mutably over all rows, in order
+    pub fn iter_rows_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        // `chunks_exact_mut` panics on zero-sized chunks, and there are no
+        // elements to hand out anyway
+        let rows = if self.cols == 0 { 0 } else { self.rows };
+        self.data.chunks_exact_mut(self.cols.max(1)).take(rows)
+    }
+
+    /// Iterate mutably over column `c`, top to bottom. Panics if out of
+    /// bounds.
+    pub fn column_mut(&mut self, c: usize) -> impl Iterator<Item = &mut T> {
+        assert!(c < self.cols, "column index out of bounds!");
+        self.data[c..].iter_mut().step_by(self.cols)
+    }
+
+    /// The underlying row-major slice
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        self.data
+    }
+}
+
+impl<T> Index<(usize, usize)> for MatrixMut<'_, T> {
+    type Output = T;
+
+    fn index(&self, (r, c): (usize, usize)) -> &T {
+        self.get(r, c).expect("matrix index out of bounds!")
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for MatrixMut<'_, T> {
+    fn index_mut(&mut self, (r, c): (usize, usize)) -> &mut T {
+        self.get_mut(r, c).expect("matrix index out of bounds!")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, MmapBufferError};
+    use std::error::Error;
+
+    #[test]
+    fn matrix_view() -> Result<(), Box<dyn Error>> {
+        let mut buf = BackedBuffer::<u32>::anonymous(12)?;
+
+        let err = buf.as_matrix(5, 2).err().unwrap();
+        assert!(matches!(
+            err,
+            MmapBufferError::LengthMismatch {
+                expected: 10,
+                actual: 12
+            }
+        ));
+        assert!(matches!(
+            buf.as_matrix(usize::MAX, 2),
+            Err(MmapBufferError::CapacityOverflow)
+        ));
+
+        let mut matrix = buf.as_matrix_mut(3, 4)?;
+        for (r, row) in matrix.iter_rows_mut().enumerate() {
+            row.fill(r as u32 * 10);
+        }
+        matrix.column_mut(1).for_each(|x| *x += 1);
+        matrix[(2, 3)] = 99;
+        assert!(matrix.get_mut(3, 0).is_none());
+
+        let matrix = buf.as_matrix(3, 4)?;
+        assert_eq!(matrix.row(1), &[10, 11, 10, 10]);
+        assert!(matrix.column(1).eq(&[1, 11, 21]));
+        assert_eq!(matrix.iter_rows().count(), 3);
+        assert_eq!(matrix[(2, 3)], 99);
+        assert_eq!(matrix.get(0, 4), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+};
+
+use bytemuck::Pod;
+use memmap2::MmapOptions;
+
+use crate::{
+    byte_size, dirty::DirtyPages, BackedBuffer, FlushOnDrop, LockMode, MmapBufferError,
+    OnCorruption, ReadOnlyBuffer, ZeroizeOnDrop,
+};
+
+/// Seals guaranteeing that the contents of a sealed buffer never change
+const SEALS: libc::c_int = libc::F_SEAL_WRITE | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW;
+
+/// A fixed size, mutable buffer of `T` in an anonymous memory file
+/// (`memfd_create`), which can be [sealed](Self::seal) once filled in to
+/// share it immutably with other processes.
+pub struct MemfdBuffer<T: Pod> {
+    buffer: BackedBuffer<T>,
+}
+
+/// An immutable buffer of `T` in a sealed memory file, see
+/// [`MemfdBuffer::seal`].
+///
+/// The seals are enforced by the kernel, so a process receiving the file
+/// descriptor, e.g. over a Unix socket, can rely on the contents never
+/// changing underneath it, or the memory file being truncated, no matter
+/// how little it trusts the sender.
+pub struct SealedBuffer<T: Pod> {
+    buffer: ReadOnlyBuffer<T>,
+}
+
+impl<T: Pod> MemfdBuffer<T> {
+    /// Create a new memory file with a fixed capacity in units of `T`. The
+    /// name is only used for debugging, e.g. in `/proc/self/fd`.
+    pub fn new(name: &str, capacity: usize) -> Result<Self, MmapBufferError> {
+        let capacity_bytes = byte_size::<T>(0, capacity)?;
+        let name = CString::new(name)
+            .map_err(|err| MmapBufferError::Io(io::Error::new(io::ErrorKind::InvalidInput, err)))?;
+
+        // SAFETY: `name` is a valid null-terminated string
+        let fd = unsafe {
+            libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        // SAFETY: `fd` is a freshly opened descriptor which nothing else owns
+        let file = File::from(unsafe { OwnedFd::from_raw_fd(fd) });
+        file.set_len(capacity_bytes as u64)?;
+
+        // SAFETY: nobody else has access to the memory file yet
+        let mmap = unsafe { MmapOptions::new().len(capacity_bytes).map_mut(&file)? };
+
+        Ok(Self {
+            buffer: BackedBuffer {
+                mmap,
+                guards: None,
+                offset: 0,
+                file_offset: 0,
+                window: false,
+                header: false,
+                copy_on_write: false,
+                huge_pages: false,
+                len: capacity,
+                file: Some(file),
+                path: None,
+                delete_on_drop: false,
+                flush_on_drop: FlushOnDrop::None,
+                fsync_on_drop: false,
+                zeroize_on_drop: ZeroizeOnDrop::None,
+                dirty_flag: false,
+                unclean_shutdown: false,
+                lock: LockMode::Unlocked,
+                on_corruption: OnCorruption::Error,
+                checksum: None,
+                lock_file: None,
+                pid_file: None,
+                dirty: DirtyPages::default(),
+                _ph: PhantomData,
+            },
+        })
+    }
+
+    /// Seal the memory file against writes, shrinking and growing, and map
+    /// it again read-only. Fails with `EBUSY` while any other writable
+    /// mapping of the file exists, e.g. in a process it was shared with.
+    pub fn seal(self) -> Result<SealedBuffer<T>, MmapBufferError> {
+        let mut buffer = self.buffer;
+
+        // Writable mappings prevent sealing, so unmap ours first
+        let file = buffer.file.take().expect("memory files are always open");
+        drop(buffer);
+
+        // SAFETY: `file` is a valid descriptor
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, SEALS) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        SealedBuffer::from_file(file)
+    }
+}
+
+impl<T: Pod> SealedBuffer<T> {
+    /// Map a sealed memory file received from another process. Fails
+    /// unless the file is sealed against writes, shrinking and growing.
+    pub fn from_fd(fd: OwnedFd) -> Result<Self, MmapBufferError> {
+        // SAFETY: `fd` is a valid descriptor
+        let seals = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GET_SEALS) };
+        if seals < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        if seals & SEALS != SEALS {
+            return Err(MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "memory file is not sealed",
+            )));
+        }
+
+        Self::from_file(File::from(fd))
+    }
+
+    fn from_file(file: File) -> Result<Self, MmapBufferError> {
+        // SAFETY: the seals keep the contents from changing
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        let len = bytemuck::try_cast_slice::<u8, T>(&mmap[..])
+            .map_err(|err| {
+                MmapBufferError::from_cast_error(err, mmap.len(), std::mem::size_of::<T>())
+            })?
+            .len();
+
+        Ok(Self {
+            buffer: ReadOnlyBuffer {
+                mmap,
+                offset: 0,
+                len,
+                file: Some(file),
+                lock_file: None,
+                _ph: PhantomData,
+            },
+        })
+    }
+}
+
+impl<T: Pod> AsFd for SealedBuffer<T> {
+    /// The sealed memory file, to pass to other processes
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.buffer
+            .file
+            .as_ref()
+            .expect("memory files are always open")
+            .as_fd()
+    }
+}
+
+impl<T: Pod> Deref for MemfdBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.buffer.deref()
+    }
+}
+
+impl<T: Pod> DerefMut for MemfdBuffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.buffer.deref_mut()
+    }
+}
+
+impl<T: Pod> Deref for SealedBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.buffer.deref()
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for SealedBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self.deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemfdBuffer, SealedBuffer};
+    use std::{error::Error, fs::File, io::Write, os::fd::AsFd};
+
+    #[test]
+    fn seal() -> Result<(), Box<dyn Error>> {
+        let mut buf = MemfdBuffer::<u32>::new("test", 1024)?;
+        buf[7] = 7;
+        let sealed = buf.seal()?;
+        assert_eq!(sealed.len(), 1024);
+        assert_eq!(sealed[7], 7);
+
+        // The receiving side sees the same contents, and can't change them
+        let fd = sealed.as_fd().try_clone_to_owned()?;
+        let mut file = File::from(fd.try_clone()?);
+        assert!(file.write_all(&[1]).is_err());
+        assert!(file.set_len(0).is_err());
+        assert_eq!(SealedBuffer::<u32>::from_fd(fd)?[7], 7);
+
+        // Unsealed memory files are rejected
+        let unsealed = tempfile::tempfile()?;
+        assert!(SealedBuffer::<u32>::from_fd(unsealed.into()).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError, ReadOnlyBuffer};
+
+/// Identifies Merkle tree files
+const MAGIC: &[u8; 8] = b"mmmerkle";
+
+/// Round constants of SHA-256
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// A SHA-256 hash
+type Hash = [u8; 32];
+
+/// SHA-256 hash of the concatenation of `parts`
+fn sha256(parts: &[&[u8]]) -> Hash {
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    let len: usize = parts.iter().map(|part| part.len()).sum();
+    let mut padding = vec![0x80];
+    padding.resize((len + 9).next_multiple_of(64) - len - 8, 0);
+    padding.extend((len as u64 * 8).to_be_bytes());
+
+    let mut block = [0; 64];
+    let mut filled = 0;
+    for &byte in parts.iter().copied().chain([&padding[..]]).flatten() {
+        block[filled] = byte;
+        filled += 1;
+        if filled == 64 {
+            compress(&mut state, &block);
+            filled = 0;
+        }
+    }
+
+    let mut hash = [0; 32];
+    for (bytes, word) in hash.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    hash
+}
+
+/// Mix one 64 byte block into the SHA-256 state
+fn compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    let mut w = [0u32; 64];
+    for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes(bytes.try_into().unwrap());
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/// Hash of a block of data, domain separated from inner nodes
+fn hash_leaf(block: &[u8]) -> Hash {
+    sha256(&[&[0], block])
+}
+
+/// Hash of an inner node
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    sha256(&[&[1], left, right])
+}
+
+/// Path of the sidecar file holding the Merkle tree of the file at `path`
+pub(crate) fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = OsString::from(path.as_os_str());
+    sidecar.push(".merkle");
+    sidecar.into()
+}
+
+/// A Merkle tree of SHA-256 hashes over fixed-size blocks of a buffer, for
+/// checking any part of a large file against a single trusted
+/// [`root`](Self::root) hash without reading all of it.
+///
+/// Only the hashes of the blocks are stored, from which the root is
+/// recomputed when the tree is loaded. Once the root matches the one
+/// published alongside the data, [`BackedBuffer::verify_range`] and
+/// [`ReadOnlyBuffer::verify_range`] only hash the blocks they check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree {
+    block_size: usize,
+    len: usize,
+    leaves: Vec<Hash>,
+    root: Hash,
+}
+
+impl MerkleTree {
+    /// Hash `bytes` in blocks of `block_size` bytes, the last of which may
+    /// be shorter.
+    ///
+    /// # Panics
+    ///
+    /// If `block_size` is zero.
+    pub fn new(bytes: &[u8], block_size: usize) -> Self {
+        assert!(block_size > 0, "block size must be positive!");
+        let leaves: Vec<_> = bytes.chunks(block_size).map(hash_leaf).collect();
+        Self::from_leaves(block_size, bytes.len(), leaves)
+    }
+
+    fn from_leaves(block_size: usize, len: usize, leaves: Vec<Hash>) -> Self {
+        // Unpaired nodes move up a level unchanged
+        let mut level = leaves.clone();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [node] => *node,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+        let root = level.first().copied().unwrap_or_else(|| hash_leaf(&[]));
+
+        Self {
+            block_size,
+            len,
+            leaves,
+            root,
+        }
+    }
+
+    /// The hash identifying the whole contents
+    pub fn root(&self) -> [u8; 32] {
+        self.root
+    }
+
+    /// Size of the hashed blocks in bytes
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Number of bytes covered by the tree
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the tree covers no bytes at all
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Write the tree to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MmapBufferError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&(self.block_size as u64).to_le_bytes())?;
+        file.write_all(&(self.len as u64).to_le_bytes())?;
+        for leaf in &self.leaves {
+            file.write_all(leaf)?;
+        }
+        file.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+
+        Ok(())
+    }
+
+    /// Read a tree written by [`save`](Self::save), failing with
+    /// [`MmapBufferError::InvalidHeader`] if the file is malformed. Compare
+    /// its [`root`](Self::root) against a trusted one before relying on it.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        let (magic, rest) = bytes
+            .split_first_chunk::<8>()
+            .ok_or(MmapBufferError::InvalidHeader)?;
+        let (block_size, rest) = rest
+            .split_first_chunk::<8>()
+            .ok_or(MmapBufferError::InvalidHeader)?;
+        let (len, rest) = rest
+            .split_first_chunk::<8>()
+            .ok_or(MmapBufferError::InvalidHeader)?;
+        if magic != MAGIC {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        let block_size = u64::from_le_bytes(*block_size) as usize;
+        let len = u64::from_le_bytes(*len) as usize;
+        if block_size == 0 || rest.len() != len.div_ceil(block_size) * 32 {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        let leaves = rest
+            .chunks_exact(32)
+            .map(|leaf| leaf.try_into().unwrap())
+            .collect();
+        Ok(Self::from_leaves(block_size, len, leaves))
+    }
+
+    /// Read the tree stored next to the file at `path` by
+    /// [`BackedBuffer::write_merkle_tree`], failing with
+    /// [`MmapBufferError::ChecksumMismatch`] unless its root is `root`
+    pub fn load_for(path: impl AsRef<Path>, root: &[u8; 32]) -> Result<Self, MmapBufferError> {
+        let tree = Self::load(sidecar_path(path.as_ref()))?;
+        if tree.root() != *root {
+            return Err(MmapBufferError::ChecksumMismatch);
+        }
+
+        Ok(tree)
+    }
+
+    /// Check the blocks overlapping `range` of `bytes` against the tree,
+    /// failing with [`MmapBufferError::ChecksumMismatch`] if any of them,
+    /// or the length of `bytes`, differs
+    fn verify(&self, bytes: &[u8], range: Range<usize>) -> Result<(), MmapBufferError> {
+        if bytes.len() != self.len {
+            return Err(MmapBufferError::ChecksumMismatch);
+        }
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let blocks = range.start / self.block_size..range.end.div_ceil(self.block_size);
+        for index in blocks {
+            let start = index * self.block_size;
+            let block = &bytes[start..(start + self.block_size).min(bytes.len())];
+            if hash_leaf(block) != self.leaves[index] {
+                return Err(MmapBufferError::ChecksumMismatch);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Build a Merkle tree over the elements, in blocks of `block_size`
+    /// bytes
+    pub fn merkle_tree(&self, block_size: usize) -> MerkleTree {
+        MerkleTree::new(bytemuck::cast_slice(self), block_size)
+    }
+
+    /// Build a Merkle tree over the elements and store it in a
+    /// `<path>.merkle` sidecar file, to ship together with the file, see
+    /// [`MerkleTree::load_for`]. Returns the tree, whose
+    /// [`root`](MerkleTree::root) consumers should check it against. Fails
+    /// for anonymous buffers.
+    pub fn write_merkle_tree(&self, block_size: usize) -> Result<MerkleTree, MmapBufferError> {
+        let path = self.path.as_ref().ok_or_else(|| {
+            MmapBufferError::Io(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "anonymous buffers have no file to embed a Merkle tree with",
+            ))
+        })?;
+
+        self.flush()?;
+        let tree = self.merkle_tree(block_size);
+        tree.save(sidecar_path(path))?;
+        Ok(tree)
+    }
+
+    /// Check the given range of elements against a Merkle tree, hashing
+    /// only the blocks it overlaps. Fails with
+    /// [`MmapBufferError::ChecksumMismatch`] if they differ, or if the tree
+    /// covers a different number of elements.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds.
+    pub fn verify_range(
+        &self,
+        tree: &MerkleTree,
+        range: Range<usize>,
+    ) -> Result<(), MmapBufferError> {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "range must be within the buffer!"
+        );
+
+        let size = std::mem::size_of::<T>();
+        tree.verify(
+            bytemuck::cast_slice(self),
+            range.start * size..range.end * size,
+        )
+    }
+}
+
+impl<T: Pod> ReadOnlyBuffer<T> {
+    /// Check the given range of elements against a Merkle tree, see
+    /// [`BackedBuffer::verify_range`]
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds.
+    pub fn verify_range(
+        &self,
+        tree: &MerkleTree,
+        range: Range<usize>,
+    ) -> Result<(), MmapBufferError> {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "range must be within the buffer!"
+        );
+
+        let size = std::mem::size_of::<T>();
+        tree.verify(
+            bytemuck::cast_slice(self),
+            range.start * size..range.end * size,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sha256;
+    use crate::{BackedBuffer, MerkleTree, MmapBufferError, ReadOnlyBuffer};
+    use std::{
+        error::Error,
+        fs::OpenOptions,
+        io::{Seek, SeekFrom, Write},
+        path::Path,
+    };
+
+    #[test]
+    fn known_sha256() {
+        let hex = |hash: [u8; 32]| hash.map(|byte| format!("{byte:02x}")).concat();
+        assert_eq!(
+            hex(sha256(&[b""])),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(sha256(&[b"ab", b"c"])),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn verify_range() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u32>::new(1000, file_path.clone())?;
+        for (i, value) in buf.iter_mut().enumerate() {
+            *value = i as u32;
+        }
+        let root = buf.write_merkle_tree(256)?.root();
+        drop(buf);
+
+        // Damage element 500, which lies in the eighth block
+        let mut file = OpenOptions::new().write(true).open(&file_path)?;
+        file.seek(SeekFrom::Start(4 * 500))?;
+        file.write_all(&[0xff])?;
+
+        let buf = ReadOnlyBuffer::<u32>::load(&file_path)?;
+        let tree = MerkleTree::load_for(&file_path, &root)?;
+        assert_eq!(
+            tree,
+            MerkleTree::load(format!("{}.merkle", file_path.display()))?
+        );
+        buf.verify_range(&tree, 0..448)?;
+        buf.verify_range(&tree, 512..1000)?;
+        let err = buf.verify_range(&tree, 499..501).unwrap_err();
+        assert!(matches!(err, MmapBufferError::ChecksumMismatch));
+
+        let err = MerkleTree::load_for(&file_path, &[0; 32]).unwrap_err();
+        assert!(matches!(err, MmapBufferError::ChecksumMismatch));
+
+        Ok(())
+    }
+}
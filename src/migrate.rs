@@ -0,0 +1,87 @@
+use std::{ffi::OsString, path::Path};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, BackedBufferBuilder, MmapBufferError};
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Convert the buffer file at `path` from elements of type `Old` to
+    /// elements of type `T`, e.g. after changing the layout of a record
+    /// struct. Each element is passed through `f` in order and written to
+    /// a new file next to the old one, which then atomically replaces it,
+    /// so readers see either the old file or the new one but never a mix.
+    ///
+    /// The old file stays locked exclusively while it is converted, and is
+    /// left untouched if converting it fails or `f` panics. Returns the
+    /// buffer holding the converted elements, with the same length.
+    pub fn migrate_from<Old: Pod>(
+        path: impl AsRef<Path>,
+        mut f: impl FnMut(Old) -> T,
+    ) -> Result<Self, MmapBufferError> {
+        let path = path.as_ref();
+        let old = BackedBuffer::<Old>::load(path)?;
+        old.advise_sequential().unwrap_or(());
+
+        // Deleted on drop, unless it makes it into place
+        let mut new = BackedBufferBuilder::new()
+            .delete_on_drop(true)
+            .create::<T>(old.len(), migrate_path(path))?;
+        for (new, &old) in new.iter_mut().zip(old.iter()) {
+            *new = f(old);
+        }
+        new.sync_all()?;
+
+        std::fs::rename(migrate_path(path), path)?;
+        new.path = Some(path.to_owned());
+        new.delete_on_drop = false;
+        Ok(new)
+    }
+}
+
+/// Path next to `path` to write the migrated file to
+fn migrate_path(path: &Path) -> OsString {
+    let mut new = OsString::from(path.as_os_str());
+    new.push(format!(".migrate-{}", std::process::id()));
+    new
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, MmapBufferError};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn migrate() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut old = BackedBuffer::<[u32; 2]>::new(3, file_path.clone())?;
+        old.copy_from_slice(&[[1, 2], [3, 4], [5, 6]]);
+        assert!(matches!(
+            BackedBuffer::<u64>::migrate_from::<[u32; 2]>(&file_path, |[a, b]| (a + b).into())
+                .unwrap_err(),
+            MmapBufferError::Locked
+        ));
+        drop(old);
+
+        let new = BackedBuffer::<u64>::migrate_from(&file_path, |[a, b]: [u32; 2]| {
+            ((a as u64) << 32) | b as u64
+        })?;
+        assert_eq!(new.path(), Some(file_path.as_path()));
+        drop(new);
+        assert_eq!(
+            &BackedBuffer::<u64>::load(&file_path)?[..],
+            &[1 << 32 | 2, 3 << 32 | 4, 5 << 32 | 6]
+        );
+
+        // A conversion which panics leaves the old file alone and cleans up
+        let result = std::panic::catch_unwind(|| {
+            BackedBuffer::<u32>::migrate_from(&file_path, |x: u64| u32::try_from(x).unwrap())
+        });
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_dir(tempdir.path())?.count(), 1);
+        assert_eq!(BackedBuffer::<u64>::load(&file_path)?[0], 1 << 32 | 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, BackedBufferBuilder, MmapBufferError};
+
+/// Identifies `.npy` files
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// NumPy pads the header so the data starts on a multiple of this
+const ALIGN: usize = 64;
+
+/// Element types with a NumPy dtype, for reading and writing `.npy` files
+pub trait NpyElement: Pod {
+    /// Kind character of the dtype, e.g. `'f'` for floats
+    const KIND: char;
+}
+
+macro_rules! npy_element {
+    ($kind:literal: $($ty:ty),*) => {
+        $(impl NpyElement for $ty {
+            const KIND: char = $kind;
+        })*
+    };
+}
+
+npy_element!('u': u8, u16, u32, u64);
+npy_element!('i': i8, i16, i32, i64);
+npy_element!('f': f32, f64);
+
+/// Shape and memory order of an array stored in a `.npy` file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NpyShape {
+    /// Length of each dimension, empty for a scalar
+    pub dims: Vec<usize>,
+    /// Whether the data is stored in column-major rather than row-major
+    /// order
+    pub fortran_order: bool,
+}
+
+impl<T: NpyElement> BackedBuffer<T> {
+    /// Load the array in a NumPy `.npy` file, mapping its data section
+    /// directly. Fails with [`MmapBufferError::TypeMismatch`] unless the
+    /// dtype is `T` in native byte order, and with
+    /// [`MmapBufferError::InvalidHeader`] if the file isn't a valid `.npy`
+    /// file. The buffer holds every element, and its shape is returned
+    /// alongside for interpreting them.
+    ///
+    /// ```
+    /// use mmap_buffer::BackedBuffer;
+    ///
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// # let path = dir.path().join("data.npy");
+    /// let buf = BackedBuffer::<f32>::anonymous(6).unwrap();
+    /// buf.save_npy_with_shape(&[2, 3], &path).unwrap();
+    ///
+    /// let (buf, shape) = BackedBuffer::<f32>::load_npy(&path).unwrap();
+    /// assert_eq!(shape.dims, [2, 3]);
+    /// assert_eq!(buf.as_matrix(2, 3).unwrap().row(1), &[0.0; 3]);
+    /// ```
+    pub fn load_npy(path: impl AsRef<Path>) -> Result<(Self, NpyShape), MmapBufferError> {
+        let path = path.as_ref();
+        let builder = BackedBufferBuilder::new();
+        let (mut file, lock_file) = builder.open_locked(path)?;
+
+        let (start, shape) = read_header::<T>(&mut file)?;
+        let len = shape
+            .dims
+            .iter()
+            .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+            .ok_or(MmapBufferError::CapacityOverflow)?;
+
+        let mut buffer = builder.map_range(file, path, start, len)?;
+        buffer.lock_file = lock_file;
+        Ok((buffer, shape))
+    }
+
+    /// Write the contents to a NumPy `.npy` file as a one-dimensional array.
+    pub fn save_npy(&self, path: impl AsRef<Path>) -> Result<(), MmapBufferError> {
+        self.save_npy_with_shape(&[self.len()], path)
+    }
+
+    /// Write the contents to a NumPy `.npy` file as a row-major array with
+    /// the given shape, failing with [`MmapBufferError::LengthMismatch`]
+    /// unless it has exactly as many elements as the buffer.
+    pub fn save_npy_with_shape(
+        &self,
+        dims: &[usize],
+        path: impl AsRef<Path>,
+    ) -> Result<(), MmapBufferError> {
+        let expected = dims
+            .iter()
+            .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+            .ok_or(MmapBufferError::CapacityOverflow)?;
+        if expected != self.len() {
+            return Err(MmapBufferError::LengthMismatch {
+                expected,
+                actual: self.len(),
+            });
+        }
+
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&header::<T>(dims))?;
+        file.write_all(bytemuck::cast_slice(self))?;
+        file.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+
+        Ok(())
+    }
+}
+
+/// Descriptor of `T` in native byte order, e.g. `<f4`
+fn descr<T: NpyElement>() -> String {
+    let order = if std::mem::size_of::<T>() == 1 {
+        '|'
+    } else if cfg!(target_endian = "little") {
+        '<'
+    } else {
+        '>'
+    };
+
+    format!("{order}{}{}", T::KIND, std::mem::size_of::<T>())
+}
+
+/// Version 1.0 header for a row-major array of `T`, or version 2.0 if the
+/// dictionary is too long
+fn header<T: NpyElement>(dims: &[usize]) -> Vec<u8> {
+    let shape = match dims {
+        [dim] => format!("({dim},)"),
+        _ => format!(
+            "({})",
+            dims.iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut dict = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {shape}, }}",
+        descr::<T>()
+    );
+
+    // Pad with spaces and a final newline, so the data starts aligned
+    let prefix = if dict.len() + 11 <= u16::MAX as usize {
+        10
+    } else {
+        12
+    };
+    let padded = (prefix + dict.len() + 1).next_multiple_of(ALIGN) - prefix;
+    dict.extend(std::iter::repeat_n(' ', padded - dict.len() - 1));
+    dict.push('\n');
+
+    let mut bytes = MAGIC.to_vec();
+    if prefix == 10 {
+        bytes.extend([1, 0]);
+        bytes.extend((padded as u16).to_le_bytes());
+    } else {
+        bytes.extend([2, 0]);
+        bytes.extend((padded as u32).to_le_bytes());
+    }
+    bytes.extend(dict.into_bytes());
+    bytes
+}
+
+/// Parse the header at the start of a `.npy` file, returning the byte offset
+/// of the data and its shape
+fn read_header<T: NpyElement>(file: &mut File) -> Result<(usize, NpyShape), MmapBufferError> {
+    let mut preamble = [0; 8];
+    file.read_exact(&mut preamble)
+        .map_err(|_| MmapBufferError::InvalidHeader)?;
+    if preamble[..6] != *MAGIC {
+        return Err(MmapBufferError::InvalidHeader);
+    }
+
+    let (prefix, dict_len) = match preamble[6] {
+        1 => {
+            let mut len = [0; 2];
+            file.read_exact(&mut len)
+                .map_err(|_| MmapBufferError::InvalidHeader)?;
+            (10, u16::from_le_bytes(len) as usize)
+        }
+        2 | 3 => {
+            let mut len = [0; 4];
+            file.read_exact(&mut len)
+                .map_err(|_| MmapBufferError::InvalidHeader)?;
+            (12, u32::from_le_bytes(len) as usize)
+        }
+        _ => return Err(MmapBufferError::InvalidHeader),
+    };
+
+    // Check the length against the file before trusting it for an allocation
+    if (prefix + dict_len) as u64 > file.metadata()?.len() {
+        return Err(MmapBufferError::InvalidHeader);
+    }
+
+    let mut dict = vec![0; dict_len];
+    file.read_exact(&mut dict)
+        .map_err(|_| MmapBufferError::InvalidHeader)?;
+    let dict = std::str::from_utf8(&dict).map_err(|_| MmapBufferError::InvalidHeader)?;
+
+    let dtype = field(dict, "descr")
+        .and_then(|value| value.strip_prefix('\''))
+        .and_then(|value| value.split('\'').next())
+        .ok_or(MmapBufferError::InvalidHeader)?;
+    let fortran_order = match field(dict, "fortran_order") {
+        Some(value) if value.starts_with("True") => true,
+        Some(value) if value.starts_with("False") => false,
+        _ => return Err(MmapBufferError::InvalidHeader),
+    };
+    let dims = field(dict, "shape")
+        .and_then(|value| value.strip_prefix('('))
+        .and_then(|value| value.split(')').next())
+        .ok_or(MmapBufferError::InvalidHeader)?
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| dim.parse().map_err(|_| MmapBufferError::InvalidHeader))
+        .collect::<Result<_, _>>()?;
+
+    // Single bytes have no byte order, so any marker will do
+    let expected = descr::<T>();
+    let matches = if std::mem::size_of::<T>() == 1 {
+        dtype.get(1..) == expected.get(1..) && dtype.starts_with(['|', '<', '>'])
+    } else {
+        dtype == expected
+    };
+    if !matches {
+        return Err(MmapBufferError::TypeMismatch);
+    }
+
+    let start = prefix + dict_len;
+    if !start.is_multiple_of(std::mem::align_of::<T>()) {
+        return Err(MmapBufferError::Alignment);
+    }
+
+    Ok((
+        start,
+        NpyShape {
+            dims,
+            fortran_order,
+        },
+    ))
+}
+
+/// The value of `key` in a header dictionary, followed by the rest of it
+fn field<'a>(dict: &'a str, key: &str) -> Option<&'a str> {
+    let start = dict.find(&format!("'{key}'"))? + key.len() + 2;
+    Some(dict[start..].trim_start().strip_prefix(':')?.trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, MmapBufferError};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn npy_round_trip() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test.npy");
+
+        let mut buf = BackedBuffer::<i32>::anonymous(6)?;
+        buf.copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        buf.save_npy_with_shape(&[3, 2], &file_path)?;
+        assert!(buf.save_npy_with_shape(&[4, 2], &file_path).is_err());
+
+        let bytes = std::fs::read(&file_path)?;
+        assert_eq!(bytes.len(), 128 + 24);
+        assert!(bytes.starts_with(b"\x93NUMPY\x01\x00"));
+        assert!(std::str::from_utf8(&bytes[10..128])?.contains("'shape': (3, 2)"));
+
+        {
+            let (mut buf, shape) = BackedBuffer::<i32>::load_npy(&file_path)?;
+            assert_eq!(shape.dims, [3, 2]);
+            assert!(!shape.fortran_order);
+            assert_eq!(&buf[..], &[1, 2, 3, 4, 5, 6]);
+            buf[5] = 60;
+        }
+
+        let err = BackedBuffer::<f32>::load_npy(&file_path).err().unwrap();
+        assert!(matches!(err, MmapBufferError::TypeMismatch));
+
+        let (buf, _) = BackedBuffer::<i32>::load_npy(&file_path)?;
+        assert_eq!(buf[5], 60);
+        drop(buf);
+
+        // NumPy's own output for `np.save(path, np.arange(3, dtype='u1'))`
+        let mut numpy = b"\x93NUMPY\x01\x00\x76\x00".to_vec();
+        let dict = "{'descr': '|u1', 'fortran_order': False, 'shape': (3,), }";
+        numpy.extend(format!("{dict:<117}\n").as_bytes());
+        numpy.extend([0, 1, 2]);
+        std::fs::write(&file_path, numpy)?;
+
+        let (buf, shape) = BackedBuffer::<u8>::load_npy(&file_path)?;
+        assert_eq!(shape.dims, [3]);
+        assert_eq!(&buf[..], &[0, 1, 2]);
+        drop(buf);
+
+        // A header length past the end of the file is rejected up front
+        std::fs::write(&file_path, b"\x93NUMPY\x02\x00\xff\xff\xff\xff")?;
+        let err = BackedBuffer::<u8>::load_npy(&file_path).err().unwrap();
+        assert!(matches!(err, MmapBufferError::InvalidHeader));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use bytemuck::Pod;
+
+use crate::BackedBuffer;
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Copy a slice over the start of the buffer like
+    /// [`copy_from_slice`](slice::copy_from_slice), but with non-temporal
+    /// stores which bypass the CPU caches where supported. This keeps large
+    /// one-shot writes, like ingesting a file, from evicting everything
+    /// else from the cache. Falls back to a regular copy on other targets.
+    ///
+    /// Panics if the slice is longer than the buffer.
+    pub fn copy_from_slice_nt(&mut self, src: &[T]) {
+        assert!(
+            src.len() <= self.len(),
+            "source slice is longer than the buffer!"
+        );
+
+        let dst: &mut [u8] = bytemuck::cast_slice_mut(&mut self[..src.len()]);
+        copy_nt(dst, bytemuck::cast_slice(src));
+    }
+}
+
+/// Copy `src` into `dst`, which must be the same length, using streaming
+/// stores for the bulk of it
+#[cfg(target_arch = "x86_64")]
+fn copy_nt(dst: &mut [u8], src: &[u8]) {
+    use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_sfence, _mm_stream_si128};
+
+    const LANE: usize = std::mem::size_of::<__m128i>();
+
+    // Streaming stores need aligned destinations, so copy up to the first
+    // aligned address normally
+    let head = dst.as_ptr().align_offset(LANE).min(dst.len());
+    let (dst_head, dst_body) = dst.split_at_mut(head);
+    let (src_head, src_body) = src.split_at(head);
+    dst_head.copy_from_slice(src_head);
+
+    let lanes = dst_body.len() / LANE;
+    for i in 0..lanes {
+        // SAFETY: SSE2 is part of x86_64, both lanes are in bounds, and the
+        // destination is aligned
+        unsafe {
+            let value = _mm_loadu_si128(src_body.as_ptr().add(i * LANE).cast());
+            _mm_stream_si128(dst_body.as_mut_ptr().add(i * LANE).cast(), value);
+        }
+    }
+
+    let tail = lanes * LANE;
+    dst_body[tail..].copy_from_slice(&src_body[tail..]);
+
+    // Streaming stores are weakly ordered, make them visible like any other
+    // write before returning
+    unsafe { _mm_sfence() };
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn copy_nt(dst: &mut [u8], src: &[u8]) {
+    dst.copy_from_slice(src);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::copy_nt;
+    use crate::BackedBuffer;
+    use std::error::Error;
+
+    #[test]
+    fn non_temporal_copy() -> Result<(), Box<dyn Error>> {
+        let src: Vec<u32> = (0..10_000).collect();
+        let mut buf = BackedBuffer::<u32>::anonymous(10_001)?;
+        buf.copy_from_slice_nt(&src);
+        assert_eq!(&buf[..10_000], &src[..]);
+        assert_eq!(buf[10_000], 0);
+
+        // Unaligned heads and tails
+        let src: Vec<u8> = (0..100).collect();
+        let mut dst = [0u8; 100];
+        for start in 0..20 {
+            dst.fill(0);
+            copy_nt(&mut dst[start..start + 61], &src[..61]);
+            assert_eq!(&dst[start..start + 61], &src[..61]);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use std::{io, ops::Range};
+
+use bytemuck::Pod;
+
+use crate::{dirty::page_size, BackedBuffer, MmapBufferError};
+
+/// Move pages which are already in memory to comply with the new policy
+const MPOL_MF_MOVE: libc::c_uint = 1 << 1;
+
+/// Number of nodes a node mask can hold
+const MAX_NODES: usize = u64::BITS as usize;
+
+/// Where the kernel places the pages of a mapping on machines with several
+/// NUMA nodes, see [`BackedBufferBuilder::numa_policy`](crate::BackedBufferBuilder::numa_policy).
+/// Node sets are bitmasks, with bit `n` standing for node `n`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumaPolicy {
+    /// Only allocate pages on the given nodes
+    Bind(u64),
+    /// Spread pages round-robin across the given nodes
+    Interleave(u64),
+    /// Allocate pages on the given node where possible, falling back to
+    /// others when it is full
+    Preferred(usize),
+}
+
+impl NumaPolicy {
+    fn mode_and_mask(self) -> Result<(libc::c_int, u64), MmapBufferError> {
+        match self {
+            NumaPolicy::Bind(nodes) => Ok((libc::MPOL_BIND, nodes)),
+            NumaPolicy::Interleave(nodes) => Ok((libc::MPOL_INTERLEAVE, nodes)),
+            NumaPolicy::Preferred(node) => Ok((libc::MPOL_PREFERRED, node_mask(node)?)),
+        }
+    }
+}
+
+/// Apply a policy to `len` bytes starting at the page-aligned `ptr`
+pub(crate) fn mbind(ptr: *const u8, len: usize, policy: NumaPolicy) -> Result<(), MmapBufferError> {
+    if len == 0 {
+        return Ok(());
+    }
+
+    let (mode, mask) = policy.mode_and_mask()?;
+
+    // SAFETY: the range lies within a live mapping, and the kernel reads
+    // `maxnode - 1` bits of the mask
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            ptr,
+            len as libc::c_ulong,
+            mode,
+            &mask as *const u64,
+            (MAX_NODES + 1) as libc::c_ulong,
+            MPOL_MF_MOVE,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+fn node_mask(node: usize) -> Result<u64, MmapBufferError> {
+    if node >= MAX_NODES {
+        return Err(MmapBufferError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("NUMA node {node} is out of range"),
+        )));
+    }
+
+    Ok(1 << node)
+}
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Migrate the pages overlapping a range of the buffer to the given
+    /// NUMA node, and allocate any of them faulted in later there too. The
+    /// range is in units of `T`, not in bytes.
+    pub fn move_to_node(
+        &mut self,
+        range: Range<usize>,
+        node: usize,
+    ) -> Result<(), MmapBufferError> {
+        let (offset, len) = self.byte_range(range);
+
+        // `mbind` works on whole pages
+        let start = offset - offset % page_size();
+        let policy = NumaPolicy::Bind(node_mask(node)?);
+
+        // SAFETY: `start` is within the mapping
+        mbind(
+            unsafe { self.mmap.as_ptr().add(start) },
+            offset + len - start,
+            policy,
+        )
+    }
+
+    /// Apply a NUMA placement policy to the whole mapping, migrating any
+    /// pages already in memory. See also
+    /// [`BackedBufferBuilder::numa_policy`](crate::BackedBufferBuilder::numa_policy).
+    pub fn set_numa_policy(&mut self, policy: NumaPolicy) -> Result<(), MmapBufferError> {
+        mbind(self.mmap.as_ptr(), self.mmap.len(), policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NumaPolicy;
+    use crate::{BackedBuffer, BackedBufferBuilder};
+    use std::error::Error;
+
+    /// Kernels without NUMA support, and some sandboxes, reject `mbind`
+    fn numa_available() -> bool {
+        let err = BackedBuffer::<u8>::anonymous(4096)
+            .and_then(|mut buf| buf.set_numa_policy(NumaPolicy::Preferred(0)));
+        !matches!(err, Err(crate::MmapBufferError::Io(err)) if err.raw_os_error().is_some())
+    }
+
+    #[test]
+    fn placement() -> Result<(), Box<dyn Error>> {
+        assert!(BackedBuffer::<u8>::anonymous(4096)?
+            .move_to_node(0..1, 64)
+            .is_err());
+
+        if !numa_available() {
+            return Ok(());
+        }
+
+        let mut buf = BackedBufferBuilder::new()
+            .numa_policy(NumaPolicy::Interleave(1))
+            .anonymous::<u64>(1 << 16)?;
+        buf.fill(1);
+        buf.move_to_node(100..2000, 0)?;
+        buf.set_numa_policy(NumaPolicy::Bind(1))?;
+        assert!(buf.iter().all(|&x| x == 1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    ffi::OsString,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+/// The process holding the pid file of a buffer, see
+/// [`BackedBufferBuilder::pid_file`](crate::BackedBufferBuilder::pid_file)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockOwner {
+    /// Process id of the owner
+    pub pid: u32,
+    /// Host the owner runs on
+    pub hostname: String,
+    /// When the owner took the lock
+    pub since: SystemTime,
+}
+
+/// A pid file held by this process, removed on drop
+#[derive(Debug)]
+pub(crate) struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Create the pid file for the buffer at `path`, replacing it if it
+    /// was left behind by a process which no longer exists
+    pub(crate) fn acquire(path: &Path) -> Result<Self, MmapBufferError> {
+        let pid_path = pid_file_path(path);
+
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&pid_path)
+            {
+                Ok(mut file) => {
+                    let owner = LockOwner {
+                        pid: std::process::id(),
+                        hostname: hostname(),
+                        since: SystemTime::now(),
+                    };
+                    let pid_file = Self { path: pid_path };
+                    file.write_all(owner.to_string().as_bytes())?;
+                    return Ok(pid_file);
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            // Files which can't be parsed may still be being written, so
+            // only replace ones which definitely belong to a dead process
+            match read_owner(&pid_path)? {
+                Some(owner) if owner.is_stale() => match std::fs::remove_file(&pid_path) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                    _ => {}
+                },
+                Some(_) => return Err(MmapBufferError::Locked),
+                // Removed in the meantime
+                None if !pid_path.exists() => {}
+                None => return Err(MmapBufferError::Locked),
+            }
+        }
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Ignore the error, there's no way to report it
+        std::fs::remove_file(&self.path).unwrap_or(());
+    }
+}
+
+impl LockOwner {
+    /// Whether the owner is known to have exited without removing the file,
+    /// which can only be told for processes on this host
+    pub(crate) fn is_stale(&self) -> bool {
+        self.hostname == hostname() && !process_exists(self.pid)
+    }
+
+    fn parse(contents: &str) -> Option<Self> {
+        let mut fields = contents.trim_end().splitn(3, ' ');
+        let pid = fields.next()?.parse().ok()?;
+        let since = fields.next()?.parse().ok()?;
+        let hostname = fields.next()?.to_owned();
+
+        Some(Self {
+            pid,
+            hostname,
+            since: UNIX_EPOCH + Duration::from_secs(since),
+        })
+    }
+}
+
+impl std::fmt::Display for LockOwner {
+    /// The format of pid files: the pid, the time in seconds since the Unix
+    /// epoch and the hostname, separated by spaces
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let since = self
+            .since
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        writeln!(f, "{} {} {}", self.pid, since, self.hostname)
+    }
+}
+
+/// Path of the pid file for the buffer at `path`
+pub(crate) fn pid_file_path(path: &Path) -> PathBuf {
+    let mut pid_path = OsString::from(path.as_os_str());
+    pid_path.push(".pid");
+    pid_path.into()
+}
+
+/// The owner recorded in a pid file, or `None` if there is no file or it
+/// can't be parsed
+fn read_owner(pid_path: &Path) -> Result<Option<LockOwner>, MmapBufferError> {
+    match std::fs::read_to_string(pid_path) {
+        Ok(contents) => Ok(LockOwner::parse(&contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(unix)]
+fn process_exists(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks whether the process exists
+    let result = unsafe { libc::kill(pid as libc::pid_t, 0) };
+
+    // Processes owned by other users exist, but can't be signalled
+    result == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Without a way to check, assume every owner is still alive
+#[cfg(not(unix))]
+fn process_exists(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut name = [0u8; 256];
+
+    // SAFETY: the buffer is writable for its whole length
+    if unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) } != 0 {
+        return String::new();
+    }
+
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..len]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+impl<T: Pod> BackedBuffer<T> {
+    /// The process holding the pid file of the buffer at the given path, if
+    /// any, see [`BackedBufferBuilder::pid_file`](crate::BackedBufferBuilder::pid_file)
+    pub fn lock_owner(path: impl AsRef<Path>) -> Result<Option<LockOwner>, MmapBufferError> {
+        read_owner(&pid_file_path(path.as_ref()))
+    }
+
+    /// Remove the pid file of the buffer at the given path, e.g. when it was
+    /// left behind by a crashed process on another host, which can't be
+    /// detected automatically. Removing the pid file of a live buffer lets
+    /// others load it at the same time.
+    pub fn force_unlock(path: impl AsRef<Path>) -> Result<(), MmapBufferError> {
+        match std::fs::remove_file(pid_file_path(path.as_ref())) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hostname, pid_file_path, LockOwner};
+    use crate::{BackedBuffer, BackedBufferBuilder, LockMode, MmapBufferError};
+    use std::{error::Error, path::Path, time::SystemTime};
+
+    #[test]
+    fn pid_file() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.lock(LockMode::Unlocked).pid_file(true);
+
+        let buf = options.create::<u32>(16, file_path.clone())?;
+        let owner = BackedBuffer::<u32>::lock_owner(&file_path)?.unwrap();
+        assert_eq!(owner.pid, std::process::id());
+        assert!(matches!(
+            options.load::<u32>(&file_path),
+            Err(MmapBufferError::Locked)
+        ));
+        drop(buf);
+        assert_eq!(BackedBuffer::<u32>::lock_owner(&file_path)?, None);
+
+        // Files left behind by dead processes on this host are replaced
+        let mut child = std::process::Command::new("true").spawn()?;
+        let dead = LockOwner {
+            pid: child.id(),
+            hostname: hostname(),
+            since: SystemTime::now(),
+        };
+        child.wait()?;
+        std::fs::write(pid_file_path(&file_path), dead.to_string())?;
+        drop(options.load::<u32>(&file_path)?);
+
+        // Others need to be removed by hand
+        let elsewhere = LockOwner {
+            hostname: "elsewhere".to_owned(),
+            ..dead
+        };
+        std::fs::write(pid_file_path(&file_path), elsewhere.to_string())?;
+        assert!(options.load::<u32>(&file_path).is_err());
+        BackedBuffer::<u32>::force_unlock(&file_path)?;
+        options.load::<u32>(&file_path)?;
+
+        Ok(())
+    }
+}
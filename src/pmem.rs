@@ -0,0 +1,264 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    marker::PhantomData,
+    ops::{Deref, DerefMut, Range},
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+use bytemuck::Pod;
+use fs2::FileExt;
+
+use crate::{byte_size, MmapBufferError};
+
+/// A fixed size, mutable buffer of `T` mapped directly onto persistent
+/// memory, for files on a DAX filesystem (Optane, CXL memory and the like).
+///
+/// The file is mapped with `MAP_SYNC`, so the kernel guarantees that its
+/// metadata is durable whenever writes reach the memory itself. Making
+/// writes durable is then a matter of evicting them from the CPU caches,
+/// which [`persist`](Self::persist) does with cache line write-backs
+/// instead of an `msync` system call. Mapping fails on filesystems without
+/// DAX support.
+pub struct PmemBuffer<T: Pod> {
+    ptr: *mut u8,
+    capacity: usize,
+    file: File,
+    _ph: PhantomData<T>,
+}
+
+// SAFETY: the mapping is plain shared memory, owned by this value like an
+// `MmapMut`
+unsafe impl<T: Pod + Send> Send for PmemBuffer<T> {}
+unsafe impl<T: Pod + Sync> Sync for PmemBuffer<T> {}
+
+impl<T: Pod> PmemBuffer<T> {
+    /// Create a new buffer at the given path with a fixed, non-zero
+    /// capacity. Any existing file is truncated.
+    pub fn new(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path)?;
+
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+
+        // Blocks must be allocated up front, faulting in a hole can't be
+        // made synchronous
+        fs2::FileExt::allocate(&file, byte_size::<T>(0, capacity)? as u64)?;
+
+        Self::from_file(file)
+    }
+
+    /// Load a buffer from an existing path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+
+        Self::from_file(file)
+    }
+
+    fn from_file(file: File) -> Result<Self, MmapBufferError> {
+        let file_size = file.metadata()?.len() as usize;
+        let element_size = std::mem::size_of::<T>();
+        if file_size == 0 || !file_size.is_multiple_of(element_size) {
+            return Err(MmapBufferError::SizeMismatch {
+                file_size,
+                element_size,
+            });
+        }
+
+        // SAFETY: we hold an exclusive lock on the file
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                file_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED_VALIDATE | libc::MAP_SYNC,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(Self {
+            ptr: ptr.cast(),
+            capacity: file_size / element_size,
+            file,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Make writes to a range of the buffer durable by writing the cache
+    /// lines holding it back to persistent memory. The range is in units of
+    /// `T`, not in bytes.
+    pub fn persist(&self, range: Range<usize>) {
+        assert!(
+            range.start <= range.end && range.end <= self.capacity,
+            "range must lie within the buffer!"
+        );
+
+        let size = std::mem::size_of::<T>();
+        // SAFETY: the range was just checked to lie within the mapping
+        unsafe {
+            write_back(
+                self.ptr.add(range.start * size),
+                (range.end - range.start) * size,
+            )
+        };
+    }
+
+    /// Make all writes to the buffer durable, see [`persist`](Self::persist)
+    pub fn persist_all(&self) {
+        self.persist(0..self.capacity);
+    }
+}
+
+/// Write the cache lines overlapping `len` bytes at `ptr` back to memory,
+/// and wait for them to get there
+///
+/// SAFETY: the bytes must be mapped
+#[cfg(target_arch = "x86_64")]
+unsafe fn write_back(ptr: *const u8, len: usize) {
+    use std::arch::{asm, x86_64};
+
+    /// Cache line size on every x86 CPU with persistent memory support
+    const LINE: usize = 64;
+
+    let start = ptr as usize & !(LINE - 1);
+    let end = ptr as usize + len;
+    let instruction = write_back_instruction();
+
+    for line in (start..end).step_by(LINE) {
+        // SAFETY: the line overlaps the mapped range, and the instruction is
+        // supported by the CPU
+        unsafe {
+            match instruction {
+                WriteBack::Clwb => {
+                    asm!("clwb [{}]", in(reg) line, options(nostack, preserves_flags))
+                }
+                WriteBack::ClflushOpt => {
+                    asm!("clflushopt [{}]", in(reg) line, options(nostack, preserves_flags))
+                }
+                WriteBack::Clflush => x86_64::_mm_clflush(line as *const u8),
+            }
+        }
+    }
+
+    // `clwb` and `clflushopt` are weakly ordered
+    unsafe { x86_64::_mm_sfence() };
+}
+
+#[cfg(target_arch = "x86_64")]
+#[derive(Clone, Copy)]
+enum WriteBack {
+    /// Writes back without evicting the line
+    Clwb,
+    ClflushOpt,
+    /// Serializing, but available everywhere
+    Clflush,
+}
+
+/// The best cache line write-back instruction the CPU supports
+#[cfg(target_arch = "x86_64")]
+fn write_back_instruction() -> WriteBack {
+    use std::{arch::x86_64, sync::OnceLock};
+
+    static INSTRUCTION: OnceLock<WriteBack> = OnceLock::new();
+    *INSTRUCTION.get_or_init(|| {
+        // `cpuid` is available on every x86_64 CPU
+        if x86_64::__get_cpuid_max(0).0 < 7 {
+            return WriteBack::Clflush;
+        }
+        let ebx = x86_64::__cpuid_count(7, 0).ebx;
+
+        if ebx & (1 << 24) != 0 {
+            WriteBack::Clwb
+        } else if ebx & (1 << 23) != 0 {
+            WriteBack::ClflushOpt
+        } else {
+            WriteBack::Clflush
+        }
+    })
+}
+
+/// Fall back to `msync` where cache line write-backs aren't implemented
+///
+/// SAFETY: the bytes must be mapped
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn write_back(ptr: *const u8, len: usize) {
+    let page_size = crate::dirty::page_size();
+    let start = ptr as usize - ptr as usize % page_size;
+
+    // Ignore the error, the range is always valid
+    unsafe { libc::msync(start as *mut _, ptr as usize + len - start, libc::MS_SYNC) };
+}
+
+impl<T: Pod> Deref for PmemBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the mapping is page aligned and holds `capacity` elements
+        unsafe { std::slice::from_raw_parts(self.ptr.cast(), self.capacity) }
+    }
+}
+
+impl<T: Pod> DerefMut for PmemBuffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the mapping is page aligned and holds `capacity` elements
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.cast(), self.capacity) }
+    }
+}
+
+impl<T: Pod> Drop for PmemBuffer<T> {
+    fn drop(&mut self) {
+        // SAFETY: we own the mapping
+        unsafe { libc::munmap(self.ptr.cast(), self.capacity * std::mem::size_of::<T>()) };
+
+        // Ignore the error, advisory locks are still kind of sus
+        self.file.unlock().unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_back, PmemBuffer};
+    use crate::MmapBufferError;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn map_sync() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        match PmemBuffer::<u64>::new(1024, file_path.clone()) {
+            Ok(mut buf) => {
+                buf[1000] = 5;
+                buf.persist(1000..1001);
+                drop(buf);
+                assert_eq!(PmemBuffer::<u64>::load(file_path)?[1000], 5);
+            }
+            // Temporary directories are rarely on DAX filesystems
+            Err(MmapBufferError::Io(err)) => {
+                assert_eq!(err.raw_os_error(), Some(libc::EOPNOTSUPP));
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        // Writing back ordinary memory is harmless
+        let data = vec![1u8; 1000];
+        unsafe { write_back(data[3..].as_ptr(), 900) };
+
+        Ok(())
+    }
+}
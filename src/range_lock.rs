@@ -0,0 +1,176 @@
+use std::{fs::File, io, ops::Range, os::fd::AsRawFd};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+/// An advisory lock on a range of the file backing a [`BackedBuffer`],
+/// released on drop. See [`BackedBuffer::lock_range`].
+#[derive(Debug)]
+pub struct RangeLock {
+    file: File,
+    start: u64,
+    len: u64,
+}
+
+/// Open file description locks, which unlike classic POSIX record locks
+/// aren't released when any other descriptor for the file is closed, and
+/// exclude each other within a process too
+#[cfg(target_os = "linux")]
+const SET_LOCK: (libc::c_int, libc::c_int) = (libc::F_OFD_SETLK, libc::F_OFD_SETLKW);
+#[cfg(not(target_os = "linux"))]
+const SET_LOCK: (libc::c_int, libc::c_int) = (libc::F_SETLK, libc::F_SETLKW);
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Take an exclusive advisory lock on a range of the backing file,
+    /// blocking until no one else holds a lock overlapping it. The range is
+    /// in units of `T`, not in bytes.
+    ///
+    /// Range locks let processes own disjoint parts of the same file, which
+    /// all of them must then load with [`LockMode::Unlocked`](crate::LockMode::Unlocked)
+    /// or [`LockMode::Shared`](crate::LockMode::Shared), since a whole-file
+    /// exclusive lock keeps everyone else out. They are independent of the
+    /// whole-file lock. On Linux they exclude each other between separately
+    /// loaded buffers even within a process, elsewhere only between
+    /// processes. Fails for anonymous buffers.
+    pub fn lock_range(&self, range: Range<usize>) -> Result<RangeLock, MmapBufferError> {
+        self.range_lock(range, libc::F_WRLCK, SET_LOCK.1)
+    }
+
+    /// Take an exclusive lock on a range of the backing file, or fail with
+    /// [`MmapBufferError::Locked`] if anyone else holds a lock overlapping
+    /// it, see [`lock_range`](Self::lock_range)
+    pub fn try_lock_range(&self, range: Range<usize>) -> Result<RangeLock, MmapBufferError> {
+        self.range_lock(range, libc::F_WRLCK, SET_LOCK.0)
+    }
+
+    /// Take a shared lock on a range of the backing file, blocking while
+    /// anyone else holds an exclusive lock overlapping it, see
+    /// [`lock_range`](Self::lock_range)
+    pub fn lock_range_shared(&self, range: Range<usize>) -> Result<RangeLock, MmapBufferError> {
+        self.range_lock(range, libc::F_RDLCK, SET_LOCK.1)
+    }
+
+    /// Take a shared lock on a range of the backing file, or fail with
+    /// [`MmapBufferError::Locked`] if anyone else holds an exclusive lock
+    /// overlapping it, see [`lock_range`](Self::lock_range)
+    pub fn try_lock_range_shared(&self, range: Range<usize>) -> Result<RangeLock, MmapBufferError> {
+        self.range_lock(range, libc::F_RDLCK, SET_LOCK.0)
+    }
+
+    fn range_lock(
+        &self,
+        range: Range<usize>,
+        kind: libc::c_int,
+        command: libc::c_int,
+    ) -> Result<RangeLock, MmapBufferError> {
+        let file = self.file.as_ref().ok_or_else(|| {
+            MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "anonymous buffers have no file to lock",
+            ))
+        })?;
+        let (offset, len) = self.byte_range(range);
+
+        // Locks belong to the open file, which the clone shares, so the
+        // guard doesn't need to borrow the buffer
+        let lock = RangeLock {
+            file: file.try_clone()?,
+            start: self.file_offset + offset as u64,
+            len: len as u64,
+        };
+        lock.set(kind, command)?;
+        Ok(lock)
+    }
+}
+
+impl RangeLock {
+    /// Byte range of the file covered by the lock
+    pub fn byte_range(&self) -> Range<u64> {
+        self.start..self.start + self.len
+    }
+
+    fn set(&self, kind: libc::c_int, command: libc::c_int) -> Result<(), MmapBufferError> {
+        // A zero length would lock everything up to the end of the file
+        if self.len == 0 {
+            return Ok(());
+        }
+
+        // SAFETY: an all-zero `flock` is valid, and open file description
+        // locks require `l_pid` to be zero
+        let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+        lock.l_type = kind as _;
+        lock.l_whence = libc::SEEK_SET as _;
+        lock.l_start = self.start as _;
+        lock.l_len = self.len as _;
+
+        loop {
+            // SAFETY: `lock` is a valid `flock` which outlives the call
+            if unsafe { libc::fcntl(self.file.as_raw_fd(), command, &lock) } == 0 {
+                return Ok(());
+            }
+
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                Some(libc::EAGAIN | libc::EACCES) => return Err(MmapBufferError::Locked),
+                _ => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl Drop for RangeLock {
+    fn drop(&mut self) {
+        // Ignore the error, the lock goes away with the file anyway
+        self.set(libc::F_UNLCK, SET_LOCK.0).unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, BackedBufferBuilder, LockMode};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn range_locks() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.lock(LockMode::Unlocked);
+        let first = options.create::<u32>(100, file_path.clone())?;
+        let second = options.load::<u32>(file_path)?;
+
+        let lock = first.try_lock_range(0..10)?;
+        assert_eq!(lock.byte_range(), 0..40);
+        let _disjoint = second.try_lock_range(10..20)?;
+        let _shared = (
+            first.try_lock_range_shared(20..30)?,
+            second.try_lock_range_shared(25..35)?,
+        );
+        let _empty = second.try_lock_range(5..5)?;
+
+        #[cfg(target_os = "linux")]
+        {
+            use crate::MmapBufferError;
+
+            assert!(matches!(
+                second.try_lock_range(5..15),
+                Err(MmapBufferError::Locked)
+            ));
+            assert!(matches!(
+                first.try_lock_range(30..31),
+                Err(MmapBufferError::Locked)
+            ));
+            drop(lock);
+            second.try_lock_range(0..10)?;
+        }
+
+        assert!(BackedBuffer::<u32>::anonymous(10)?
+            .try_lock_range(0..1)
+            .is_err());
+
+        Ok(())
+    }
+}
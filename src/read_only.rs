@@ -0,0 +1,85 @@
+use std::{fs::File, marker::PhantomData, ops::Deref, path::Path};
+
+use bytemuck::{try_cast_slice, Pod};
+use memmap2::Mmap;
+
+use crate::{BackedBufferBuilder, LockMode, MmapBufferError};
+
+/// An immutable buffer of `T` backed by a file.
+///
+/// Unlike [`BackedBuffer`](crate::BackedBuffer), the file is opened without
+/// write permissions and only a *shared* advisory lock is taken, so any
+/// number of readers can map the same file at once. Writers holding an
+/// exclusive lock are still kept out.
+pub struct ReadOnlyBuffer<T: Pod> {
+    pub(crate) mmap: Mmap,
+    pub(crate) offset: usize,
+    pub(crate) len: usize,
+    pub(crate) file: Option<File>,
+    /// Separate lock file, if enabled
+    pub(crate) lock_file: Option<File>,
+    pub(crate) _ph: PhantomData<T>,
+}
+
+impl<T: Pod> ReadOnlyBuffer<T> {
+    /// Load a read-only buffer from an existing path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new()
+            .lock(LockMode::Shared)
+            .load_read_only(path)
+    }
+}
+
+impl<T: Pod> Deref for ReadOnlyBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: should predictably panic if file corrupted
+        try_cast_slice(&self.mmap[self.offset..self.offset + self.len * std::mem::size_of::<T>()])
+            .unwrap()
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for ReadOnlyBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self.deref()
+    }
+}
+
+impl<T: Pod> Drop for ReadOnlyBuffer<T> {
+    fn drop(&mut self) {
+        for file in [self.file.take(), self.lock_file.take()]
+            .into_iter()
+            .flatten()
+        {
+            // Ignore the error, advisory locks are still kind of sus
+            file.unlock().unwrap_or(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadOnlyBuffer;
+    use crate::BackedBuffer;
+    use std::{error::Error, fs::File, io::Write, path::Path};
+
+    #[test]
+    fn shared_readers() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        File::create(file_path.clone())?.write_all("hello, world!".as_bytes())?;
+
+        let reader_1 = ReadOnlyBuffer::<u8>::load(file_path.clone())?;
+        let reader_2 = ReadOnlyBuffer::<u8>::load(file_path.clone())?;
+        assert_eq!(&reader_1[..], &reader_2[..]);
+
+        // Writers are locked out until all readers are gone
+        BackedBuffer::<u8>::load(file_path.clone()).expect_err("");
+        drop((reader_1, reader_2));
+        BackedBuffer::<u8>::load(file_path)?;
+
+        Ok(())
+    }
+}
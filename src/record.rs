@@ -0,0 +1,166 @@
+use std::path::Path;
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, BackedBufferBuilder, MmapBufferError};
+
+/// A record type with a fingerprint of its layout, which a [header] can
+/// record in place of the type name, so that a program loading a file
+/// written by another version of it with a different record layout fails
+/// with [`MmapBufferError::TypeMismatch`] instead of reading garbage.
+/// Unlike the type name, the fingerprint doesn't change when the type is
+/// moved or renamed.
+///
+/// Implement it with `#[derive(MmapRecord)]`, which fingerprints the size
+/// and alignment of the struct, and the name, type, offset and size of
+/// each field. The struct must be `#[repr(C)]`, so that the layout only
+/// depends on the fields.
+///
+/// ```
+/// use bytemuck::{Pod, Zeroable};
+/// use mmap_buffer::{BackedBuffer, MmapRecord};
+///
+/// #[derive(Clone, Copy, MmapRecord)]
+/// #[repr(C)]
+/// struct Point {
+///     x: f32,
+///     y: f32,
+/// }
+///
+/// // SAFETY: `repr(C)` with no padding, and all fields are `Pod`
+/// unsafe impl Zeroable for Point {}
+/// unsafe impl Pod for Point {}
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let tempdir = tempfile::tempdir()?;
+/// # let path = tempdir.path().join("points");
+/// let mut points = BackedBuffer::<Point>::new_record(16, &path)?;
+/// points[0].x = 1.0;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [header]: BackedBufferBuilder::header
+pub trait MmapRecord: Pod {
+    /// Fingerprint of the layout of the type
+    const LAYOUT: u64;
+}
+
+impl<T: MmapRecord> BackedBuffer<T> {
+    /// Create a new buffer like [`new`](Self::new), but with a header
+    /// recording the layout of `T`, see
+    /// [`BackedBufferBuilder::record`]. Such files must be loaded with
+    /// [`load_record`](Self::load_record).
+    pub fn new_record(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new()
+            .record::<T>()
+            .create(capacity, path)
+    }
+
+    /// Load a buffer created with [`new_record`](Self::new_record),
+    /// checking that the header matches the layout of `T`.
+    pub fn load_record(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        BackedBufferBuilder::new().record::<T>().load(path)
+    }
+}
+
+/// Layout fingerprinting used by the code `#[derive(MmapRecord)]`
+/// generates: 64-bit FNV-1a, like the type name hash in headers
+#[doc(hidden)]
+pub mod fingerprint {
+    /// Hash of no input
+    pub const SEED: u64 = 0xcbf29ce484222325;
+
+    /// Continue `hash` with the bytes of `s`, followed by a terminator so
+    /// that consecutive strings can't run into each other
+    pub const fn hash_str(hash: u64, s: &str) -> u64 {
+        let bytes = s.as_bytes();
+        let mut hash = hash;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash = (hash ^ bytes[i] as u64).wrapping_mul(0x100000001b3);
+            i += 1;
+        }
+        (hash ^ 0xff).wrapping_mul(0x100000001b3)
+    }
+
+    /// Continue `hash` with `n`, as 8 little endian bytes
+    pub const fn hash_usize(hash: u64, n: usize) -> u64 {
+        let bytes = (n as u64).to_le_bytes();
+        let mut hash = hash;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash = (hash ^ bytes[i] as u64).wrapping_mul(0x100000001b3);
+            i += 1;
+        }
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, MmapBufferError, MmapRecord};
+    use bytemuck::{Pod, Zeroable};
+    use std::{error::Error, path::Path};
+
+    mod v1 {
+        use crate::MmapRecord;
+
+        #[derive(Clone, Copy, MmapRecord)]
+        #[repr(C)]
+        pub struct Entry {
+            pub id: u64,
+            pub value: u32,
+            pub flags: u32,
+        }
+    }
+
+    mod v2 {
+        use crate::MmapRecord;
+
+        // Same size and types as before, but the fields were swapped
+        #[derive(Clone, Copy, MmapRecord)]
+        #[repr(C)]
+        pub struct Entry {
+            pub id: u64,
+            pub flags: u32,
+            pub value: u32,
+        }
+    }
+
+    // Defined elsewhere, but with the same layout
+    #[derive(Clone, Copy, MmapRecord)]
+    #[repr(C)]
+    struct Moved {
+        id: u64,
+        value: u32,
+        flags: u32,
+    }
+
+    // SAFETY: `repr(C)` with no padding, and all fields are `Pod`
+    unsafe impl Zeroable for v1::Entry {}
+    unsafe impl Pod for v1::Entry {}
+    unsafe impl Zeroable for v2::Entry {}
+    unsafe impl Pod for v2::Entry {}
+    unsafe impl Zeroable for Moved {}
+    unsafe impl Pod for Moved {}
+
+    #[test]
+    fn layout_fingerprint() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        assert_eq!(v1::Entry::LAYOUT, Moved::LAYOUT);
+        assert_ne!(v1::Entry::LAYOUT, v2::Entry::LAYOUT);
+
+        let mut buf = BackedBuffer::<v1::Entry>::new_record(4, file_path.clone())?;
+        buf[3].value = 7;
+        drop(buf);
+
+        assert_eq!(BackedBuffer::<Moved>::load_record(&file_path)?[3].value, 7);
+        let err = BackedBuffer::<v2::Entry>::load_record(&file_path).unwrap_err();
+        assert!(matches!(err, MmapBufferError::TypeMismatch));
+
+        Ok(())
+    }
+}
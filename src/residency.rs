@@ -0,0 +1,98 @@
+use std::io;
+
+use bytemuck::Pod;
+
+use crate::{dirty::page_size, BackedBuffer, MmapBufferError};
+
+/// Which pages of a mapping are resident in RAM, see
+/// [`BackedBuffer::residency`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Residency {
+    pages: Vec<bool>,
+    page_size: usize,
+}
+
+impl Residency {
+    /// Size of one page in bytes
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Number of pages in the mapping
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Number of pages currently resident
+    pub fn resident_count(&self) -> usize {
+        self.pages.iter().filter(|&&resident| resident).count()
+    }
+
+    /// Whether the page with the given index is resident. Panics if out of
+    /// bounds.
+    pub fn is_resident(&self, page: usize) -> bool {
+        self.pages[page]
+    }
+
+    /// Residency of each page, in order
+    pub fn pages(&self) -> &[bool] {
+        &self.pages
+    }
+}
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Check which pages of the mapping are currently resident in RAM
+    /// (`mincore`), e.g. to decide whether a range is worth
+    /// [prefetching](Self::prefetch). Pages are counted from the start of
+    /// the mapping, which includes any header. The result is only a
+    /// snapshot, as the kernel may load or evict pages at any time.
+    pub fn residency(&self) -> Result<Residency, MmapBufferError> {
+        let page_size = page_size();
+        let len = self.mmap.len();
+        let mut pages = vec![0u8; len.div_ceil(page_size)];
+
+        if len > 0 {
+            // SAFETY: the mapping starts on a page boundary and `pages` has
+            // room for one byte per page of it
+            let result = unsafe {
+                libc::mincore(self.mmap.as_ptr() as *mut _, len, pages.as_mut_ptr().cast())
+            };
+            if result != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+
+        Ok(Residency {
+            // Only the lowest bit is specified, the rest are reserved
+            pages: pages.into_iter().map(|page| page & 1 != 0).collect(),
+            page_size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BackedBuffer;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn residency() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u8>::new(1 << 20, file_path)?;
+        buf[0] = 1;
+
+        let residency = buf.residency()?;
+        assert_eq!(residency.page_count() * residency.page_size(), 1 << 20);
+        assert!(residency.is_resident(0));
+        assert!(residency.resident_count() >= 1);
+
+        assert_eq!(
+            BackedBuffer::<u8>::anonymous(0)?.residency()?.page_count(),
+            0
+        );
+
+        Ok(())
+    }
+}
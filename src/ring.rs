@@ -0,0 +1,302 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+use bytemuck::Pod;
+use fs2::FileExt;
+
+use crate::{byte_size, dirty::page_size, MmapBufferError};
+
+/// A region of a file mapped twice, back to back, in virtual memory, so
+/// that any window of up to `len` bytes starting in the first copy is
+/// contiguous.
+pub(crate) struct DoubleMapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// SAFETY: the mapping is plain shared memory, owned by this value like an
+// `MmapMut`
+unsafe impl Send for DoubleMapping {}
+unsafe impl Sync for DoubleMapping {}
+
+impl DoubleMapping {
+    /// Map `len` bytes of `file` starting at `offset` twice. Both `offset`
+    /// and `len` must be multiples of the page size.
+    ///
+    /// SAFETY: the caller must make sure the file isn't truncated while
+    /// mapped
+    pub(crate) unsafe fn new(file: &File, offset: usize, len: usize) -> io::Result<Self> {
+        assert!(
+            offset.is_multiple_of(page_size()) && len.is_multiple_of(page_size()) && len > 0,
+            "mapping must be a positive number of whole pages!"
+        );
+
+        // Reserve address space for both copies, then map the file over
+        // each half
+        let reserved = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                2 * len,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if reserved == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mapping = Self {
+            ptr: reserved.cast(),
+            len,
+        };
+
+        for half in 0..2 {
+            let addr = unsafe { mapping.ptr.add(half * len) };
+            let mapped = unsafe {
+                libc::mmap(
+                    addr.cast(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_SHARED | libc::MAP_FIXED,
+                    file.as_raw_fd(),
+                    offset as libc::off_t,
+                )
+            };
+            if mapped == libc::MAP_FAILED {
+                // Dropping `mapping` releases the whole reservation
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(mapping)
+    }
+
+    /// Start of the first copy, which may be written through since the
+    /// mapping is never borrowed as a reference
+    pub(crate) fn as_mut_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    /// Synchronously write changes back to the file. Both copies share the
+    /// same pages, so flushing the first is enough.
+    pub(crate) fn flush(&self) -> io::Result<()> {
+        if unsafe { libc::msync(self.ptr.cast(), self.len, libc::MS_SYNC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for DoubleMapping {
+    fn drop(&mut self) {
+        // SAFETY: we own the whole reservation
+        unsafe { libc::munmap(self.ptr.cast(), 2 * self.len) };
+    }
+}
+
+/// A fixed-capacity ring of `T` backed by a file.
+///
+/// The file is mapped twice back-to-back in virtual memory, so a window of
+/// up to [`capacity`](Self::capacity) elements starting anywhere in the ring
+/// is a single contiguous slice, even when it crosses the wrap point. The
+/// capacity is rounded up so the file is a whole number of pages.
+///
+/// Tracking read and write positions is up to the caller, see
+/// [`SpscQueue`](crate::ipc::SpscQueue) for a queue built on top of this.
+pub struct RingBuffer<T: Pod> {
+    mapping: DoubleMapping,
+    capacity: usize,
+    file: File,
+    _ph: PhantomData<T>,
+}
+
+impl<T: Pod> RingBuffer<T> {
+    /// Create a new ring at the given path with room for at least
+    /// `capacity` elements. Any existing file is truncated.
+    pub fn new(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .create(true)
+            .open(path)?;
+
+        // Only truncate once no one else is using the file
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+        file.set_len(0)?;
+        file.set_len(ring_size::<T>(capacity)? as u64)?;
+
+        Self::from_file(file)
+    }
+
+    /// Load a ring from an existing path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+
+        Self::from_file(file)
+    }
+
+    fn from_file(file: File) -> Result<Self, MmapBufferError> {
+        let file_size = file.metadata()?.len() as usize;
+        let element_size = std::mem::size_of::<T>();
+        if file_size == 0
+            || !file_size.is_multiple_of(page_size())
+            || !file_size.is_multiple_of(element_size)
+        {
+            return Err(MmapBufferError::SizeMismatch {
+                file_size,
+                element_size,
+            });
+        }
+
+        // SAFETY: we hold an exclusive lock on the file
+        let mapping = unsafe { DoubleMapping::new(&file, 0, file_size)? };
+
+        Ok(Self {
+            mapping,
+            capacity: file_size / element_size,
+            file,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Number of elements in the ring
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Contiguous view of `len` elements starting at `start`, wrapping
+    /// around the end of the ring. `start` is taken modulo the capacity.
+    pub fn slice(&self, start: usize, len: usize) -> &[T] {
+        let ptr = self.window(start, len);
+
+        // SAFETY: the window lies within the double mapping
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+    }
+
+    /// Mutable contiguous view of `len` elements starting at `start`,
+    /// wrapping around the end of the ring. `start` is taken modulo the
+    /// capacity.
+    pub fn slice_mut(&mut self, start: usize, len: usize) -> &mut [T] {
+        let ptr = self.window(start, len);
+
+        // SAFETY: the window lies within the double mapping, and no element
+        // appears twice in it since `len` is at most the capacity
+        unsafe { std::slice::from_raw_parts_mut(ptr, len) }
+    }
+
+    /// Synchronously write any outstanding changes back to the file.
+    pub fn flush(&self) -> Result<(), MmapBufferError> {
+        Ok(self.mapping.flush()?)
+    }
+
+    fn window(&self, start: usize, len: usize) -> *mut T {
+        assert!(len <= self.capacity, "`len` must not exceed the capacity!");
+
+        let offset = (start % self.capacity) * std::mem::size_of::<T>();
+        // SAFETY: the offset is within the first copy, and pages are aligned
+        // for any `T` whose size divides the file size
+        unsafe { self.mapping.as_mut_ptr().add(offset).cast() }
+    }
+}
+
+/// Size in bytes of a ring holding at least `capacity` elements of `T`,
+/// rounded up to a whole number of pages and elements
+pub(crate) fn ring_size<T: Pod>(capacity: usize) -> Result<usize, MmapBufferError> {
+    let element_size = std::mem::size_of::<T>();
+    let page_size = page_size();
+
+    // Least common multiple of page and element size
+    let (mut a, mut b) = (page_size, element_size);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    let granule = page_size / a * element_size;
+
+    usize::max(byte_size::<T>(0, capacity)?, 1)
+        .checked_next_multiple_of(granule)
+        .ok_or(MmapBufferError::CapacityOverflow)
+}
+
+impl<T: Pod> Deref for RingBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.slice(0, self.capacity)
+    }
+}
+
+impl<T: Pod> DerefMut for RingBuffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.slice_mut(0, self.capacity)
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for RingBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self.deref()
+    }
+}
+
+impl<T: Pod> AsMut<[T]> for RingBuffer<T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self.deref_mut()
+    }
+}
+
+impl<T: Pod> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        // Ignore the error, advisory locks are still kind of sus
+        self.file.unlock().unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RingBuffer;
+    use crate::{dirty::page_size, MmapBufferError};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn wraparound() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut ring = RingBuffer::<u32>::new(10, file_path.clone())?;
+        let capacity = ring.capacity();
+        assert_eq!(capacity, page_size() / 4);
+
+        // A write across the wrap point lands at both ends
+        ring.slice_mut(capacity - 2, 4)
+            .copy_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(ring[capacity - 2..], [1, 2]);
+        assert_eq!(ring[..2], [3, 4]);
+        assert_eq!(ring.slice(2 * capacity - 1, 2), [2, 3]);
+        drop(ring);
+
+        let ring = RingBuffer::<u32>::load(file_path.clone())?;
+        assert_eq!(ring.slice(capacity - 2, 4), [1, 2, 3, 4]);
+
+        // Recreating a ring in use fails without wiping it
+        let err = RingBuffer::<u32>::new(10, file_path).err().unwrap();
+        assert!(matches!(err, MmapBufferError::Locked));
+        assert_eq!(ring.slice(capacity - 2, 4), [1, 2, 3, 4]);
+
+        Ok(())
+    }
+}
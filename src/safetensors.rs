@@ -0,0 +1,433 @@
+use std::{fs::File, path::Path};
+
+use bytemuck::Pod;
+use fs2::FileExt;
+use memmap2::{Mmap, MmapOptions};
+
+use crate::MmapBufferError;
+
+/// Element types with a safetensors dtype
+pub trait TensorElement: Pod {
+    /// Name of the dtype in the header, e.g. `"F32"`
+    const DTYPE: &'static str;
+}
+
+macro_rules! tensor_element {
+    ($($ty:ty => $dtype:literal),*) => {
+        $(impl TensorElement for $ty {
+            const DTYPE: &'static str = $dtype;
+        })*
+    };
+}
+
+tensor_element!(
+    u8 => "U8", u16 => "U16", u32 => "U32", u64 => "U64",
+    i8 => "I8", i16 => "I16", i32 => "I32", i64 => "I64",
+    f32 => "F32", f64 => "F64"
+);
+
+/// Description of one tensor in a [`SafeTensorsFile`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TensorInfo {
+    /// Name of the tensor
+    pub name: String,
+    /// Name of the element type, e.g. `"F32"` or `"BF16"`
+    pub dtype: String,
+    /// Length of each dimension, in row-major order
+    pub shape: Vec<usize>,
+    /// Byte range of the tensor's data, relative to the end of the header
+    data_offsets: (usize, usize),
+}
+
+/// A read-only mapping of a [safetensors](https://huggingface.co/docs/safetensors)
+/// file, exposing each tensor as a typed slice without reading it into
+/// memory.
+///
+/// Like a [`ReadOnlyBuffer`](crate::ReadOnlyBuffer), the file is opened
+/// without write permissions under a shared advisory lock.
+///
+/// ```no_run
+/// use mmap_buffer::SafeTensorsFile;
+///
+/// let weights = SafeTensorsFile::open("model.safetensors").unwrap();
+/// let layer: &[f32] = weights.tensor("weights.0").unwrap();
+/// ```
+pub struct SafeTensorsFile {
+    mmap: Mmap,
+    /// Byte offset of the data section
+    data_start: usize,
+    tensors: Vec<TensorInfo>,
+    file: File,
+}
+
+impl SafeTensorsFile {
+    /// Open and parse the header of an existing file. Fails with
+    /// [`MmapBufferError::InvalidHeader`] if it isn't a valid safetensors
+    /// file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = File::open(path)?;
+        FileExt::try_lock_shared(&file).map_err(MmapBufferError::from_lock_error)?;
+
+        // SAFETY: writers through this crate are excluded by the lock
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+
+        let header_len = mmap
+            .get(..8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+            .and_then(|len| usize::try_from(len).ok())
+            .ok_or(MmapBufferError::InvalidHeader)?;
+        let data_start = header_len
+            .checked_add(8)
+            .filter(|&start| start <= mmap.len())
+            .ok_or(MmapBufferError::InvalidHeader)?;
+
+        let header = std::str::from_utf8(&mmap[8..data_start])
+            .map_err(|_| MmapBufferError::InvalidHeader)?;
+        let tensors =
+            parse_header(header, mmap.len() - data_start).ok_or(MmapBufferError::InvalidHeader)?;
+
+        Ok(Self {
+            mmap,
+            data_start,
+            tensors,
+            file,
+        })
+    }
+
+    /// All tensors in the file, in the order they appear in the header
+    pub fn tensors(&self) -> &[TensorInfo] {
+        &self.tensors
+    }
+
+    /// Description of the tensor with the given name, if there is one
+    pub fn info(&self, name: &str) -> Option<&TensorInfo> {
+        self.tensors.iter().find(|tensor| tensor.name == name)
+    }
+
+    /// View the data of the tensor with the given name, failing with
+    /// [`MmapBufferError::TypeMismatch`] if it doesn't hold elements of `T`.
+    /// Safetensors data is little-endian, so multi-byte types only match on
+    /// little-endian targets.
+    pub fn tensor<T: TensorElement>(&self, name: &str) -> Result<&[T], MmapBufferError> {
+        let tensor = self.info(name).ok_or_else(|| {
+            MmapBufferError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no tensor named {name:?}"),
+            ))
+        })?;
+
+        let native = cfg!(target_endian = "little") || std::mem::size_of::<T>() == 1;
+        if tensor.dtype != T::DTYPE || !native {
+            return Err(MmapBufferError::TypeMismatch);
+        }
+
+        let (start, end) = tensor.data_offsets;
+        let bytes = &self.mmap[self.data_start + start..self.data_start + end];
+        let size = tensor
+            .shape
+            .iter()
+            .try_fold(std::mem::size_of::<T>(), |size, &dim| size.checked_mul(dim));
+        if size != Some(bytes.len()) {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        bytemuck::try_cast_slice(bytes).map_err(|err| {
+            MmapBufferError::from_cast_error(err, bytes.len(), std::mem::size_of::<T>())
+        })
+    }
+}
+
+impl Drop for SafeTensorsFile {
+    fn drop(&mut self) {
+        // Ignore the error, advisory locks are still kind of sus
+        self.file.unlock().unwrap_or(());
+    }
+}
+
+/// Parse the JSON header, checking every tensor fits in `data_len` bytes
+fn parse_header(header: &str, data_len: usize) -> Option<Vec<TensorInfo>> {
+    let mut parser = Parser {
+        rest: header.trim_end_matches(' '),
+        depth: 0,
+    };
+    let Json::Object(entries) = parser.value()? else {
+        return None;
+    };
+    if !parser.rest.trim_start().is_empty() {
+        return None;
+    }
+
+    let mut tensors = Vec::new();
+    for (name, value) in entries {
+        if name == "__metadata__" {
+            continue;
+        }
+
+        let Json::Object(fields) = value else {
+            return None;
+        };
+        let field = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+
+        let Some(Json::String(dtype)) = field("dtype") else {
+            return None;
+        };
+        let Some(Json::Array(shape)) = field("shape") else {
+            return None;
+        };
+        let Some(Json::Array(offsets)) = field("data_offsets") else {
+            return None;
+        };
+
+        let shape = shape
+            .iter()
+            .map(Json::as_usize)
+            .collect::<Option<Vec<_>>>()?;
+        let [start, end] = offsets.as_slice() else {
+            return None;
+        };
+        let (start, end) = (start.as_usize()?, end.as_usize()?);
+        if start > end || end > data_len {
+            return None;
+        }
+
+        tensors.push(TensorInfo {
+            name,
+            dtype: dtype.clone(),
+            shape,
+            data_offsets: (start, end),
+        });
+    }
+
+    Some(tensors)
+}
+
+/// The subset of JSON found in safetensors headers
+enum Json {
+    Null,
+    Bool,
+    /// Kept as text, as offsets may not fit in an `f64`
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(text) => text.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Deepest nesting of arrays and objects a header needs, for the shape
+/// array of a tensor within the top level object
+const MAX_DEPTH: usize = 3;
+
+/// A minimal recursive descent JSON parser
+struct Parser<'a> {
+    rest: &'a str,
+    /// Number of arrays and objects currently open
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Option<Json> {
+        self.rest = self.rest.trim_start();
+        let c = self.rest.chars().next()?;
+        if !matches!(c, '{' | '[') {
+            return self.scalar(c);
+        }
+
+        // Bound the recursion, so hostile headers can't overflow the stack
+        if self.depth == MAX_DEPTH {
+            return None;
+        }
+
+        self.depth += 1;
+        let value = self.nested(c);
+        self.depth -= 1;
+        value
+    }
+
+    /// Parse an array or object, starting with `c`
+    fn nested(&mut self, c: char) -> Option<Json> {
+        self.rest = &self.rest[1..];
+        match c {
+            '{' => {
+                let mut entries = Vec::new();
+                if !self.eat('}') {
+                    loop {
+                        self.rest = self.rest.trim_start();
+                        let key = self.string()?;
+                        if !self.eat(':') {
+                            return None;
+                        }
+                        entries.push((key, self.value()?));
+                        if self.eat('}') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return None;
+                        }
+                    }
+                }
+
+                Some(Json::Object(entries))
+            }
+            _ => {
+                let mut items = Vec::new();
+                if !self.eat(']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat(']') {
+                            break;
+                        }
+                        if !self.eat(',') {
+                            return None;
+                        }
+                    }
+                }
+
+                Some(Json::Array(items))
+            }
+        }
+    }
+
+    /// Parse a string, keyword or number, starting with `c`
+    fn scalar(&mut self, c: char) -> Option<Json> {
+        match c {
+            '"' => self.string().map(Json::String),
+            'n' => self.keyword("null", Json::Null),
+            't' => self.keyword("true", Json::Bool),
+            'f' => self.keyword("false", Json::Bool),
+            _ => {
+                let end = self
+                    .rest
+                    .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+                    .unwrap_or(self.rest.len());
+                if end == 0 {
+                    return None;
+                }
+
+                let (number, rest) = self.rest.split_at(end);
+                self.rest = rest;
+                Some(Json::Number(number.to_owned()))
+            }
+        }
+    }
+
+    /// Parse a string literal, unescaping it
+    fn string(&mut self) -> Option<String> {
+        let mut chars = self.rest.strip_prefix('"')?.char_indices();
+        let mut string = String::new();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 2..];
+                    return Some(string);
+                }
+                '\\' => string.push(match chars.next()?.1 {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let hex: String = (0..4)
+                            .filter_map(|_| chars.next())
+                            .map(|(_, c)| c)
+                            .collect();
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)
+                            .unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                    other => other,
+                }),
+                c => string.push(c),
+            }
+        }
+
+        None
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Option<Json> {
+        self.rest = self.rest.strip_prefix(word)?;
+        Some(value)
+    }
+
+    /// Skip whitespace and then `c`, returning whether it was there
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.trim_start().strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SafeTensorsFile;
+    use crate::MmapBufferError;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn tensors() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test.safetensors");
+
+        let header = r#"{"__metadata__":{"format":"pt"},"weights.0":{"dtype":"F32","shape":[2,2],"data_offsets":[0,16]},"bias":{"dtype":"I8","shape":[3],"data_offsets":[16,19]}}"#;
+        let header = format!("{header:<160}");
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend(header.as_bytes());
+        for x in [1.0f32, 2.0, 3.0, 4.0] {
+            bytes.extend(x.to_le_bytes());
+        }
+        bytes.extend([1, 2, 255]);
+        std::fs::write(&file_path, bytes)?;
+
+        let file = SafeTensorsFile::open(&file_path)?;
+        assert_eq!(file.tensors().len(), 2);
+        assert_eq!(file.info("weights.0").unwrap().shape, [2, 2]);
+
+        if cfg!(target_endian = "little") {
+            assert_eq!(file.tensor::<f32>("weights.0")?, &[1.0, 2.0, 3.0, 4.0]);
+        }
+        assert_eq!(file.tensor::<i8>("bias")?, &[1, 2, -1]);
+
+        let err = file.tensor::<u32>("weights.0").unwrap_err();
+        assert!(matches!(err, MmapBufferError::TypeMismatch));
+        assert!(file.tensor::<f32>("missing").is_err());
+
+        std::fs::write(&file_path, b"\x10\0\0\0\0\0\0\0{\"a\":")?;
+        assert!(matches!(
+            SafeTensorsFile::open(&file_path),
+            Err(MmapBufferError::InvalidHeader)
+        ));
+
+        // Deeply nested headers are rejected rather than overflowing the stack
+        let header = "[".repeat(1 << 20);
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend(header.as_bytes());
+        std::fs::write(&file_path, bytes)?;
+        assert!(matches!(
+            SafeTensorsFile::open(&file_path),
+            Err(MmapBufferError::InvalidHeader)
+        ));
+
+        let header = r#"{"__metadata__":{"a":{"b":[]}}}"#;
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend(header.as_bytes());
+        std::fs::write(&file_path, bytes)?;
+        assert!(matches!(
+            SafeTensorsFile::open(&file_path),
+            Err(MmapBufferError::InvalidHeader)
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    os::fd::{FromRawFd, OwnedFd},
+};
+
+use bytemuck::Pod;
+use memmap2::MmapOptions;
+
+use crate::{
+    byte_size, dirty::DirtyPages, AtomicElement, BackedBuffer, FlushOnDrop, LockMode,
+    MmapBufferError, OnCorruption, ZeroizeOnDrop,
+};
+
+/// A fixed size buffer of `T` in a named POSIX shared memory object, so
+/// several processes can exchange data without a path on a real filesystem.
+///
+/// No advisory lock is taken, since the whole point is for multiple
+/// processes to map the buffer at once. Coordinating concurrent writes is
+/// the caller's responsibility. The shared memory object outlives the
+/// buffer until it is removed with [`SharedBuffer::unlink`].
+pub struct SharedBuffer<T: Pod> {
+    pub(crate) buffer: BackedBuffer<T>,
+    name: CString,
+}
+
+impl<T: Pod> SharedBuffer<T> {
+    /// Create a new shared memory object with the given name and a fixed
+    /// capacity in units of `T`. Fails if an object with this name already
+    /// exists.
+    pub fn create(name: &str, capacity: usize) -> Result<Self, MmapBufferError> {
+        let capacity_bytes = byte_size::<T>(0, capacity)?;
+        let name = shm_name(name)?;
+        let file = shm_open(&name, libc::O_RDWR | libc::O_CREAT | libc::O_EXCL)?;
+
+        if let Err(err) = file.set_len(capacity_bytes as u64) {
+            // SAFETY: `name` is a valid null-terminated string
+            unsafe { libc::shm_unlink(name.as_ptr()) };
+            return Err(err.into());
+        }
+
+        Self::from_file(file, name)
+    }
+
+    /// Open an existing shared memory object with the given name.
+    pub fn open(name: &str) -> Result<Self, MmapBufferError> {
+        let name = shm_name(name)?;
+        let file = shm_open(&name, libc::O_RDWR)?;
+
+        Self::from_file(file, name)
+    }
+
+    /// Remove the shared memory object with the given name. Processes which
+    /// already have it mapped keep their mapping.
+    pub fn unlink(name: &str) -> Result<(), MmapBufferError> {
+        let name = shm_name(name)?;
+
+        // SAFETY: `name` is a valid null-terminated string
+        if unsafe { libc::shm_unlink(name.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(())
+    }
+
+    /// View the contents as a slice of atomics, so processes sharing the
+    /// buffer can coordinate without locks, see
+    /// [`BackedBuffer::as_atomic_slice`]
+    pub fn as_atomic_slice<A: AtomicElement>(&mut self) -> Result<&[A], MmapBufferError> {
+        self.buffer.as_atomic_slice()
+    }
+
+    /// Name of the shared memory object
+    pub fn name(&self) -> &str {
+        // Built from a `&str`, so always valid UTF-8
+        self.name.to_str().unwrap()
+    }
+
+    fn from_file(file: File, name: CString) -> Result<Self, MmapBufferError> {
+        // SAFETY: concurrent access is the documented responsibility of the caller
+        let mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+        let len = bytemuck::try_cast_slice::<u8, T>(&mmap[..])
+            .map_err(|err| {
+                MmapBufferError::from_cast_error(err, mmap.len(), std::mem::size_of::<T>())
+            })?
+            .len();
+
+        Ok(Self {
+            buffer: BackedBuffer {
+                mmap,
+                guards: None,
+                offset: 0,
+                file_offset: 0,
+                window: false,
+                header: false,
+                copy_on_write: false,
+                huge_pages: false,
+                len,
+                file: Some(file),
+                path: None,
+                delete_on_drop: false,
+                flush_on_drop: FlushOnDrop::None,
+                fsync_on_drop: false,
+                zeroize_on_drop: ZeroizeOnDrop::None,
+                dirty_flag: false,
+                unclean_shutdown: false,
+                lock: LockMode::Unlocked,
+                on_corruption: OnCorruption::Error,
+                checksum: None,
+                lock_file: None,
+                pid_file: None,
+                dirty: DirtyPages::default(),
+                _ph: PhantomData,
+            },
+            name,
+        })
+    }
+}
+
+/// POSIX requires shared memory names to start with a single slash
+fn shm_name(name: &str) -> Result<CString, MmapBufferError> {
+    let name = if name.starts_with('/') {
+        name.to_owned()
+    } else {
+        format!("/{name}")
+    };
+
+    CString::new(name)
+        .map_err(|err| MmapBufferError::Io(io::Error::new(io::ErrorKind::InvalidInput, err)))
+}
+
+fn shm_open(name: &CString, flags: libc::c_int) -> Result<File, MmapBufferError> {
+    // Apple declares `shm_open` as variadic, so the mode must be promoted
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    let mode = 0o600 as libc::c_uint;
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    let mode = 0o600 as libc::mode_t;
+
+    // SAFETY: `name` is a valid null-terminated string
+    let fd = unsafe { libc::shm_open(name.as_ptr(), flags, mode) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    // SAFETY: `fd` is a freshly opened descriptor which nothing else owns
+    Ok(File::from(unsafe { OwnedFd::from_raw_fd(fd) }))
+}
+
+impl<T: Pod> Deref for SharedBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.buffer.deref()
+    }
+}
+
+impl<T: Pod> DerefMut for SharedBuffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.buffer.deref_mut()
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for SharedBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self.deref()
+    }
+}
+
+impl<T: Pod> AsMut<[T]> for SharedBuffer<T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self.deref_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedBuffer;
+    use std::error::Error;
+
+    #[test]
+    fn create_open() -> Result<(), Box<dyn Error>> {
+        let name = format!("mmap-buffer-test-{}", std::process::id());
+
+        let mut writer = SharedBuffer::<u32>::create(&name, 16)?;
+        assert!(SharedBuffer::<u32>::create(&name, 16).is_err());
+        writer[3] = 42;
+
+        let reader = SharedBuffer::<u32>::open(&name)?;
+        assert_eq!(reader.len(), 16);
+        assert_eq!(reader[3], 42);
+
+        SharedBuffer::<u32>::unlink(&name)?;
+        assert!(SharedBuffer::<u32>::open(&name).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::Path,
+};
+
+use bytemuck::Pod;
+use fs2::FileExt;
+use memmap2::{MmapMut, MmapOptions};
+
+use crate::{header::type_hash, MmapBufferError};
+
+/// Columns start on multiples of this many bytes, so that they never share
+/// a cache line and are aligned for any reasonable `T`
+const COLUMN_ALIGN: usize = 64;
+
+/// Element type and length of one column of a [`SoaBuffer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Column {
+    len: usize,
+    element_size: usize,
+    align: usize,
+    type_hash: u64,
+}
+
+impl Column {
+    /// A column of `len` elements of type `T`
+    pub fn new<T: Pod>(len: usize) -> Self {
+        Self {
+            len,
+            element_size: std::mem::size_of::<T>(),
+            align: std::mem::align_of::<T>(),
+            type_hash: type_hash::<T>(),
+        }
+    }
+
+    /// Number of elements in the column
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the column has no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn is<T: Pod>(&self) -> bool {
+        *self == Self::new::<T>(self.len)
+    }
+}
+
+/// A struct-of-arrays buffer: several columns, each a slice of its own
+/// element type, stored in disjoint regions of a single file.
+///
+/// The layout isn't stored in the file, so the same columns must be given
+/// when loading it again. Unlike a [`Container`](crate::Container), all
+/// columns can be borrowed mutably at once with
+/// [`columns_mut`](Self::columns_mut).
+///
+/// ```
+/// use mmap_buffer::{Column, SoaBuffer};
+///
+/// # let dir = tempfile::tempdir().unwrap();
+/// # let path = dir.path().join("data");
+/// let columns = [Column::new::<f32>(100), Column::new::<u8>(100)];
+/// let mut buf = SoaBuffer::create(&columns, &path).unwrap();
+///
+/// let (scores, flags) = buf.columns_mut::<(f32, u8)>().unwrap();
+/// scores[0] = 0.5;
+/// flags[0] = 1;
+/// ```
+pub struct SoaBuffer {
+    mmap: MmapMut,
+    columns: Vec<Column>,
+    /// Byte offset of each column in the file
+    offsets: Vec<usize>,
+    file: File,
+}
+
+impl SoaBuffer {
+    /// Create a new buffer at the given path with the given columns, all
+    /// zeroed. Any existing file is truncated.
+    pub fn create(columns: &[Column], path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let (offsets, size) = layout(columns)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path)?;
+
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+        file.set_len(size as u64)?;
+
+        Self::from_file(file, columns, offsets)
+    }
+
+    /// Load a buffer created with the same columns from an existing path.
+    pub fn load(columns: &[Column], path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let (offsets, size) = layout(columns)?;
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+
+        let file_size = file.metadata()?.len() as usize;
+        if file_size != size {
+            return Err(MmapBufferError::LengthMismatch {
+                expected: size,
+                actual: file_size,
+            });
+        }
+
+        Self::from_file(file, columns, offsets)
+    }
+
+    fn from_file(
+        file: File,
+        columns: &[Column],
+        offsets: Vec<usize>,
+    ) -> Result<Self, MmapBufferError> {
+        // SAFETY: we hold an exclusive lock on the file
+        let mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+
+        Ok(Self {
+            mmap,
+            columns: columns.to_vec(),
+            offsets,
+            file,
+        })
+    }
+
+    /// The columns of the buffer
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// View column `index`, failing with [`MmapBufferError::TypeMismatch`]
+    /// if it doesn't hold elements of `T`.
+    pub fn column<T: Pod>(&self, index: usize) -> Result<&[T], MmapBufferError> {
+        let (start, end) = self.column_range::<T>(index)?;
+        Ok(bytemuck::cast_slice(&self.mmap[start..end]))
+    }
+
+    /// Mutably view column `index`, failing with
+    /// [`MmapBufferError::TypeMismatch`] if it doesn't hold elements of `T`.
+    pub fn column_mut<T: Pod>(&mut self, index: usize) -> Result<&mut [T], MmapBufferError> {
+        let (start, end) = self.column_range::<T>(index)?;
+        Ok(bytemuck::cast_slice_mut(&mut self.mmap[start..end]))
+    }
+
+    /// Mutably view all columns at once, as a tuple of slices with the
+    /// element types in `C`, e.g. `columns_mut::<(f32, u32)>()`. Fails with
+    /// [`MmapBufferError::TypeMismatch`] unless `C` lists the element type
+    /// of every column, in order.
+    pub fn columns_mut<C: ColumnTypes>(&mut self) -> Result<C::SlicesMut<'_>, MmapBufferError> {
+        if !C::matches(&self.columns) {
+            return Err(MmapBufferError::TypeMismatch);
+        }
+
+        // SAFETY: the columns were just checked, and their regions are
+        // disjoint and within the mapping
+        Ok(unsafe { C::split(self.mmap.as_mut_ptr(), &self.offsets, &self.columns) })
+    }
+
+    /// Synchronously write any outstanding changes back to the file.
+    pub fn flush(&self) -> Result<(), MmapBufferError> {
+        Ok(self.mmap.flush()?)
+    }
+
+    fn column_range<T: Pod>(&self, index: usize) -> Result<(usize, usize), MmapBufferError> {
+        let column = self.columns.get(index).ok_or_else(|| {
+            MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no column at index {index}"),
+            ))
+        })?;
+        if !column.is::<T>() {
+            return Err(MmapBufferError::TypeMismatch);
+        }
+
+        let start = self.offsets[index];
+        Ok((start, start + column.len * column.element_size))
+    }
+}
+
+impl Drop for SoaBuffer {
+    fn drop(&mut self) {
+        // Ignore the error, advisory locks are still kind of sus
+        self.file.unlock().unwrap_or(());
+    }
+}
+
+/// Byte offset of each column, and the total size of the file
+fn layout(columns: &[Column]) -> Result<(Vec<usize>, usize), MmapBufferError> {
+    let mut offsets = Vec::with_capacity(columns.len());
+    let mut size = 0usize;
+
+    for column in columns {
+        assert!(
+            column.align <= COLUMN_ALIGN,
+            "column alignment must be at most {COLUMN_ALIGN} bytes!"
+        );
+
+        let start = size
+            .checked_next_multiple_of(COLUMN_ALIGN)
+            .ok_or(MmapBufferError::CapacityOverflow)?;
+        offsets.push(start);
+        size = column
+            .len
+            .checked_mul(column.element_size)
+            .and_then(|bytes| bytes.checked_add(start))
+            .ok_or(MmapBufferError::CapacityOverflow)?;
+    }
+
+    Ok((offsets, size))
+}
+
+/// Tuples of element types, one per column of a [`SoaBuffer`], see
+/// [`SoaBuffer::columns_mut`]. Implemented for tuples of up to 8 `Pod`
+/// types.
+pub trait ColumnTypes {
+    /// A tuple of mutable slices, one per column
+    type SlicesMut<'a>;
+
+    #[doc(hidden)]
+    fn matches(columns: &[Column]) -> bool;
+
+    /// SAFETY: `columns` must match, and `base + offsets[i]` must point to the
+    /// disjoint, suitably aligned region of each column
+    #[doc(hidden)]
+    unsafe fn split<'a>(
+        base: *mut u8,
+        offsets: &[usize],
+        columns: &[Column],
+    ) -> Self::SlicesMut<'a>;
+}
+
+macro_rules! column_types {
+    ($($ty:ident $index:tt),*) => {
+        impl<$($ty: Pod),*> ColumnTypes for ($($ty,)*) {
+            type SlicesMut<'a> = ($(&'a mut [$ty],)*);
+
+            fn matches(columns: &[Column]) -> bool {
+                let count = [$($index),*].len();
+                columns.len() == count $(&& columns[$index].is::<$ty>())*
+            }
+
+            unsafe fn split<'a>(
+                base: *mut u8,
+                offsets: &[usize],
+                columns: &[Column],
+            ) -> Self::SlicesMut<'a> {
+                ($(
+                    unsafe {
+                        std::slice::from_raw_parts_mut(
+                            base.add(offsets[$index]).cast::<$ty>(),
+                            columns[$index].len,
+                        )
+                    },
+                )*)
+            }
+        }
+    };
+}
+
+column_types!(A 0);
+column_types!(A 0, B 1);
+column_types!(A 0, B 1, C 2);
+column_types!(A 0, B 1, C 2, D 3);
+column_types!(A 0, B 1, C 2, D 3, E 4);
+column_types!(A 0, B 1, C 2, D 3, E 4, F 5);
+column_types!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+column_types!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+#[cfg(test)]
+mod tests {
+    use super::{Column, SoaBuffer};
+    use crate::MmapBufferError;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn columns() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        let columns = [
+            Column::new::<u8>(3),
+            Column::new::<f64>(2),
+            Column::new::<u16>(5),
+        ];
+
+        {
+            let mut buf = SoaBuffer::create(&columns, file_path.clone())?;
+            let (a, b, c) = buf.columns_mut::<(u8, f64, u16)>()?;
+            a.copy_from_slice(&[1, 2, 3]);
+            b[1] = 2.5;
+            c[4] = 9;
+
+            assert!(buf.columns_mut::<(u8, f64)>().is_err());
+        }
+
+        let buf = SoaBuffer::load(&columns, file_path.clone())?;
+        assert_eq!(buf.column::<u8>(0)?, &[1, 2, 3]);
+        assert_eq!(buf.column::<f64>(1)?, &[0.0, 2.5]);
+        assert_eq!(buf.column::<u16>(2)?[4], 9);
+
+        let err = buf.column::<u32>(2).unwrap_err();
+        assert!(matches!(err, MmapBufferError::TypeMismatch));
+        drop(buf);
+
+        let err = SoaBuffer::load(&columns[..2], file_path).err().unwrap();
+        assert!(matches!(err, MmapBufferError::LengthMismatch { .. }));
+
+        Ok(())
+    }
+}
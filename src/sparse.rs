@@ -0,0 +1,123 @@
+use std::{io, ops::Range};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Zero the given range of elements and release the disk blocks which
+    /// held them, so mostly zero buffers only take up space for the rest.
+    /// The file keeps its size, and the range reads as zeroes afterwards.
+    ///
+    /// Blocks are released with `fallocate(FALLOC_FL_PUNCH_HOLE)` on Linux,
+    /// which only frees whole filesystem blocks and zeroes the partial
+    /// ones at either end. Elsewhere, and for anonymous and copy-on-write
+    /// buffers, the range is just zeroed.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of bounds.
+    pub fn punch_hole(&mut self, range: Range<usize>) -> Result<(), MmapBufferError> {
+        let (offset, len) = self.byte_range(range);
+        if len == 0 {
+            return Ok(());
+        }
+        self.mark_dirty();
+
+        match &self.file {
+            #[cfg(target_os = "linux")]
+            Some(file) if !self.copy_on_write => {
+                punch_file(file, self.file_offset + offset as u64, len as u64)?;
+            }
+            _ => self.mmap[offset..offset + len].fill(0),
+        }
+
+        Ok(())
+    }
+
+    /// Number of bytes the backing file actually occupies on disk, which is
+    /// less than its size if it has holes, see
+    /// [`punch_hole`](Self::punch_hole). Outside of Unix this is just the
+    /// size of the file. Fails for anonymous buffers.
+    pub fn allocated_size(&self) -> Result<u64, MmapBufferError> {
+        let file = self.file.as_ref().ok_or_else(|| {
+            MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "anonymous buffers have no file to measure",
+            ))
+        })?;
+
+        let metadata = file.metadata()?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            // Counted in 512 byte units whatever the block size
+            Ok(metadata.blocks() * 512)
+        }
+
+        #[cfg(not(unix))]
+        {
+            Ok(metadata.len())
+        }
+    }
+}
+
+/// Deallocate `len` bytes of `file` starting at byte `start`, keeping its
+/// size
+#[cfg(target_os = "linux")]
+pub(crate) fn punch_file(file: &std::fs::File, start: u64, len: u64) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: the descriptor is valid
+    let result = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+            start as libc::off_t,
+            len as libc::off_t,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BackedBuffer;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn punch_hole() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = BackedBuffer::<u64>::new(1 << 17, file_path)?;
+        buf.fill(u64::MAX);
+        buf.flush()?;
+        let allocated = buf.allocated_size()?;
+
+        buf.punch_hole(100..(1 << 17) - 100)?;
+        assert_eq!(buf[99], u64::MAX);
+        assert_eq!(buf[100], 0);
+        assert_eq!(buf[(1 << 17) - 101], 0);
+        assert_eq!(buf[(1 << 17) - 100], u64::MAX);
+
+        // Filesystems without hole support, like some overlays, keep the
+        // blocks around
+        #[cfg(target_os = "linux")]
+        assert!(buf.allocated_size()? <= allocated);
+
+        let mut anon = BackedBuffer::<u64>::anonymous(16)?;
+        anon.fill(1);
+        anon.punch_hole(4..8)?;
+        assert_eq!(&anon[3..9], &[1, 0, 0, 0, 0, 1]);
+        assert!(anon.allocated_size().is_err());
+
+        Ok(())
+    }
+}
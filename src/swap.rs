@@ -0,0 +1,137 @@
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+};
+
+use bytemuck::Pod;
+
+use crate::{checksum, BackedBuffer, MmapBufferError};
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Exchange the paths of the files backing two buffers, e.g. to put a
+    /// rebuilt index in place of the live one. Both buffers keep their
+    /// mappings and locks, which follow the files rather than their paths,
+    /// and learn their new path. Checksum sidecars are exchanged too.
+    ///
+    /// On Linux this is a single atomic `renameat2(RENAME_EXCHANGE)`, so
+    /// other processes always find a complete file at either path.
+    /// Elsewhere, and on filesystems without support for it, it falls back
+    /// to three renames through a temporary path, during which the first
+    /// path is briefly missing. Fails for buffers without a path.
+    pub fn swap_files(a: &mut Self, b: &mut Self) -> Result<(), MmapBufferError> {
+        let (Some(path_a), Some(path_b)) = (&a.path, &b.path) else {
+            return Err(MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only buffers with a path can be swapped",
+            )));
+        };
+        if a.checksum.is_some() != b.checksum.is_some() {
+            return Err(MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "either both or neither buffer must have a checksum",
+            )));
+        }
+
+        exchange(path_a, path_b)?;
+        if a.checksum.is_some() {
+            exchange(
+                &checksum::sidecar_path(path_a),
+                &checksum::sidecar_path(path_b),
+            )?;
+        }
+
+        std::mem::swap(&mut a.path, &mut b.path);
+        Ok(())
+    }
+}
+
+/// Atomically exchange the files at two paths where possible
+fn exchange(a: &Path, b: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+        let c_a = CString::new(a.as_os_str().as_bytes())?;
+        let c_b = CString::new(b.as_os_str().as_bytes())?;
+
+        // SAFETY: both paths are valid null-terminated strings
+        let result = unsafe {
+            libc::renameat2(
+                libc::AT_FDCWD,
+                c_a.as_ptr(),
+                libc::AT_FDCWD,
+                c_b.as_ptr(),
+                libc::RENAME_EXCHANGE,
+            )
+        };
+        if result == 0 {
+            return Ok(());
+        }
+
+        // Anything but missing support for exchanging is a real error
+        let err = io::Error::last_os_error();
+        if !matches!(err.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) {
+            return Err(err);
+        }
+    }
+
+    let temp = temp_path(a);
+    std::fs::rename(a, &temp)?;
+    if let Err(err) = std::fs::rename(b, a) {
+        // Put the first file back where it was
+        std::fs::rename(&temp, a).unwrap_or(());
+        return Err(err);
+    }
+    std::fs::rename(&temp, b)
+}
+
+/// A path next to `path` to move it out of the way to
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = OsString::from(path.as_os_str());
+    temp.push(format!(".swap-{}", std::process::id()));
+    temp.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::exchange;
+    use crate::{BackedBuffer, BackedBufferBuilder};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn swap_files() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path_a = Path::join(tempdir.path(), "a");
+        let path_b = Path::join(tempdir.path(), "b");
+
+        let mut options = BackedBufferBuilder::new();
+        options.checksum(true);
+        let mut live = options.create_from_slice(b"live", &path_a)?;
+        let mut rebuilt = options.create_from_slice(b"new!", &path_b)?;
+
+        BackedBuffer::swap_files(&mut live, &mut rebuilt)?;
+        assert_eq!(live.path(), Some(path_b.as_path()));
+        assert_eq!(std::fs::read(&path_a)?, b"new!");
+
+        // Writes still reach the right file, and checksums still match
+        rebuilt.copy_from_slice(b"next");
+        drop((live, rebuilt));
+        assert_eq!(&options.load::<u8>(&path_a)?[..], b"next");
+        assert_eq!(&options.load::<u8>(&path_b)?[..], b"live");
+
+        // The fallback leaves the same result
+        std::fs::write(&path_a, "a")?;
+        std::fs::write(&path_b, "b")?;
+        std::fs::remove_file(Path::join(tempdir.path(), "a.crc32"))?;
+        exchange(&path_a, &path_b)?;
+        assert_eq!(std::fs::read(&path_a)?, b"b");
+        assert!(BackedBuffer::swap_files(
+            &mut BackedBuffer::<u8>::anonymous(1)?,
+            &mut BackedBuffer::<u8>::anonymous(1)?
+        )
+        .is_err());
+
+        Ok(())
+    }
+}
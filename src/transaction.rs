@@ -0,0 +1,315 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    ops::Deref,
+    path::Path,
+};
+
+use bytemuck::Pod;
+
+use crate::{checksum::crc32, dirty::page_size, BackedBuffer, MmapBufferError};
+
+/// Shadow copies of modified pages of the system page size, keyed by page
+/// index
+type Pages = BTreeMap<usize, Box<[u8]>>;
+
+/// Identifies a complete journal
+const JOURNAL_MAGIC: [u8; 8] = *b"MMAPJRNL";
+
+/// A [`BackedBuffer`] whose modifications are grouped into transactions
+/// which either apply completely or not at all, even across crashes.
+///
+/// Writes made through a [`Transaction`] go to in-memory shadow copies of
+/// the affected pages. On commit, the shadow pages are first written to a
+/// `<path>.journal` file ending in a checksummed commit record and synced,
+/// and only then copied into the primary file. If the process dies while
+/// copying, the journal is replayed on the next load, and a journal without
+/// a valid commit record is simply discarded. The primary file therefore
+/// never contains a torn transaction.
+pub struct TransactionalBuffer<T: Pod> {
+    buffer: BackedBuffer<T>,
+    journal: File,
+}
+
+impl<T: Pod> TransactionalBuffer<T> {
+    /// Create a new buffer at the given path with a fixed capacity.
+    /// This capacity is in units of `T`, not in bytes
+    pub fn new(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let path = path.as_ref();
+        let buffer = BackedBuffer::new(capacity, path)?;
+        let journal = open_journal(path)?;
+        journal.set_len(0)?;
+
+        Ok(Self { buffer, journal })
+    }
+
+    /// Load a buffer from an existing path, replaying the journal if a
+    /// committed transaction was interrupted.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let path = path.as_ref();
+        let mut buffer = BackedBuffer::load(path)?;
+        let mut journal = open_journal(path)?;
+
+        if let Some((page_size, pages)) = read_journal(&mut journal)? {
+            apply_pages(&mut buffer, page_size, &pages)?;
+        }
+        journal.set_len(0)?;
+        journal.sync_all()?;
+
+        Ok(Self { buffer, journal })
+    }
+
+    /// Start a transaction. Its writes only become visible in the buffer
+    /// once it is [committed](Transaction::commit), and are discarded if it
+    /// is dropped or [rolled back](Transaction::rollback).
+    pub fn begin(&mut self) -> Transaction<'_, T> {
+        Transaction {
+            owner: self,
+            pages: BTreeMap::new(),
+            page_size: page_size(),
+        }
+    }
+}
+
+impl<T: Pod> Deref for TransactionalBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.buffer.deref()
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for TransactionalBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self.deref()
+    }
+}
+
+/// An open transaction on a [`TransactionalBuffer`], see
+/// [`TransactionalBuffer::begin`]
+pub struct Transaction<'a, T: Pod> {
+    owner: &'a mut TransactionalBuffer<T>,
+    pages: Pages,
+    page_size: usize,
+}
+
+impl<T: Pod> Transaction<'_, T> {
+    /// Read the element at `index`, including any writes made in this
+    /// transaction so far.
+    pub fn get(&self, index: usize) -> T {
+        let start = self.byte_offset(index, 1);
+
+        let mut value = T::zeroed();
+        for (i, byte) in bytemuck::bytes_of_mut(&mut value).iter_mut().enumerate() {
+            let pos = start + i;
+            *byte = match self.pages.get(&(pos / self.page_size)) {
+                Some(page) => page[pos % self.page_size],
+                None => self.owner.buffer.mmap[pos],
+            };
+        }
+
+        value
+    }
+
+    /// Write `values` starting at element `index`.
+    pub fn write(&mut self, index: usize, values: &[T]) {
+        let start = self.byte_offset(index, values.len());
+        let mapping = &self.owner.buffer.mmap;
+        let page_size = self.page_size;
+
+        for (pos, &byte) in (start..).zip(bytemuck::cast_slice::<T, u8>(values)) {
+            let page = pos / page_size;
+            let shadow = self.pages.entry(page).or_insert_with(|| {
+                let start = page * page_size;
+                let end = usize::min(start + page_size, mapping.len());
+                mapping[start..end].into()
+            });
+
+            shadow[pos % page_size] = byte;
+        }
+    }
+
+    /// Durably apply all writes made in this transaction.
+    pub fn commit(self) -> Result<(), MmapBufferError> {
+        if self.pages.is_empty() {
+            return Ok(());
+        }
+
+        // The commit record makes the journal authoritative once synced
+        let journal = &mut self.owner.journal;
+        let record = encode_journal(self.page_size, &self.pages);
+        journal.set_len(0)?;
+        journal.seek(SeekFrom::Start(0))?;
+        journal.write_all(&record)?;
+        journal.sync_all()?;
+
+        apply_pages(&mut self.owner.buffer, self.page_size, &self.pages)?;
+
+        journal.set_len(0)?;
+        journal.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Discard all writes made in this transaction. Equivalent to dropping it.
+    pub fn rollback(self) {}
+
+    fn byte_offset(&self, index: usize, len: usize) -> usize {
+        assert!(
+            index + len <= self.owner.len(),
+            "range must be within the buffer!"
+        );
+        self.owner.buffer.offset + index * std::mem::size_of::<T>()
+    }
+}
+
+fn open_journal(path: &Path) -> Result<File, MmapBufferError> {
+    let mut journal = path.as_os_str().to_owned();
+    journal.push(".journal");
+
+    Ok(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(journal)?)
+}
+
+/// Layout: magic, page size, page count, then (page index, page length,
+/// page bytes) for each page, and finally a CRC-32 of everything before it.
+/// The page size is recorded so the journal can be replayed on a machine
+/// with different pages.
+fn encode_journal(page_size: usize, pages: &Pages) -> Vec<u8> {
+    let mut record = JOURNAL_MAGIC.to_vec();
+    record.extend_from_slice(&(page_size as u64).to_le_bytes());
+    record.extend_from_slice(&(pages.len() as u64).to_le_bytes());
+
+    for (&index, page) in pages {
+        record.extend_from_slice(&(index as u64).to_le_bytes());
+        record.extend_from_slice(&(page.len() as u64).to_le_bytes());
+        record.extend_from_slice(page);
+    }
+
+    let checksum = crc32(&record);
+    record.extend_from_slice(&checksum.to_le_bytes());
+    record
+}
+
+/// Returns the page size and pages of a complete journal, or `None` if it
+/// is empty or was never fully committed
+fn read_journal(journal: &mut File) -> Result<Option<(usize, Pages)>, MmapBufferError> {
+    let mut record = Vec::new();
+    journal.seek(SeekFrom::Start(0))?;
+    journal.read_to_end(&mut record)?;
+
+    let Some((body, checksum)) = record.split_last_chunk::<4>() else {
+        return Ok(None);
+    };
+
+    if !body.starts_with(&JOURNAL_MAGIC) || crc32(body) != u32::from_le_bytes(*checksum) {
+        return Ok(None);
+    }
+
+    let mut words = &body[JOURNAL_MAGIC.len()..];
+    let mut pages = BTreeMap::new();
+    let page_size = take_u64(&mut words)?;
+    let count = take_u64(&mut words)?;
+    for _ in 0..count {
+        let index = take_u64(&mut words)?;
+        let len = take_u64(&mut words)?;
+        let page = words.get(..len).ok_or(MmapBufferError::InvalidHeader)?;
+        words = &words[len..];
+        pages.insert(index, page.into());
+    }
+
+    Ok(Some((page_size, pages)))
+}
+
+fn take_u64(words: &mut &[u8]) -> Result<usize, MmapBufferError> {
+    let (word, rest) = words
+        .split_first_chunk::<8>()
+        .ok_or(MmapBufferError::InvalidHeader)?;
+    *words = rest;
+
+    Ok(u64::from_le_bytes(*word) as usize)
+}
+
+/// Copy the pages into the buffer like any other tracked write, then flush
+/// it, which also brings its checksum up to date
+fn apply_pages<T: Pod>(
+    buffer: &mut BackedBuffer<T>,
+    page_size: usize,
+    pages: &Pages,
+) -> Result<(), MmapBufferError> {
+    for (&index, page) in pages {
+        let start = index
+            .checked_mul(page_size)
+            .filter(|start| start + page.len() <= buffer.mmap.len())
+            .ok_or(MmapBufferError::InvalidHeader)?;
+        buffer.write_bytes(start, page);
+    }
+
+    buffer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_journal, open_journal, TransactionalBuffer};
+    use crate::dirty::page_size;
+    use std::{collections::BTreeMap, error::Error, io::Write, path::Path};
+
+    #[test]
+    fn commit_rollback() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = TransactionalBuffer::<u32>::new(2048, file_path)?;
+
+        let mut tx = buf.begin();
+        tx.write(1020, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(tx.get(1025), 6);
+        tx.commit()?;
+        assert_eq!(buf[1020..1028], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert!(buf.buffer.dirty_pages() > 0);
+
+        let mut tx = buf.begin();
+        tx.write(0, &[9]);
+        tx.rollback();
+        assert_eq!(buf[0], 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_journal() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+        let page = page_size();
+        drop(TransactionalBuffer::<u8>::new(2 * page, file_path.clone())?);
+
+        // A committed journal whose pages never made it to the primary file
+        let mut pages = BTreeMap::new();
+        pages.insert(1, vec![7; page].into_boxed_slice());
+        open_journal(&file_path)?.write_all(&encode_journal(page, &pages))?;
+
+        let mut buf = TransactionalBuffer::<u8>::load(file_path.clone())?;
+        assert!(buf[page..].iter().all(|&x| x == 7));
+
+        let mut tx = buf.begin();
+        tx.write(page, &vec![3; page]);
+        tx.commit()?;
+        drop(buf);
+
+        // A torn journal is discarded, leaving the page it covers alone
+        pages.insert(1, vec![5; page].into_boxed_slice());
+        let record = encode_journal(page, &pages);
+        open_journal(&file_path)?.write_all(&record[..record.len() - 1])?;
+
+        let buf = TransactionalBuffer::<u8>::load(file_path)?;
+        assert!(buf[page..].iter().all(|&x| x == 3));
+
+        Ok(())
+    }
+}
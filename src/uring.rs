@@ -0,0 +1,461 @@
+use std::{
+    io,
+    marker::PhantomData,
+    ops::Range,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use bytemuck::Pod;
+use memmap2::{MmapOptions, MmapRaw};
+
+use crate::{dirty::page_size, BackedBuffer, MmapBufferError};
+
+// Definitions from `linux/io_uring.h`, which `libc` doesn't export
+const IORING_OFF_SQ_RING: u64 = 0;
+const IORING_OFF_CQ_RING: u64 = 0x8000000;
+const IORING_OFF_SQES: u64 = 0x10000000;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OP_FSYNC: u8 = 3;
+const IORING_OP_MADVISE: u8 = 25;
+const IORING_FSYNC_DATASYNC: u32 = 1;
+
+/// Most bytes covered by a single operation, whose length is only 32 bits
+const CHUNK_SIZE: usize = 1 << 30;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// Submission queue entry, with the unions flattened to the members used
+/// here
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// An `io_uring` instance for queueing [`prefetch`](Self::prefetch) and
+/// [`flush_range`](Self::flush_range) of [`BackedBuffer`]s without blocking
+/// the calling thread. Requires Linux 5.6 or later, and the `io_uring`
+/// feature.
+///
+/// Operations are handed to the kernel before the queueing call returns,
+/// and run in the background from then on. [`wait`](Self::wait) blocks
+/// until all of them have completed, reporting the first one which failed.
+/// Ranges over a gigabyte are split into several operations, so warming or
+/// syncing a huge mapping never ties up a thread for the whole of it.
+///
+/// The kernel keeps using the address and file of a buffer until its
+/// operations complete, so buffers stay borrowed for as long as the ring
+/// lives, and dropping the ring waits for anything still in flight. A
+/// buffer can't be dropped, resized or remapped in the meantime:
+///
+/// ```compile_fail
+/// use mmap_buffer::{BackedBuffer, IoUring};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let buf = BackedBuffer::<u64>::anonymous(1 << 20)?;
+/// let mut ring = IoUring::new(8)?;
+/// ring.prefetch(&buf, 0..buf.len())?;
+/// drop(buf);
+/// ring.wait()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct IoUring<'a> {
+    ring: OwnedFd,
+    sq: MmapRaw,
+    cq: MmapRaw,
+    sqes: MmapRaw,
+    params: Params,
+    /// Entries written to the submission queue but not yet submitted
+    unsubmitted: u32,
+    /// Entries queued whose completion hasn't been reaped yet
+    in_flight: u32,
+    /// First failure among the completions reaped so far
+    error: Option<io::Error>,
+    /// Buffers with operations queued, which must outlive the ring
+    _buffers: PhantomData<&'a ()>,
+}
+
+impl<'a> IoUring<'a> {
+    /// Set up a new ring with room for at least `entries` operations to be
+    /// submitted at once. More can be queued, but queueing then waits for
+    /// earlier ones to complete.
+    pub fn new(entries: u32) -> Result<Self, MmapBufferError> {
+        let mut params = Params::default();
+
+        // SAFETY: `params` is a valid `io_uring_params` for the kernel to
+        // fill in
+        let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, entries.max(1), &mut params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        // SAFETY: `fd` is a freshly opened descriptor which nothing else owns
+        let ring = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+        let map = |offset, len| {
+            MmapOptions::new()
+                .offset(offset)
+                .len(len)
+                .map_raw(ring.as_raw_fd())
+        };
+
+        let sq_len = params.sq_off.array as usize + 4 * params.sq_entries as usize;
+        let cq_len =
+            params.cq_off.cqes as usize + std::mem::size_of::<Cqe>() * params.cq_entries as usize;
+        let sqes_len = std::mem::size_of::<Sqe>() * params.sq_entries as usize;
+
+        Ok(Self {
+            sq: map(IORING_OFF_SQ_RING, sq_len)?,
+            cq: map(IORING_OFF_CQ_RING, cq_len)?,
+            sqes: map(IORING_OFF_SQES, sqes_len)?,
+            ring,
+            params,
+            unsubmitted: 0,
+            in_flight: 0,
+            error: None,
+            _buffers: PhantomData,
+        })
+    }
+
+    /// Queue reading a range of `buffer` into memory, like
+    /// [`BackedBuffer::prefetch`]. The range is in units of `T`, not in
+    /// bytes.
+    pub fn prefetch<T: Pod>(
+        &mut self,
+        buffer: &'a BackedBuffer<T>,
+        range: Range<usize>,
+    ) -> Result<(), MmapBufferError> {
+        let (offset, len) = buffer.byte_range(range);
+
+        // `madvise` wants a page-aligned start
+        let addr = buffer.mmap.as_ptr() as usize + offset;
+        let start = addr - addr % page_size();
+        for (addr, len) in chunks(start, len + addr - start) {
+            self.push(Sqe {
+                opcode: IORING_OP_MADVISE,
+                addr: addr as u64,
+                len,
+                op_flags: libc::MADV_WILLNEED as u32,
+                ..Sqe::default()
+            })?;
+        }
+
+        Ok(self.submit()?)
+    }
+
+    /// Queue syncing a range of `buffer` to disk, like
+    /// [`BackedBuffer::flush_range`]. `offset` and `len` are in units of
+    /// `T`, not in bytes. Nothing is queued for anonymous buffers, which
+    /// have no file to sync.
+    pub fn flush_range<T: Pod>(
+        &mut self,
+        buffer: &'a BackedBuffer<T>,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), MmapBufferError> {
+        let (offset, len) = buffer.byte_range(offset..offset + len);
+        let Some(file) = &buffer.file else {
+            return Ok(());
+        };
+
+        let start = buffer.file_offset as usize + offset;
+        for (off, len) in chunks(start, len) {
+            self.push(Sqe {
+                opcode: IORING_OP_FSYNC,
+                fd: file.as_raw_fd(),
+                off: off as u64,
+                len,
+                op_flags: IORING_FSYNC_DATASYNC,
+                ..Sqe::default()
+            })?;
+        }
+
+        Ok(self.submit()?)
+    }
+
+    /// Block until every queued operation has completed, returning the
+    /// first error any of them ran into since the last call.
+    pub fn wait(&mut self) -> Result<(), MmapBufferError> {
+        while self.in_flight > 0 {
+            self.enter(1)?;
+            self.reap();
+        }
+
+        match self.error.take() {
+            Some(err) => Err(err.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of queued operations which haven't completed yet
+    pub fn in_flight(&mut self) -> usize {
+        self.reap();
+        self.in_flight as usize
+    }
+
+    /// Write an entry to the submission queue, first making room for it
+    /// and its completion if needed
+    fn push(&mut self, mut sqe: Sqe) -> io::Result<()> {
+        if self.unsubmitted == self.params.sq_entries {
+            self.submit()?;
+        }
+        self.reap();
+        while self.in_flight >= self.params.cq_entries {
+            self.enter(1)?;
+            self.reap();
+        }
+
+        let off = &self.params.sq_off;
+        let tail = self.sq_word(off.tail).load(Ordering::Relaxed);
+        let index = tail & self.sq_word(off.ring_mask).load(Ordering::Relaxed);
+        sqe.user_data = tail as u64;
+
+        // SAFETY: `index` is masked to within the entries and the index
+        // array, which the kernel doesn't read until the tail moves past it
+        unsafe {
+            self.sqes
+                .as_mut_ptr()
+                .cast::<Sqe>()
+                .add(index as usize)
+                .write(sqe);
+            self.sq
+                .as_mut_ptr()
+                .add(off.array as usize)
+                .cast::<u32>()
+                .add(index as usize)
+                .write(index);
+        }
+        self.sq_word(off.tail)
+            .store(tail.wrapping_add(1), Ordering::Release);
+
+        self.unsubmitted += 1;
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    /// Hand every entry written so far to the kernel
+    fn submit(&mut self) -> io::Result<()> {
+        while self.unsubmitted > 0 {
+            self.enter(0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Submit outstanding entries, waiting for at least `min_complete`
+    /// completions
+    fn enter(&mut self, min_complete: u32) -> io::Result<()> {
+        let flags = if min_complete > 0 {
+            IORING_ENTER_GETEVENTS
+        } else {
+            0
+        };
+
+        loop {
+            // SAFETY: no signal mask is passed
+            let submitted = unsafe {
+                libc::syscall(
+                    libc::SYS_io_uring_enter,
+                    self.ring.as_raw_fd(),
+                    self.unsubmitted,
+                    min_complete,
+                    flags,
+                    std::ptr::null::<libc::sigset_t>(),
+                    0usize,
+                )
+            };
+
+            if submitted >= 0 {
+                self.unsubmitted -= submitted as u32;
+                return Ok(());
+            }
+
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
+    /// Consume every completion posted so far, recording the first failure
+    fn reap(&mut self) {
+        let off = &self.params.cq_off;
+        let mut head = self.cq_word(off.head).load(Ordering::Relaxed);
+        let tail = self.cq_word(off.tail).load(Ordering::Acquire);
+        let mask = self.cq_word(off.ring_mask).load(Ordering::Relaxed);
+
+        while head != tail {
+            // SAFETY: the kernel has finished writing entries before the
+            // tail, and `cqes` is masked to within them
+            let cqe = unsafe {
+                self.cq
+                    .as_ptr()
+                    .add(off.cqes as usize)
+                    .cast::<Cqe>()
+                    .add((head & mask) as usize)
+                    .read()
+            };
+            if cqe.res < 0 && self.error.is_none() {
+                self.error = Some(io::Error::from_raw_os_error(-cqe.res));
+            }
+
+            head = head.wrapping_add(1);
+            self.in_flight -= 1;
+        }
+
+        self.cq_word(off.head).store(head, Ordering::Release);
+    }
+
+    fn sq_word(&self, offset: u32) -> &AtomicU32 {
+        // SAFETY: the kernel reports offsets of aligned words in the ring,
+        // shared with it, so only accessed atomically
+        unsafe { &*self.sq.as_ptr().add(offset as usize).cast::<AtomicU32>() }
+    }
+
+    fn cq_word(&self, offset: u32) -> &AtomicU32 {
+        // SAFETY: as for `sq_word`
+        unsafe { &*self.cq.as_ptr().add(offset as usize).cast::<AtomicU32>() }
+    }
+}
+
+impl Drop for IoUring<'_> {
+    fn drop(&mut self) {
+        // Closing the ring doesn't wait for operations to finish, and the
+        // buffers they refer to may go away as soon as it is dropped. If
+        // the ring is leaked instead, the operations only ever hint at or
+        // sync memory, never write to it.
+        while self.in_flight > 0 && self.enter(1).is_ok() {
+            self.reap();
+        }
+    }
+}
+
+/// Split `len` bytes starting at `start` into pieces of at most
+/// [`CHUNK_SIZE`] bytes
+fn chunks(start: usize, len: usize) -> impl Iterator<Item = (usize, u32)> {
+    (start..start + len)
+        .step_by(CHUNK_SIZE)
+        .map(move |chunk| (chunk, usize::min(CHUNK_SIZE, start + len - chunk) as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunks, IoUring, CHUNK_SIZE};
+    use crate::{BackedBuffer, MmapBufferError};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn queued() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        // More operations than fit in the ring at once, on a window whose
+        // mapping starts partway through the file
+        BackedBuffer::<u64>::new(1 << 16, file_path.clone())?;
+        let mut buf = BackedBuffer::<u64>::load_range(&file_path, 1000, (1 << 16) - 1000)?;
+        buf.fill(5);
+        let anonymous = BackedBuffer::<u8>::anonymous(1 << 20)?;
+
+        {
+            // io_uring may be disabled, e.g. by a seccomp filter
+            let mut ring = match IoUring::new(4) {
+                Err(MmapBufferError::Io(err))
+                    if matches!(err.raw_os_error(), Some(libc::ENOSYS | libc::EPERM)) =>
+                {
+                    return Ok(())
+                }
+                ring => ring?,
+            };
+
+            for i in 0..16 {
+                ring.flush_range(&buf, i * 1000, 1000)?;
+                ring.prefetch(&buf, i * 1000..(i + 1) * 1000)?;
+            }
+            ring.wait()?;
+            assert_eq!(ring.in_flight(), 0);
+
+            // Dropping the ring waits for these
+            ring.prefetch(&anonymous, 0..anonymous.len())?;
+            ring.flush_range(&anonymous, 0, anonymous.len())?;
+        }
+        drop(buf);
+
+        let buf = BackedBuffer::<u64>::load(&file_path)?;
+        assert!(buf[..1000].iter().all(|&x| x == 0));
+        assert!(buf[1000..].iter().all(|&x| x == 5));
+
+        assert_eq!(
+            chunks(4096, 2 * CHUNK_SIZE + 1).collect::<Vec<_>>(),
+            [
+                (4096, CHUNK_SIZE as u32),
+                (4096 + CHUNK_SIZE, CHUNK_SIZE as u32),
+                (4096 + 2 * CHUNK_SIZE, 1)
+            ]
+        );
+
+        Ok(())
+    }
+}
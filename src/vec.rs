@@ -0,0 +1,325 @@
+use std::{
+    fs::{File, OpenOptions},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+use bytemuck::{cast_slice, cast_slice_mut, Pod};
+use fs2::FileExt;
+use memmap2::{MmapMut, MmapOptions};
+
+use crate::{byte_size, MmapBufferError};
+
+/// A growable, `Vec`-like buffer of `T` backed by a file.
+///
+/// The logical length is stored in a small header at the start of the
+/// file, so it survives reloading. When the capacity is exceeded the file
+/// is extended and remapped, which invalidates any outstanding slices (the
+/// borrow checker enforces this).
+pub struct BackedVec<T: Pod> {
+    mmap: MmapMut,
+    file: File,
+    _ph: PhantomData<T>,
+}
+
+impl<T: Pod> BackedVec<T> {
+    /// Size of the header in bytes, chosen so the data after it stays
+    /// aligned for `T`
+    const HEADER_SIZE: usize = {
+        let align = std::mem::align_of::<T>();
+        if align > 8 {
+            align
+        } else {
+            8
+        }
+    };
+
+    /// Create a new, empty vector at the given path. Any existing file
+    /// is truncated.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Self::with_capacity(0, path)
+    }
+
+    /// Create a new, empty vector at the given path with room for at
+    /// least `capacity` elements before the file needs to grow. Fails with
+    /// [`MmapBufferError::SizeMismatch`] for zero-sized `T`.
+    pub fn with_capacity(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        Self::check_element_size(0)?;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .create(true)
+            .open(path)?;
+
+        // Only truncate once no one else is using the file
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+        file.set_len(0)?;
+        file.set_len(Self::file_size(capacity)? as u64)?;
+
+        // SAFETY: we hold an exclusive lock on the file
+        let mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+
+        Ok(Self {
+            mmap,
+            file,
+            _ph: PhantomData,
+        })
+    }
+
+    /// Load a vector from an existing path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        file.try_lock_exclusive()
+            .map_err(MmapBufferError::from_lock_error)?;
+
+        let file_size = file.metadata()?.len() as usize;
+        Self::check_element_size(file_size)?;
+        if file_size < Self::HEADER_SIZE {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        if !(file_size - Self::HEADER_SIZE).is_multiple_of(std::mem::size_of::<T>()) {
+            return Err(MmapBufferError::SizeMismatch {
+                file_size,
+                element_size: std::mem::size_of::<T>(),
+            });
+        }
+
+        // SAFETY: we hold an exclusive lock on the file
+        let mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+        let vec = Self {
+            mmap,
+            file,
+            _ph: PhantomData,
+        };
+
+        if vec.len() > vec.capacity() {
+            return Err(MmapBufferError::InvalidHeader);
+        }
+
+        Ok(vec)
+    }
+
+    /// Number of elements in the vector
+    pub fn len(&self) -> usize {
+        u64::from_ne_bytes(self.mmap[..8].try_into().unwrap()) as usize
+    }
+
+    /// Returns `true` if the vector contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of elements the vector can hold without growing the file
+    pub fn capacity(&self) -> usize {
+        (self.mmap.len() - Self::HEADER_SIZE) / std::mem::size_of::<T>()
+    }
+
+    /// Append an element to the back of the vector, growing the file if
+    /// necessary.
+    pub fn push(&mut self, value: T) -> Result<(), MmapBufferError> {
+        let len = self.len();
+        self.reserve(1)?;
+        self.set_len(len + 1);
+        self[len] = value;
+
+        Ok(())
+    }
+
+    /// Remove the last element and return it, or `None` if empty. Doesn't
+    /// shrink the file.
+    pub fn pop(&mut self) -> Option<T> {
+        let len = self.len().checked_sub(1)?;
+        let value = self[len];
+        self.set_len(len);
+
+        Some(value)
+    }
+
+    /// Append all elements of the slice, growing the file at most once.
+    pub fn extend_from_slice(&mut self, slice: &[T]) -> Result<(), MmapBufferError> {
+        let len = self.len();
+        self.reserve(slice.len())?;
+        self.set_len(len + slice.len());
+        self[len..].copy_from_slice(slice);
+
+        Ok(())
+    }
+
+    /// Set the length to zero. Doesn't shrink the file.
+    pub fn clear(&mut self) {
+        self.set_len(0);
+    }
+
+    /// Synchronously write any outstanding changes, including the length,
+    /// back to the file.
+    pub fn flush(&self) -> Result<(), MmapBufferError> {
+        Ok(self.mmap.flush()?)
+    }
+
+    /// Ensure there is room for at least `additional` more elements,
+    /// growing the file geometrically if needed.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), MmapBufferError> {
+        let required = self
+            .len()
+            .checked_add(additional)
+            .ok_or(MmapBufferError::CapacityOverflow)?;
+        if required <= self.capacity() {
+            return Ok(());
+        }
+
+        let new_capacity = usize::max(required, usize::max(2 * self.capacity(), 8));
+        let file_size = Self::file_size(new_capacity).or_else(|_| Self::file_size(required))?;
+        self.file.set_len(file_size as u64)?;
+
+        // SAFETY: we hold an exclusive lock on the file, and the old mapping
+        // is dropped once replaced
+        self.mmap = unsafe { MmapOptions::new().map_mut(&self.file)? };
+
+        Ok(())
+    }
+
+    /// Ensure there is room for at least `additional` more elements,
+    /// growing the file by exactly as much as needed.
+    pub fn reserve_exact(&mut self, additional: usize) -> Result<(), MmapBufferError> {
+        let required = self
+            .len()
+            .checked_add(additional)
+            .ok_or(MmapBufferError::CapacityOverflow)?;
+        if required <= self.capacity() {
+            return Ok(());
+        }
+
+        self.file.set_len(Self::file_size(required)? as u64)?;
+
+        // SAFETY: we hold an exclusive lock on the file, and the old mapping
+        // is dropped once replaced
+        self.mmap = unsafe { MmapOptions::new().map_mut(&self.file)? };
+
+        Ok(())
+    }
+
+    fn set_len(&mut self, len: usize) {
+        self.mmap[..8].copy_from_slice(&(len as u64).to_ne_bytes());
+    }
+
+    /// Zero-sized elements take up no room in the file, so there is no
+    /// way to tell how many of them it can hold
+    fn check_element_size(file_size: usize) -> Result<(), MmapBufferError> {
+        match std::mem::size_of::<T>() {
+            0 => Err(MmapBufferError::SizeMismatch {
+                file_size,
+                element_size: 0,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn file_size(capacity: usize) -> Result<usize, MmapBufferError> {
+        byte_size::<T>(Self::HEADER_SIZE, capacity)
+    }
+}
+
+impl<T: Pod> Deref for BackedVec<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        let len = self.len();
+        &cast_slice(&self.mmap[Self::HEADER_SIZE..])[..len]
+    }
+}
+
+impl<T: Pod> DerefMut for BackedVec<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let len = self.len();
+        &mut cast_slice_mut(&mut self.mmap[Self::HEADER_SIZE..])[..len]
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for BackedVec<T> {
+    fn as_ref(&self) -> &[T] {
+        self.deref()
+    }
+}
+
+impl<T: Pod> AsMut<[T]> for BackedVec<T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self.deref_mut()
+    }
+}
+
+impl<T: Pod> Drop for BackedVec<T> {
+    fn drop(&mut self) {
+        // Ignore the error, advisory locks are still kind of sus
+        self.file.unlock().unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BackedVec;
+    use crate::MmapBufferError;
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn push_pop() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut vec = BackedVec::<u32>::new(file_path)?;
+        for i in 0..100 {
+            vec.push(i)?;
+        }
+
+        assert_eq!(vec.len(), 100);
+        assert!(vec.capacity() >= 100);
+        assert_eq!(vec.pop(), Some(99));
+        assert_eq!(vec[..3], [0, 1, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reload() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        {
+            let mut vec = BackedVec::<u64>::with_capacity(2, file_path.clone())?;
+            vec.extend_from_slice(&[1, 2, 3, 4, 5])?;
+            vec.pop();
+        }
+
+        let vec = BackedVec::<u64>::load(file_path.clone())?;
+        assert_eq!(&vec[..], &[1, 2, 3, 4]);
+
+        // Recreating a vector in use fails without wiping it
+        let err = BackedVec::<u64>::new(file_path).err().unwrap();
+        assert!(matches!(err, MmapBufferError::Locked));
+        assert_eq!(&vec[..], &[1, 2, 3, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn zero_sized() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let err = BackedVec::<()>::new(file_path.clone()).err().unwrap();
+        assert!(matches!(err, MmapBufferError::SizeMismatch { .. }));
+
+        BackedVec::<u64>::new(file_path.clone())?;
+        let err = BackedVec::<()>::load(file_path).err().unwrap();
+        assert!(matches!(err, MmapBufferError::SizeMismatch { .. }));
+
+        Ok(())
+    }
+}
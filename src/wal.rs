@@ -0,0 +1,193 @@
+use std::{ops::Deref, path::Path};
+
+use bytemuck::Pod;
+
+use crate::{checksum::crc32, BackedBuffer, BackedVec, MmapBufferError};
+
+/// Size of the fixed part of a log record: element index, element count
+/// and checksum of the payload
+const RECORD_HEADER_SIZE: usize = 8 + 8 + 4;
+
+/// A [`BackedBuffer`] with a write-ahead log.
+///
+/// Every write is first appended to a memory-mapped `<path>.wal` log and
+/// flushed, and only then applied to the main buffer. The log is cleared
+/// at each [`checkpoint`](Self::checkpoint), which happens automatically
+/// when the buffer is dropped. After an unclean shutdown, [`load`](Self::load)
+/// replays whatever complete records the log still holds, so no write which
+/// returned successfully is ever lost.
+pub struct WalBuffer<T: Pod> {
+    buffer: BackedBuffer<T>,
+    log: BackedVec<u8>,
+}
+
+impl<T: Pod> WalBuffer<T> {
+    /// Create a new buffer at the given path with a fixed capacity.
+    /// This capacity is in units of `T`, not in bytes
+    pub fn new(capacity: usize, path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let path = path.as_ref();
+        let buffer = BackedBuffer::new(capacity, path)?;
+        let log = BackedVec::new(log_path(path))?;
+
+        Ok(Self { buffer, log })
+    }
+
+    /// Load a buffer from an existing path, replaying the log if the
+    /// previous session didn't shut down cleanly.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MmapBufferError> {
+        let path = path.as_ref();
+        let buffer = BackedBuffer::load(path)?;
+        let log = match BackedVec::load(log_path(path)) {
+            Ok(log) => log,
+            Err(MmapBufferError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                BackedVec::new(log_path(path))?
+            }
+            Err(err) => return Err(err),
+        };
+
+        let mut wal = Self { buffer, log };
+        wal.replay()?;
+        wal.checkpoint()?;
+
+        Ok(wal)
+    }
+
+    /// Durably log `values`, then write them starting at element `index`.
+    pub fn write(&mut self, index: usize, values: &[T]) -> Result<(), MmapBufferError> {
+        assert!(
+            index + values.len() <= self.len(),
+            "range must be within the buffer!"
+        );
+
+        let payload: &[u8] = bytemuck::cast_slice(values);
+        self.log.extend_from_slice(&(index as u64).to_le_bytes())?;
+        self.log
+            .extend_from_slice(&(values.len() as u64).to_le_bytes())?;
+        self.log.extend_from_slice(&crc32(payload).to_le_bytes())?;
+        self.log.extend_from_slice(payload)?;
+        self.log.flush()?;
+
+        self.buffer[index..index + values.len()].copy_from_slice(values);
+        Ok(())
+    }
+
+    /// Flush the main buffer and clear the log, since everything it
+    /// records is now durable.
+    pub fn checkpoint(&mut self) -> Result<(), MmapBufferError> {
+        self.buffer.flush()?;
+        self.log.clear();
+        self.log.flush()
+    }
+
+    /// Number of bytes currently held in the log
+    pub fn log_len(&self) -> usize {
+        self.log.len()
+    }
+
+    /// Apply every complete record in the log to the main buffer, stopping
+    /// at the first torn one
+    fn replay(&mut self) -> Result<(), MmapBufferError> {
+        let size = std::mem::size_of::<T>();
+        let mut records = &self.log[..];
+
+        while let Some((header, rest)) = records.split_first_chunk::<RECORD_HEADER_SIZE>() {
+            let index = u64::from_le_bytes(header[..8].try_into().unwrap()) as usize;
+            let count = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+            let checksum = u32::from_le_bytes(header[16..].try_into().unwrap());
+
+            let Some(payload) = count.checked_mul(size).and_then(|bytes| rest.get(..bytes)) else {
+                break;
+            };
+
+            let in_bounds = index
+                .checked_add(count)
+                .is_some_and(|end| end <= self.buffer.len());
+            if crc32(payload) != checksum || !in_bounds {
+                break;
+            }
+
+            bytemuck::cast_slice_mut(&mut self.buffer[index..index + count])
+                .copy_from_slice(payload);
+            records = &rest[count * size..];
+        }
+
+        Ok(())
+    }
+}
+
+fn log_path(path: &Path) -> std::path::PathBuf {
+    let mut log = path.as_os_str().to_owned();
+    log.push(".wal");
+    log.into()
+}
+
+impl<T: Pod> Deref for WalBuffer<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.buffer.deref()
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for WalBuffer<T> {
+    fn as_ref(&self) -> &[T] {
+        self.deref()
+    }
+}
+
+impl<T: Pod> Drop for WalBuffer<T> {
+    fn drop(&mut self) {
+        // On failure the log is simply replayed on the next load
+        self.checkpoint().unwrap_or(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{log_path, WalBuffer, RECORD_HEADER_SIZE};
+    use crate::{BackedBuffer, BackedVec};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn write_checkpoint() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut buf = WalBuffer::<u32>::new(16, file_path.clone())?;
+        buf.write(2, &[1, 2, 3])?;
+        assert_eq!(buf[2..5], [1, 2, 3]);
+        assert!(buf.log_len() > 0);
+
+        buf.checkpoint()?;
+        assert_eq!(buf.log_len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_after_crash() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        // Simulate a crash after logging, before the main buffer was written
+        let mut buf = WalBuffer::<u32>::new(16, file_path.clone())?;
+        buf.write(0, &[7, 8])?;
+        let log = std::fs::read(log_path(&file_path))?;
+        let record = log[8..8 + RECORD_HEADER_SIZE + 8].to_vec();
+        drop(buf);
+
+        // Followed by a torn record
+        BackedBuffer::<u32>::load(file_path.clone())?.fill(0);
+        let mut log = BackedVec::<u8>::new(log_path(&file_path))?;
+        log.extend_from_slice(&record)?;
+        log.extend_from_slice(&record[..record.len() - 1])?;
+        drop(log);
+
+        let buf = WalBuffer::<u32>::load(file_path)?;
+        assert_eq!(buf[..3], [7, 8, 0]);
+        assert_eq!(buf.log_len(), 0);
+
+        Ok(())
+    }
+}
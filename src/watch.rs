@@ -0,0 +1,304 @@
+#[cfg(target_os = "linux")]
+use std::{
+    ffi::CString,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+use std::{
+    fs::File,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError};
+
+/// A change to the file backing a buffer, see [`BackedBuffer::watch`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileEvent {
+    /// The contents changed, but not the size
+    Modified,
+    /// The file grew to the given size in bytes
+    Grown(u64),
+    /// The file shrunk to the given size in bytes
+    Truncated(u64),
+}
+
+/// Receives [`FileEvent`]s for the file backing a buffer, and stops
+/// watching it when dropped. See [`BackedBuffer::watch`].
+#[derive(Debug)]
+pub struct FileWatcher {
+    events: Receiver<FileEvent>,
+    signal: Arc<Signal>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Watch the backing file for changes made by other processes, e.g. to
+    /// know when to [`remap`](Self::remap) the buffer or verify its
+    /// checksum. Fails for anonymous buffers.
+    ///
+    /// On Linux, inotify reports changes in size, and writes made through
+    /// `write(2)`, as they happen. Writes through a mapping raise no inotify
+    /// events though, and only update the modification time once they are
+    /// flushed, or once the kernel writes them back on its own, so the size
+    /// and modification time are checked every `interval` as well, and
+    /// [`FileEvent::Modified`] can arrive late. Elsewhere, or if inotify
+    /// isn't available, only the periodic check is made.
+    pub fn watch(&self, interval: Duration) -> Result<FileWatcher, MmapBufferError> {
+        let file = self.file.as_ref().ok_or_else(|| {
+            MmapBufferError::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "anonymous buffers have no file to watch",
+            ))
+        })?;
+
+        // Follows the file even if it is renamed
+        let file = file.try_clone()?;
+        let mut last = stat(&file)?;
+
+        let (sender, events) = mpsc::channel();
+        let signal = Arc::new(Signal {
+            stop: AtomicBool::new(false),
+            #[cfg(target_os = "linux")]
+            inotify: Inotify::new(&file).ok(),
+        });
+        let signalled = signal.clone();
+
+        let thread = std::thread::spawn(move || loop {
+            signalled.wait(interval);
+            if signalled.stop.load(Ordering::Acquire) {
+                return;
+            }
+
+            // Give up once the file can't be inspected anymore
+            let Ok(current) = stat(&file) else {
+                return;
+            };
+
+            let event = if current.0 > last.0 {
+                FileEvent::Grown(current.0)
+            } else if current.0 < last.0 {
+                FileEvent::Truncated(current.0)
+            } else if current.1 != last.1 {
+                FileEvent::Modified
+            } else {
+                continue;
+            };
+
+            last = current;
+            if sender.send(event).is_err() {
+                return;
+            }
+        });
+
+        Ok(FileWatcher {
+            events,
+            signal,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Size and modification time of a file
+fn stat(file: &File) -> io::Result<(u64, SystemTime)> {
+    let metadata = file.metadata()?;
+    Ok((metadata.len(), metadata.modified()?))
+}
+
+/// Wakes the watching thread when the file may have changed, or when it
+/// should stop
+#[derive(Debug)]
+struct Signal {
+    stop: AtomicBool,
+    #[cfg(target_os = "linux")]
+    inotify: Option<Inotify>,
+}
+
+impl Signal {
+    /// Wait for at most `timeout`, or until woken early
+    fn wait(&self, timeout: Duration) {
+        #[cfg(target_os = "linux")]
+        if let Some(inotify) = &self.inotify {
+            return inotify.wait(timeout);
+        }
+
+        std::thread::park_timeout(timeout);
+    }
+
+    /// Stop the watching thread, which should be `thread`, and wait for it
+    /// to finish
+    fn stop(&self, thread: JoinHandle<()>) {
+        self.stop.store(true, Ordering::Release);
+
+        #[cfg(target_os = "linux")]
+        if let Some(inotify) = &self.inotify {
+            inotify.wake();
+        }
+        thread.thread().unpark();
+
+        // The thread only ever blocks in `wait`, so this is quick
+        thread.join().unwrap_or(());
+    }
+}
+
+/// An inotify instance watching a single file, together with an eventfd
+/// for waking up whoever is waiting on it
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+struct Inotify {
+    inotify: OwnedFd,
+    wake: OwnedFd,
+}
+
+#[cfg(target_os = "linux")]
+impl Inotify {
+    fn new(file: &File) -> io::Result<Self> {
+        // SAFETY: neither call takes pointers
+        let inotify =
+            owned_fd(unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) })?;
+        let wake = owned_fd(unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) })?;
+
+        // Watches whatever the descriptor refers to, wherever it was moved
+        let path = CString::new(format!("/proc/self/fd/{}", file.as_raw_fd()))?;
+        let mask = libc::IN_MODIFY | libc::IN_ATTRIB | libc::IN_CLOSE_WRITE;
+
+        // SAFETY: `path` is a valid null-terminated string
+        if unsafe { libc::inotify_add_watch(inotify.as_raw_fd(), path.as_ptr(), mask) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { inotify, wake })
+    }
+
+    /// Wait for at most `timeout`, or until the file sees an event or
+    /// [`wake`](Self::wake) is called
+    fn wait(&self, timeout: Duration) {
+        let mut fds = [self.inotify.as_raw_fd(), self.wake.as_raw_fd()].map(|fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        });
+        let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+
+        // SAFETY: `fds` is valid for its length. Errors, like being
+        // interrupted, just mean checking the file early.
+        unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+
+        // Only the state of the file matters, not which events led to it
+        let mut events = [0u8; 4096];
+        // SAFETY: `events` is valid for its length
+        while unsafe {
+            libc::read(
+                self.inotify.as_raw_fd(),
+                events.as_mut_ptr().cast(),
+                events.len(),
+            )
+        } > 0
+        {}
+    }
+
+    fn wake(&self) {
+        let one = 1u64.to_ne_bytes();
+
+        // SAFETY: an eventfd takes 8 byte writes
+        unsafe { libc::write(self.wake.as_raw_fd(), one.as_ptr().cast(), one.len()) };
+    }
+}
+
+/// Take ownership of a descriptor returned by a system call, or its error
+#[cfg(target_os = "linux")]
+fn owned_fd(fd: libc::c_int) -> io::Result<OwnedFd> {
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `fd` is a freshly opened descriptor which nothing else owns
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+impl FileWatcher {
+    /// Wait for the next change, or `None` if the file can't be watched
+    /// anymore
+    pub fn recv(&self) -> Option<FileEvent> {
+        self.events.recv().ok()
+    }
+
+    /// The next change, if one already happened
+    pub fn try_recv(&self) -> Option<FileEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Wait for the next change for at most `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<FileEvent> {
+        self.events.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.signal.stop(thread);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileEvent;
+    use crate::{BackedBuffer, BackedBufferBuilder, LockMode};
+    use std::{
+        error::Error,
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn watch() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options.lock(LockMode::Shared);
+        let mut writer = options.create::<u64>(16, file_path.clone())?;
+        let reader = options.load::<u64>(file_path)?;
+
+        let watcher = reader.watch(Duration::from_millis(5))?;
+        assert_eq!(watcher.try_recv(), None);
+
+        let timeout = Duration::from_secs(10);
+        writer.resize(32)?;
+        assert_eq!(watcher.recv_timeout(timeout), Some(FileEvent::Grown(256)));
+        writer.resize(8)?;
+        assert_eq!(
+            watcher.recv_timeout(timeout),
+            Some(FileEvent::Truncated(64))
+        );
+
+        assert!(BackedBuffer::<u64>::anonymous(8)?
+            .watch(Duration::from_millis(5))
+            .is_err());
+
+        // Dropping stops the thread right away, rather than after an interval
+        let watcher = reader.watch(Duration::from_secs(3600))?;
+        let start = Instant::now();
+        drop(watcher);
+        assert!(start.elapsed() < Duration::from_secs(60));
+
+        // Size changes don't have to wait for the next check
+        #[cfg(target_os = "linux")]
+        {
+            let watcher = reader.watch(Duration::from_secs(3600))?;
+            writer.resize(16)?;
+            assert_eq!(watcher.recv_timeout(timeout), Some(FileEvent::Grown(128)));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use bytemuck::Pod;
+
+use crate::{BackedBuffer, MmapBufferError, ZeroizeOnDrop};
+
+impl<T: Pod> BackedBuffer<T> {
+    /// Overwrite every element, including any hidden by
+    /// [`shrink`](Self::shrink), with zeroes in a way the compiler can't
+    /// optimize out, and flush them to the file. Unlike
+    /// [`fill`](slice::fill), this is guaranteed to happen even if the
+    /// buffer is never read again, so it is suitable for wiping key
+    /// material.
+    pub fn zeroize(&mut self) -> Result<(), MmapBufferError> {
+        self.mark_dirty();
+        wipe(&mut self.mmap[self.offset..]);
+
+        if !self.copy_on_write {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Wipe the buffer as configured by
+    /// [`BackedBufferBuilder::zeroize_on_drop`](crate::BackedBufferBuilder::zeroize_on_drop)
+    pub(crate) fn zeroize_on_drop(&mut self) -> Result<(), MmapBufferError> {
+        if self.zeroize_on_drop == ZeroizeOnDrop::None {
+            return Ok(());
+        }
+
+        self.zeroize()?;
+
+        #[cfg(target_os = "linux")]
+        if self.zeroize_on_drop == ZeroizeOnDrop::WipeAndPunchHoles && !self.copy_on_write {
+            if let Some(file) = &self.file {
+                let start = self.file_offset + self.offset as u64;
+                let len = (self.mmap.len() - self.offset) as u64;
+                crate::sparse::punch_file(file, start, len)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Zero `bytes`, making sure the writes aren't elided
+fn wipe(bytes: &mut [u8]) {
+    bytes.fill(0);
+
+    // Acts as though the zeroes were read, so they have to be written
+    std::hint::black_box(&*bytes);
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BackedBuffer, BackedBufferBuilder, ZeroizeOnDrop};
+    use std::{error::Error, path::Path};
+
+    #[test]
+    fn zeroize_on_drop() -> Result<(), Box<dyn Error>> {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = Path::join(tempdir.path(), "test");
+
+        let mut options = BackedBufferBuilder::new();
+        options
+            .header(true)
+            .zeroize_on_drop(ZeroizeOnDrop::WipeAndPunchHoles);
+        let mut buf = options.create::<u8>(1 << 16, file_path.clone())?;
+        buf.fill(0xaa);
+        buf.flush()?;
+        drop(buf);
+
+        // The header survives, so the file still loads
+        let buf = BackedBuffer::<u8>::load_with_header(&file_path)?;
+        assert!(buf.iter().all(|&byte| byte == 0));
+        drop(buf);
+
+        let mut buf = BackedBuffer::<u8>::load_with_header(&file_path)?;
+        buf.fill(0xaa);
+        buf.zeroize()?;
+        assert!(buf.iter().all(|&byte| byte == 0));
+
+        Ok(())
+    }
+}